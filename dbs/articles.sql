@@ -0,0 +1,17 @@
+PRAGMA page_size = 1024;
+
+CREATE TABLE articles (
+    id integer primary key autoincrement,
+    title text,
+    body text
+);
+
+CREATE INDEX idx_articles_body ON articles(body);
+
+-- Bodies longer than a page spill onto overflow pages
+INSERT INTO articles (title, body)
+VALUES
+    ('short',       'fits on the page'),
+    ('medium',      'medium ' || replace(hex(zeroblob(500)), '00', 'ab')),
+    ('long',        'long ' || replace(hex(zeroblob(2500)), '00', 'cd')),
+    ('very long',   'very long ' || replace(hex(zeroblob(10000)), '00', 'ef'));
@@ -0,0 +1,9 @@
+#!/bin/sh
+
+FLDR=dbs
+DB=birds
+
+rm -f $FLDR/$DB.db $FLDR/$DB.db-wal
+sqlite3 $FLDR/$DB.db < $FLDR/$DB.sql
+mv $FLDR/$DB.db.tmp $FLDR/$DB.db
+mv $FLDR/$DB.db-wal.tmp $FLDR/$DB.db-wal
@@ -0,0 +1,30 @@
+PRAGMA page_size = 1024;
+
+CREATE TABLE birds (
+    id integer primary key autoincrement,
+    name text,
+    wingspan integer
+);
+
+CREATE INDEX idx_birds_name ON birds(name);
+
+INSERT INTO birds (name, wingspan)
+VALUES
+    ('Robin', 22),
+    ('Sparrow', 21),
+    ('Heron', 180);
+
+-- Everything below only ends up in the -wal file,
+-- which is copied aside before sqlite3 checkpoints it on exit
+PRAGMA journal_mode = WAL;
+PRAGMA wal_autocheckpoint = 0;
+
+UPDATE birds SET wingspan = 185 WHERE name = 'Heron';
+DELETE FROM birds WHERE name = 'Sparrow';
+
+-- Enough rows to split the table's root page
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+INSERT INTO birds (name, wingspan) SELECT 'Albatross ' || i, 300 + i FROM n;
+
+.shell cp dbs/birds.db dbs/birds.db.tmp
+.shell cp dbs/birds.db-wal dbs/birds.db-wal.tmp
@@ -0,0 +1,7 @@
+#!/bin/sh
+
+FLDR=dbs
+DB=collations
+
+rm $FLDR/$DB.db
+sqlite3 $FLDR/$DB.db < $FLDR/$DB.sql
@@ -0,0 +1,26 @@
+CREATE TABLE users (
+    id integer primary key,
+    name text collate nocase,
+    city text collate rtrim,
+    code text
+);
+
+CREATE TABLE logins (
+    user_name text,
+    day integer
+);
+
+CREATE INDEX idx_users_name ON users (name);
+
+INSERT INTO users (name, city, code)
+VALUES
+    ('alice', 'Oslo',   'a'),
+    ('Bob',   'Oslo  ', 'B'),
+    ('ALICE', 'Rome',   'A'),
+    ('carol', 'Rome ',  'c');
+
+INSERT INTO logins (user_name, day)
+VALUES
+    ('Alice', 1),
+    ('BOB',   2),
+    ('bob',   3);
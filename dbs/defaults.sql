@@ -0,0 +1,27 @@
+CREATE TABLE tasks (
+    id integer primary key,
+    title text not null,
+    status text not null default 'open',
+    priority integer default (1 + 1),
+    score real default -1,
+    note text
+);
+
+INSERT INTO tasks (title) VALUES ('write tests');
+INSERT INTO tasks (title, status, priority, score, note) VALUES ('ship', 'done', 5, 2.5, 'late');
+
+-- The rows above are stored without the added columns
+ALTER TABLE tasks ADD COLUMN estimate integer default 8;
+ALTER TABLE tasks ADD COLUMN tag text;
+
+-- Virtual generated columns are not stored, stored ones are
+CREATE TABLE boxes (
+    id integer primary key,
+    width real,
+    area real generated always as (width * height) virtual,
+    height real,
+    label text as (upper(name)) stored,
+    name text
+);
+
+INSERT INTO boxes (width, height, name) VALUES (2, 3, 'small'), (1.5, 4, 'tall');
@@ -0,0 +1,50 @@
+CREATE TABLE members (
+    id integer primary key,
+    email text,
+    team text,
+    nick text
+);
+
+CREATE TABLE scores (
+    player text,
+    points integer
+);
+
+CREATE TABLE tags (
+    name text
+);
+
+CREATE TABLE notes (
+    title text,
+    stars integer
+);
+
+CREATE UNIQUE INDEX idx_members_email ON members (email);
+CREATE UNIQUE INDEX idx_members_team_nick ON members (team, nick);
+CREATE INDEX idx_scores_points ON scores (points DESC);
+CREATE INDEX idx_tags_name ON tags (name COLLATE NOCASE);
+CREATE INDEX idx_notes_title ON notes (lower(title));
+CREATE INDEX idx_notes_stars ON notes (stars) WHERE stars > 0;
+
+INSERT INTO members (email, team, nick)
+VALUES
+    ('ann@example.com', 'red',  'ann'),
+    ('bo@example.com',  'red',  'bo'),
+    ('cy@example.com',  'blue', 'ann'),
+    (NULL,              'blue', NULL);
+
+INSERT INTO scores (player, points)
+VALUES
+    ('ann', 10),
+    ('bo',  30),
+    ('cy',  20);
+
+INSERT INTO tags (name)
+VALUES
+    ('Rust'),
+    ('sql');
+
+INSERT INTO notes (title, stars)
+VALUES
+    ('Draft', 0),
+    ('Plan',  2);
@@ -0,0 +1,7 @@
+#!/bin/sh
+
+FLDR=dbs
+DB=keys
+
+rm $FLDR/$DB.db
+sqlite3 $FLDR/$DB.db < $FLDR/$DB.sql
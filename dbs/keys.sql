@@ -0,0 +1,40 @@
+CREATE TABLE desc_ids (
+    id integer primary key desc,
+    name text
+);
+
+CREATE TABLE table_ids (
+    id integer,
+    name text,
+    primary key (id desc)
+);
+
+CREATE TABLE int_ids (
+    id int primary key,
+    name text
+);
+
+CREATE TABLE users (
+    name text primary key,
+    age integer
+) WITHOUT ROWID;
+
+CREATE INDEX idx_users_age ON users (age);
+
+CREATE TABLE scores (
+    player text,
+    round integer,
+    points real,
+    primary key (round, player)
+) WITHOUT ROWID;
+
+INSERT INTO desc_ids (id, name) VALUES (20, 'twenty'), (10, 'ten');
+INSERT INTO table_ids (id, name) VALUES (20, 'twenty'), (10, 'ten');
+INSERT INTO int_ids (id, name) VALUES (20, 'twenty'), (10, 'ten');
+INSERT INTO users (name, age) VALUES ('carol', 41), ('alice', 30), ('bob', 30);
+INSERT INTO scores (player, round, points)
+VALUES
+    ('bob',   2, 7.5),
+    ('alice', 1, 9.25),
+    ('bob',   1, 6.25),
+    ('alice', 2, 8.5);
@@ -0,0 +1,7 @@
+#!/bin/sh
+
+FLDR=dbs
+DB=numbers
+
+rm $FLDR/$DB.db
+sqlite3 $FLDR/$DB.db < $FLDR/$DB.sql
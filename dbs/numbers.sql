@@ -0,0 +1,13 @@
+CREATE TABLE t (
+    id integer primary key,
+    n numeric,
+    r real
+);
+
+-- n alternates between whole and half numbers, r repeats 0 to 49
+WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 2000)
+INSERT INTO t (n, r)
+SELECT CASE WHEN x % 2 = 0 THEN x / 2 ELSE x / 2.0 END, x % 50 FROM c;
+
+CREATE INDEX idx_t_n ON t (n);
+CREATE INDEX idx_t_r ON t (r);
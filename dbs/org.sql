@@ -0,0 +1,31 @@
+CREATE TABLE staff (
+    id integer primary key,
+    name text,
+    manager_id integer
+);
+
+CREATE TABLE categories (
+    id integer primary key,
+    name text,
+    parent_id integer
+);
+
+INSERT INTO staff (name, manager_id)
+VALUES
+    ('Grace',   NULL),
+    ('Alan',    1),
+    ('Barbara', 1),
+    ('Ken',     2),
+    ('Linus',   2),
+    ('Margaret', 3),
+    ('Dennis',  4);
+
+INSERT INTO categories (name, parent_id)
+VALUES
+    ('Books',      NULL),
+    ('Fiction',    1),
+    ('Science',    1),
+    ('Physics',    3),
+    ('Quantum',    4),
+    ('Music',      NULL),
+    ('Jazz',       6);
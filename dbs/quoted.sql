@@ -0,0 +1,27 @@
+CREATE TABLE "Order Items" (
+    "Item ID" integer primary key,
+    [item name] text,
+    `unit price` real,
+    "qty""s" integer
+);
+
+CREATE INDEX "idx order items name" ON "Order Items" ([item name]);
+
+INSERT INTO "Order Items" ([item name], `unit price`, "qty""s")
+VALUES
+    ('Pen',   1.5,  10),
+    ('Paper', 4.25, 2),
+    ('Ink',   9.99, 1);
+
+-- Comments are kept in the schema, type names may be quoted
+CREATE TABLE codes (
+    "id" "INTEGER" PRIMARY KEY, -- the rowid
+    /* a code like
+       'A-1' */
+    code 'text' /* not a number */ NOT NULL
+);
+
+INSERT INTO codes (id, code) VALUES (7, 42), (9, 'B-2');
+
+-- Virtual tables cannot be read
+CREATE VIRTUAL TABLE notes USING fts5(body);
@@ -0,0 +1,32 @@
+CREATE TABLE customers (
+    id integer primary key autoincrement,
+    name text,
+    first_order_id integer,
+    last_code text
+);
+
+CREATE TABLE orders (
+    id integer primary key autoincrement,
+    customer_id integer,
+    code text,
+    amount integer
+);
+
+CREATE INDEX idx_orders_customer_id ON orders(customer_id);
+CREATE INDEX idx_orders_code ON orders(code);
+
+-- Customer 5 has no orders
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2000)
+INSERT INTO orders (customer_id, code, amount)
+SELECT (i % 4) + 1, printf('O%04d', i), (i * 37) % 100 FROM n;
+
+INSERT INTO customers (name, first_order_id, last_code)
+VALUES
+    ('Ada',     4,    'O2000'),
+    ('Grace',   1,    'O1997'),
+    ('Linus',   2,    'O1998'),
+    ('Ken',     3,    'O1999'),
+    ('Barbara', NULL, NULL);
+
+-- The planner reads the row counts from sqlite_stat1
+ANALYZE;
@@ -0,0 +1,37 @@
+CREATE TABLE employees (
+    id integer primary key,
+    name text,
+    dept text,
+    salary integer
+);
+
+CREATE TABLE audit (
+    employee_id integer,
+    note text
+);
+
+CREATE VIEW engineers AS
+SELECT id, name, salary FROM employees WHERE dept = 'eng';
+
+CREATE VIEW well_paid (who, yearly) AS
+SELECT name, salary * 12 FROM engineers WHERE salary > 5000;
+
+CREATE VIEW dept_totals AS
+SELECT dept, count(*) AS headcount, sum(salary) FROM employees GROUP BY dept;
+
+CREATE TRIGGER employees_audit AFTER UPDATE ON employees
+BEGIN
+    INSERT INTO audit (employee_id, note) VALUES (new.id, 'updated');
+END;
+
+INSERT INTO employees (name, dept, salary)
+VALUES
+    ('Ada',    'eng',   7000),
+    ('Brian',  'sales', 4000),
+    ('Chen',   'eng',   5000),
+    ('Dana',   'eng',   9000),
+    ('Eve',    'sales', 6000);
+
+-- A view may combine two SELECTs by UNION
+CREATE VIEW names AS
+SELECT name FROM employees UNION SELECT note FROM audit;
@@ -15,14 +15,20 @@ fn add(num: i64, byte: u8) -> i64 {
 pub fn parse_varint(encoded: &mut &[u8]) -> i64 {
     let mut decoded: i64 = 0;
 
-    for _ in 0..9 {
-        if encoded.len() == 0 {
+    for i in 0..9 {
+        if encoded.is_empty() {
             panic!("invalid varint");
         }
 
         let byte = encoded[0];
         *encoded = &encoded[1..];
 
+        // The ninth byte contributes all 8 of its bits
+        if i == 8 {
+            decoded = (decoded << 8) | byte as i64;
+            break;
+        }
+
         decoded = add(decoded, byte);
 
         if !is_high_order_bit_set(byte) {
@@ -30,7 +36,7 @@ pub fn parse_varint(encoded: &mut &[u8]) -> i64 {
         }
     }
 
-    return decoded;
+    decoded
 }
 
 pub fn parse_varints(window: &[u8]) -> Vec<i64> {
@@ -38,9 +44,63 @@ pub fn parse_varints(window: &[u8]) -> Vec<i64> {
     let window = &mut window;
 
     let mut decoded_varints = vec![];
-    while window.len() > 0 {
+    while !window.is_empty() {
         let decoded = parse_varint(window);
         decoded_varints.push(decoded);
     }
     decoded_varints
 }
+
+pub fn encode_varint(value: i64) -> Vec<u8> {
+    let value = value as u64;
+
+    // The ninth byte contributes all 8 of its bits
+    if value >> 56 != 0 {
+        let mut encoded: Vec<_> = (0..8)
+            .map(|i| ((value >> (57 - 7 * i)) & 0x7f) as u8 | 0x80)
+            .collect();
+        encoded.push(value as u8);
+        return encoded;
+    }
+
+    let mut encoded = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest != 0 {
+        encoded.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    encoded.reverse();
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_varint, parse_varint};
+
+    #[test]
+    fn encode_varint_roundtrip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            240,
+            2287,
+            16383,
+            16384,
+            1 << 56,
+            -1,
+            i64::MIN,
+        ] {
+            let encoded = encode_varint(value);
+            assert_eq!(parse_varint(&mut &encoded[..]), value);
+        }
+    }
+
+    #[test]
+    fn encode_varint_len() {
+        assert_eq!(encode_varint(127), [0x7f]);
+        assert_eq!(encode_varint(128), [0x81, 0x00]);
+        assert_eq!(encode_varint(-1).len(), 9);
+    }
+}
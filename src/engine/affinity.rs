@@ -0,0 +1,338 @@
+use super::{printf::format_general, Value};
+
+/// The preferred storage class of the values of a column.
+/// https://www.sqlite.org/datatype3.html#type_affinity
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    /// No preference, values are kept as they are
+    #[default]
+    Blob,
+}
+
+impl Affinity {
+    /// Derives the affinity from the declared type of a column,
+    /// the first matching rule wins.
+    pub fn from_type_name(type_name: &str) -> Self {
+        let type_name = type_name.to_uppercase();
+        let contains_any = |parts: &[&str]| parts.iter().any(|part| type_name.contains(part));
+
+        if contains_any(&["INT"]) {
+            Affinity::Integer
+        } else if contains_any(&["CHAR", "CLOB", "TEXT"]) {
+            Affinity::Text
+        } else if contains_any(&["BLOB"]) || type_name.is_empty() {
+            Affinity::Blob
+        } else if contains_any(&["REAL", "FLOA", "DOUB"]) {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Affinity::Numeric | Affinity::Integer | Affinity::Real)
+    }
+
+    /// The affinity applied to the operands of a comparison before comparing them:
+    /// NUMERIC if only one operand is numeric, TEXT if the other has none.
+    /// https://www.sqlite.org/datatype3.html#type_conversions_prior_to_comparison
+    pub fn for_comparison(l: Affinity, r: Affinity) -> Affinity {
+        match (l, r) {
+            (l, r) if l.is_numeric() != r.is_numeric() => Affinity::Numeric,
+            (Affinity::Text, Affinity::Blob) | (Affinity::Blob, Affinity::Text) => Affinity::Text,
+            _ => Affinity::Blob,
+        }
+    }
+
+    /// Converts a value to the preferred storage class if that loses no information.
+    pub fn apply(&self, value: Value) -> Value {
+        match (self, value) {
+            (Affinity::Text, Value::Integer(integer)) => Value::Text(integer.to_string()),
+            (Affinity::Text, Value::Real(real)) => Value::Text(real_to_text(real)),
+            (Affinity::Numeric | Affinity::Integer, Value::Text(text)) => {
+                parse_numeric(&text).map_or(Value::Text(text), real_to_integer)
+            }
+            (Affinity::Numeric | Affinity::Integer, Value::Real(real)) => {
+                real_to_integer(Value::Real(real))
+            }
+            (Affinity::Real, Value::Text(text)) => match parse_numeric(&text) {
+                Some(Value::Integer(integer)) => Value::Real(integer as f64),
+                Some(value) => value,
+                None => Value::Text(text),
+            },
+            (Affinity::Real, Value::Integer(integer)) => Value::Real(integer as f64),
+            (_, value) => value,
+        }
+    }
+
+    /// Converts a value to the storage class of the affinity like `CAST` does, which may lose information.
+    /// https://www.sqlite.org/lang_expr.html#castexpr
+    pub fn cast(&self, value: Value) -> Value {
+        match (self, value) {
+            (_, Value::Null) => Value::Null,
+            (Affinity::Text, value) => Value::Text(value.to_text().unwrap()),
+            (Affinity::Blob, Value::Blob(blob)) => Value::Blob(blob),
+            (Affinity::Blob, value) => Value::Blob(value.to_text().unwrap().into_bytes()),
+            (Affinity::Integer, value) => Value::Integer(value.to_integer().unwrap()),
+            (Affinity::Real, value) => Value::Real(value.to_real().unwrap()),
+            (Affinity::Numeric, value @ (Value::Integer(_) | Value::Real(_))) => value,
+            (Affinity::Numeric, value) => real_to_integer(value.to_numeric().unwrap()),
+        }
+    }
+}
+
+/// Parses text that is a well-formed integer or real literal, surrounding spaces are allowed.
+/// Integers too large for 64 bits become reals.
+pub fn parse_numeric(text: &str) -> Option<Value> {
+    let text = text.trim_matches(|c: char| c.is_ascii_whitespace());
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+
+    let (mantissa, exponent) = match digits.find(['e', 'E']) {
+        Some(idx) => (&digits[..idx], Some(&digits[idx + 1..])),
+        None => (digits, None),
+    };
+    let (integer_part, fraction) = match mantissa.split_once('.') {
+        Some((integer_part, fraction)) => (integer_part, Some(fraction)),
+        None => (mantissa, None),
+    };
+
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let is_mantissa = is_digits(integer_part)
+        && fraction.into_iter().all(is_digits)
+        && (integer_part.len() + fraction.map_or(0, str::len)) > 0;
+    let is_exponent = exponent.into_iter().all(|exponent| {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        !exponent.is_empty() && is_digits(exponent)
+    });
+    if !is_mantissa || !is_exponent {
+        return None;
+    }
+
+    if fraction.is_none() && exponent.is_none() {
+        if let Ok(integer) = text.parse() {
+            return Some(Value::Integer(integer));
+        }
+    }
+    text.parse().ok().map(Value::Real)
+}
+
+/// The longest prefix of text, after leading spaces, that is a number
+fn numeric_prefix(text: &str) -> &str {
+    let text = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let bytes = text.as_bytes();
+    let digits_from = |mut end: usize| {
+        while bytes.get(end).is_some_and(u8::is_ascii_digit) {
+            end += 1;
+        }
+        end
+    };
+
+    let mut end = digits_from(usize::from(matches!(bytes.first(), Some(b'+' | b'-'))));
+    if bytes.get(end) == Some(&b'.') {
+        end = digits_from(end + 1);
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        let exponent_end = digits_from(end + 1 + sign);
+        if exponent_end > end + 1 + sign {
+            end = exponent_end;
+        }
+    }
+
+    &text[..end]
+}
+
+/// Converts the longest numeric prefix of text to a real, 0.0 if there is none.
+pub fn real_prefix(text: &str) -> f64 {
+    numeric_prefix(text).parse().unwrap_or(0.0)
+}
+
+/// Converts the longest numeric prefix of text to an integer,
+/// or to a real if it has a fractional part or exponent or does not fit 64 bits.
+pub fn number_prefix(text: &str) -> Value {
+    let prefix = numeric_prefix(text);
+    match (prefix.parse(), prefix.parse()) {
+        (Ok(integer), _) => Value::Integer(integer),
+        (_, Ok(real)) => Value::Real(real),
+        // No number at all
+        _ => Value::Integer(0),
+    }
+}
+
+/// Converts the longest numeric prefix of text to an integer, dropping any fractional part.
+pub fn integer_prefix(text: &str) -> i64 {
+    let prefix = numeric_prefix(text);
+    prefix
+        .parse()
+        .unwrap_or_else(|_| prefix.parse::<f64>().unwrap_or(0.0) as i64)
+}
+
+/// Reals without a fractional part that fit 64 bits become integers.
+fn real_to_integer(value: Value) -> Value {
+    match value {
+        Value::Real(real) if real.fract() == 0.0 && real.abs() < 9.2e18 => {
+            Value::Integer(real as i64)
+        }
+        value => value,
+    }
+}
+
+/// Formats a real the way SQLite converts it to text: 15 significant digits,
+/// always with a decimal point.
+pub fn real_to_text(real: f64) -> String {
+    if real.is_nan() {
+        return String::from("NaN");
+    }
+    if real.is_infinite() {
+        return String::from(if real > 0.0 { "Inf" } else { "-Inf" });
+    }
+
+    let sign = if real < 0.0 { "-" } else { "" };
+    let text = format_general(real.abs(), 15, false);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) if !mantissa.contains('.') => {
+            format!("{sign}{mantissa}.0e{exponent}")
+        }
+        None if !text.contains('.') => format!("{sign}{text}.0"),
+        _ => format!("{sign}{text}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        integer_prefix, number_prefix, parse_numeric, real_prefix, real_to_text, Affinity,
+    };
+    use crate::engine::Value;
+
+    #[test]
+    fn affinity_from_type_name() {
+        let cases = [
+            ("INTEGER", Affinity::Integer),
+            ("bigint unsigned", Affinity::Integer),
+            ("VARCHAR(255)", Affinity::Text),
+            ("nchar(55)", Affinity::Text),
+            ("BLOB", Affinity::Blob),
+            ("", Affinity::Blob),
+            ("double precision", Affinity::Real),
+            ("DECIMAL(10,5)", Affinity::Numeric),
+            ("boolean", Affinity::Numeric),
+            // "INT" is found before "POINT" could mean a real
+            ("FLOATING POINT", Affinity::Integer),
+        ];
+
+        for (type_name, affinity) in cases {
+            assert_eq!(Affinity::from_type_name(type_name), affinity, "{type_name}");
+        }
+    }
+
+    #[test]
+    fn apply_affinity() {
+        assert_eq!(
+            Affinity::Numeric.apply(Value::from(" 42 ")),
+            Value::Integer(42)
+        );
+        assert_eq!(
+            Affinity::Numeric.apply(Value::from("3.0e+5")),
+            Value::Integer(300000)
+        );
+        assert_eq!(
+            Affinity::Numeric.apply(Value::from("2.5")),
+            Value::Real(2.5)
+        );
+        assert_eq!(
+            Affinity::Integer.apply(Value::from("12abc")),
+            Value::from("12abc")
+        );
+        assert_eq!(Affinity::Real.apply(Value::from("7")), Value::Real(7.0));
+        assert_eq!(Affinity::Real.apply(Value::Integer(7)), Value::Real(7.0));
+        assert_eq!(Affinity::Text.apply(Value::Integer(7)), Value::from("7"));
+        assert_eq!(Affinity::Text.apply(Value::Real(7.0)), Value::from("7.0"));
+        assert_eq!(Affinity::Blob.apply(Value::from("7")), Value::from("7"));
+        assert_eq!(Affinity::Text.apply(Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn comparison_affinity() {
+        use Affinity::*;
+
+        assert_eq!(Affinity::for_comparison(Integer, Text), Numeric);
+        assert_eq!(Affinity::for_comparison(Blob, Real), Numeric);
+        assert_eq!(Affinity::for_comparison(Text, Blob), Text);
+        assert_eq!(Affinity::for_comparison(Integer, Real), Blob);
+        assert_eq!(Affinity::for_comparison(Text, Text), Blob);
+    }
+
+    #[test]
+    fn parse_numeric_literals() {
+        assert_eq!(parse_numeric("-17"), Some(Value::Integer(-17)));
+        assert_eq!(parse_numeric(".5"), Some(Value::Real(0.5)));
+        assert_eq!(parse_numeric("5."), Some(Value::Real(5.0)));
+        assert_eq!(parse_numeric("1e3"), Some(Value::Real(1000.0)));
+        assert_eq!(
+            parse_numeric("9223372036854775808"),
+            Some(Value::Real(9223372036854775808.0))
+        );
+        for text in ["", ".", "e5", "1e", "0x10", "inf", "NaN", "1 2"] {
+            assert_eq!(parse_numeric(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn numeric_prefix() {
+        assert_eq!(real_prefix(" 12abc"), 12.0);
+        assert_eq!(real_prefix("-1.5e3x"), -1500.0);
+        assert_eq!(real_prefix("2e"), 2.0);
+        assert_eq!(real_prefix(".5"), 0.5);
+        assert_eq!(real_prefix("abc"), 0.0);
+        assert_eq!(real_prefix("-"), 0.0);
+        assert_eq!(integer_prefix("9223372036854775807x"), i64::MAX);
+        assert_eq!(integer_prefix(" 12.7"), 12);
+        assert_eq!(integer_prefix("-1e3"), -1000);
+        assert_eq!(integer_prefix("abc"), 0);
+        assert_eq!(number_prefix("3x"), Value::Integer(3));
+        assert_eq!(number_prefix("3.0"), Value::Real(3.0));
+        assert_eq!(number_prefix("1e2"), Value::Real(100.0));
+        assert_eq!(number_prefix("-"), Value::Integer(0));
+    }
+
+    #[test]
+    fn cast_values() {
+        assert_eq!(
+            Affinity::Numeric.cast(Value::from("3.0")),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            Affinity::Numeric.cast(Value::from("1e3x")),
+            Value::Integer(1000)
+        );
+        assert_eq!(Affinity::Numeric.cast(Value::Real(3.0)), Value::Real(3.0));
+        assert_eq!(Affinity::Integer.cast(Value::Real(3.7)), Value::Integer(3));
+        assert_eq!(
+            Affinity::Integer.cast(Value::from(" 12 ")),
+            Value::Integer(12)
+        );
+        assert_eq!(Affinity::Real.cast(Value::from("abc")), Value::Real(0.0));
+        assert_eq!(Affinity::Text.cast(Value::Real(1.5)), Value::from("1.5"));
+        assert_eq!(
+            Affinity::Blob.cast(Value::Integer(5)),
+            Value::Blob(vec![b'5'])
+        );
+        assert_eq!(Affinity::Integer.cast(Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn real_as_text() {
+        assert_eq!(real_to_text(1.0), "1.0");
+        assert_eq!(real_to_text(-2.5), "-2.5");
+        assert_eq!(real_to_text(1.5e20), "1.5e+20");
+        assert_eq!(real_to_text(1e-5), "1.0e-05");
+        assert_eq!(real_to_text(4561.666666666667), "4561.66666666667");
+        assert_eq!(real_to_text(9223372036854775808.0), "9.22337203685478e+18");
+        assert_eq!(real_to_text(0.1 + 0.2), "0.3");
+    }
+}
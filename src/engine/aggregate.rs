@@ -0,0 +1,486 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    sql::{Expr, Function, Literal},
+    sqlite_storage::serialize_record,
+};
+
+use super::{collation::Collation, Record, TableHeader, Value};
+
+/// Whether the function call aggregates rows rather than computing a value per row.
+/// `min` and `max` are scalar functions when called with several arguments.
+pub fn is_aggregate(function: &Function) -> bool {
+    match &function.name.to_lowercase()[..] {
+        "count" | "sum" | "total" | "avg" | "group_concat" => true,
+        "min" | "max" => function.args.len() == 1,
+        _ => false,
+    }
+}
+
+/// Returns the first aggregate call in the expression.
+pub fn find_aggregate(expr: &Expr) -> Option<&Function> {
+    match expr {
+        Expr::Function(function) if is_aggregate(function) => Some(function),
+        expr => expr.children().into_iter().find_map(find_aggregate),
+    }
+}
+
+/// Name of the hidden column holding the result of the `idx`th aggregate call
+pub fn aggregate_column_name(idx: usize) -> String {
+    format!("#aggregate{idx}")
+}
+
+/// Replaces the aggregate calls in the expression by references to hidden columns.
+/// Calls are collected in `calls`, identical calls share a column.
+pub fn extract_aggregates(expr: &Expr, calls: &mut Vec<Function>) -> Result<Expr, String> {
+    Ok(match expr {
+        Expr::Function(function) if is_aggregate(function) => {
+            if let Some(nested) = function.args.iter().find_map(find_aggregate) {
+                return Err(format!("misuse of aggregate function {}()", nested.name));
+            }
+
+            let idx = match calls.iter().position(|call| call == function) {
+                Some(idx) => idx,
+                None => {
+                    calls.push(function.clone());
+                    calls.len() - 1
+                }
+            };
+            Expr::Literal(Literal::Id(aggregate_column_name(idx)))
+        }
+        expr => expr.try_map_children(|child| extract_aggregates(child, calls))?,
+    })
+}
+
+/// Running state of one aggregate function over the rows of a group.
+#[derive(Clone, Debug)]
+enum Accumulator {
+    Count(i64),
+    // The sum stays an integer as long as every value is one
+    Sum {
+        integer: i64,
+        real: f64,
+        is_real: bool,
+        is_empty: bool,
+    },
+    Total(f64),
+    Avg {
+        sum: f64,
+        cnt: i64,
+    },
+    Min(Option<Value>),
+    Max(Option<Value>),
+    GroupConcat(Option<String>),
+}
+
+/// An aggregate call: the accumulator and, for DISTINCT, the values seen so far,
+/// with the collation of the argument they are told apart and min() and max() ordered by
+#[derive(Clone, Debug)]
+struct Aggregate {
+    accumulator: Accumulator,
+    // COUNT(*) counts rows rather than values
+    count_rows: bool,
+    seen: Option<HashSet<Vec<u8>>>,
+    collation: Collation,
+}
+
+impl Aggregate {
+    fn new(function: &Function, table_header: &TableHeader) -> Result<Self, String> {
+        let name = function.name.to_lowercase();
+        let arg_cnt = function.args.len();
+
+        let arg_cnt_ok = match &name[..] {
+            "count" => arg_cnt <= 1,
+            "group_concat" => arg_cnt == 1 || arg_cnt == 2,
+            _ => arg_cnt == 1,
+        };
+        if !arg_cnt_ok {
+            return Err(format!(
+                "wrong number of arguments to function {}()",
+                function.name
+            ));
+        }
+        if function.distinct && arg_cnt != 1 {
+            return Err(String::from(
+                "DISTINCT aggregates must have exactly one argument",
+            ));
+        }
+
+        let accumulator = match &name[..] {
+            "count" => Accumulator::Count(0),
+            "sum" => Accumulator::Sum {
+                integer: 0,
+                real: 0.0,
+                is_real: false,
+                is_empty: true,
+            },
+            "total" => Accumulator::Total(0.0),
+            "avg" => Accumulator::Avg { sum: 0.0, cnt: 0 },
+            "min" => Accumulator::Min(None),
+            "max" => Accumulator::Max(None),
+            "group_concat" => Accumulator::GroupConcat(None),
+            _ => unreachable!(),
+        };
+
+        Ok(Self {
+            accumulator,
+            count_rows: arg_cnt == 0,
+            seen: function.distinct.then(HashSet::new),
+            collation: (function.args.first())
+                .and_then(|arg| arg.collation(table_header))
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Adds the arguments of one row, returns true if the row holds a new minimum or maximum.
+    fn step(&mut self, args: &[Value]) -> Result<bool, String> {
+        if self.count_rows {
+            if let Accumulator::Count(cnt) = &mut self.accumulator {
+                *cnt += 1;
+            }
+            return Ok(false);
+        }
+
+        let value = &args[0];
+        // Every aggregate ignores NULLs
+        if *value == Value::Null {
+            return Ok(false);
+        }
+        if let Some(seen) = &mut self.seen {
+            if !seen.insert(hash_key(&[self.collation.key(value.clone())])) {
+                return Ok(false);
+            }
+        }
+
+        match &mut self.accumulator {
+            Accumulator::Count(cnt) => *cnt += 1,
+            Accumulator::Sum {
+                integer,
+                real,
+                is_real,
+                is_empty,
+            } => {
+                *is_empty = false;
+                match value {
+                    Value::Integer(x) if !*is_real => {
+                        *integer = integer
+                            .checked_add(*x)
+                            .ok_or_else(|| String::from("integer overflow"))?;
+                    }
+                    value => {
+                        if !*is_real {
+                            *is_real = true;
+                            *real = *integer as f64;
+                        }
+                        *real += to_real(value);
+                    }
+                }
+            }
+            Accumulator::Total(total) => *total += to_real(value),
+            Accumulator::Avg { sum, cnt } => {
+                *sum += to_real(value);
+                *cnt += 1;
+            }
+            Accumulator::Min(min) => {
+                let is_new = match min {
+                    Some(min) => self.collation.compare(value, min).is_lt(),
+                    None => true,
+                };
+                if is_new {
+                    *min = Some(value.clone());
+                    return Ok(true);
+                }
+            }
+            Accumulator::Max(max) => {
+                let is_new = match max {
+                    Some(max) => self.collation.compare(value, max).is_gt(),
+                    None => true,
+                };
+                if is_new {
+                    *max = Some(value.clone());
+                    return Ok(true);
+                }
+            }
+            Accumulator::GroupConcat(concat) => {
+                let separator = match args.get(1) {
+                    Some(Value::Null) => String::new(),
+                    Some(separator) => separator.to_string(),
+                    None => String::from(","),
+                };
+                match concat {
+                    Some(concat) => {
+                        concat.push_str(&separator);
+                        concat.push_str(&value.to_string());
+                    }
+                    None => *concat = Some(value.to_string()),
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn finish(self) -> Value {
+        match self.accumulator {
+            Accumulator::Count(cnt) => Value::Integer(cnt),
+            Accumulator::Sum { is_empty: true, .. } => Value::Null,
+            Accumulator::Sum {
+                real,
+                is_real: true,
+                ..
+            } => Value::Real(real),
+            Accumulator::Sum { integer, .. } => Value::Integer(integer),
+            Accumulator::Total(total) => Value::Real(total),
+            Accumulator::Avg { cnt: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, cnt } => Value::Real(sum / cnt as f64),
+            Accumulator::Min(value) | Accumulator::Max(value) => value.unwrap_or(Value::Null),
+            Accumulator::GroupConcat(concat) => concat.map_or(Value::Null, Value::Text),
+        }
+    }
+}
+
+struct Group {
+    key: Vec<Value>,
+    // The values of bare columns are taken from this row
+    record: Record,
+    aggregates: Vec<Aggregate>,
+}
+
+/// Hash aggregation: rows are assigned to groups by their GROUP BY values
+/// and every aggregate call is accumulated per group.
+///
+/// Bare columns take their values from the last row of the group,
+/// or from the row holding the extremum if the only aggregate call is `min` or `max`.
+pub struct HashAggregate {
+    aggregates: Vec<Aggregate>,
+    // Index of the only aggregate call if it is min or max
+    extremum: Option<usize>,
+    group_idxs: HashMap<Vec<u8>, usize>,
+    groups: Vec<Group>,
+}
+
+impl HashAggregate {
+    /// The arguments of the calls are expressions on rows with `table_header`.
+    pub fn new(calls: &[Function], table_header: &TableHeader) -> Result<Self, String> {
+        let aggregates = calls
+            .iter()
+            .map(|call| Aggregate::new(call, table_header))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let extremum = match &aggregates[..] {
+            [Aggregate {
+                accumulator: Accumulator::Min(_) | Accumulator::Max(_),
+                ..
+            }] => Some(0),
+            _ => None,
+        };
+
+        Ok(Self {
+            aggregates,
+            extremum,
+            group_idxs: HashMap::new(),
+            groups: vec![],
+        })
+    }
+
+    /// Adds a row, `args` holds the argument values of every aggregate call.
+    pub fn step(
+        &mut self,
+        key: Vec<Value>,
+        args: Vec<Vec<Value>>,
+        record: Record,
+    ) -> Result<(), String> {
+        let group_idx = *self.group_idxs.entry(hash_key(&key)).or_insert_with(|| {
+            self.groups.push(Group {
+                key,
+                record: record.clone(),
+                aggregates: self.aggregates.clone(),
+            });
+            self.groups.len() - 1
+        });
+        let group = &mut self.groups[group_idx];
+
+        let mut is_extremum = false;
+        for (idx, (aggregate, args)) in group.aggregates.iter_mut().zip(args).enumerate() {
+            let is_new_extremum = aggregate.step(&args)?;
+            is_extremum |= is_new_extremum && self.extremum == Some(idx);
+        }
+
+        if self.extremum.is_none() || is_extremum {
+            group.record = record;
+        }
+        Ok(())
+    }
+
+    /// Returns a record per group in GROUP BY order: the values of the group's row
+    /// followed by the result of every aggregate call.
+    /// Without GROUP BY there is a single group, even if there were no rows.
+    pub fn finish(mut self, is_grouped: bool, column_cnt: usize) -> Vec<Record> {
+        if !is_grouped && self.groups.is_empty() {
+            self.groups.push(Group {
+                key: vec![],
+                record: Record::new(0, vec![Value::Null; column_cnt]),
+                aggregates: self.aggregates,
+            });
+        }
+
+        self.groups.sort_by(|a, b| compare_values(&a.key, &b.key));
+        self.groups
+            .into_iter()
+            .map(|group| {
+                let mut values = group.record.values;
+                values.extend(group.aggregates.into_iter().map(Aggregate::finish));
+                Record::new(group.record.rowid, values)
+            })
+            .collect()
+    }
+}
+
+fn compare_values(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| a.compare(b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Encodes values such that equal values have equal keys, integral reals are equal to integers.
+pub fn hash_key(values: &[Value]) -> Vec<u8> {
+    let values: Vec<_> = values
+        .iter()
+        .map(|value| match value {
+            Value::Real(real) if real.fract() == 0.0 && real.abs() < 9.2e18 => {
+                Value::Integer(*real as i64)
+            }
+            value => value.clone(),
+        })
+        .collect();
+    serialize_record(&values)
+}
+
+fn to_real(value: &Value) -> f64 {
+    match value {
+        Value::Integer(integer) => *integer as f64,
+        Value::Real(real) => *real,
+        Value::Text(text) => text.trim().parse().unwrap_or(0.0),
+        Value::Null | Value::Blob(_) => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::{Record, TableHeader, Value},
+        sql::{Expr, Function, Literal},
+    };
+
+    use super::HashAggregate;
+
+    fn call(name: &str, distinct: bool, arg_cnt: usize) -> Function {
+        let arg = Expr::Literal(Literal::Id(String::from("x")));
+        Function {
+            name: String::from(name),
+            distinct,
+            args: vec![arg; arg_cnt],
+        }
+    }
+
+    fn aggregate(calls: &[Function], rows: &[(i64, Value)]) -> Vec<Vec<Value>> {
+        let mut hash_aggregate = HashAggregate::new(calls, &TableHeader::new(&[])).unwrap();
+        for (group, value) in rows {
+            let args = calls.iter().map(|_| vec![value.clone()]).collect();
+            let record = Record::new(0, vec![Value::Integer(*group)]);
+            hash_aggregate
+                .step(vec![Value::Integer(*group)], args, record)
+                .unwrap();
+        }
+
+        hash_aggregate
+            .finish(true, 1)
+            .into_iter()
+            .map(|record| record.values)
+            .collect()
+    }
+
+    #[test]
+    fn aggregates_per_group() {
+        let calls = [
+            call("count", false, 0),
+            call("count", true, 1),
+            call("sum", false, 1),
+            call("avg", false, 1),
+            call("max", false, 1),
+            call("group_concat", false, 1),
+        ];
+        let rows = [
+            (2, Value::Integer(5)),
+            (1, Value::Integer(1)),
+            (2, Value::Null),
+            (1, Value::Real(1.0)),
+            (2, Value::Integer(7)),
+        ];
+
+        let groups = aggregate(&calls, &rows);
+        assert_eq!(
+            groups,
+            [
+                vec![
+                    Value::Integer(1),
+                    Value::Integer(2),
+                    Value::Integer(1),
+                    Value::Real(2.0),
+                    Value::Real(1.0),
+                    Value::Integer(1),
+                    Value::Text(String::from("1,1.0")),
+                ],
+                vec![
+                    Value::Integer(2),
+                    Value::Integer(3),
+                    Value::Integer(2),
+                    Value::Integer(12),
+                    Value::Real(6.0),
+                    Value::Integer(7),
+                    Value::Text(String::from("5,7")),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn aggregates_without_rows() {
+        let calls = [
+            call("count", false, 0),
+            call("sum", false, 1),
+            call("total", false, 1),
+        ];
+        let hash_aggregate = HashAggregate::new(&calls, &TableHeader::new(&[])).unwrap();
+
+        let records = hash_aggregate.finish(false, 1);
+        assert_eq!(
+            records[0].values,
+            [
+                Value::Null,
+                Value::Integer(0),
+                Value::Null,
+                Value::Real(0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn sum_overflow() {
+        let calls = [call("sum", false, 1)];
+        let mut hash_aggregate = HashAggregate::new(&calls, &TableHeader::new(&[])).unwrap();
+
+        let record = Record::new(0, vec![]);
+        let args = vec![vec![Value::Integer(i64::MAX)]];
+        hash_aggregate
+            .step(vec![], args.clone(), record.clone())
+            .unwrap();
+        let err = hash_aggregate.step(vec![], args, record).unwrap_err();
+        assert_eq!(err, "integer overflow");
+    }
+}
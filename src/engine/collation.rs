@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+
+use super::Value;
+
+/// How two TEXT values are compared, other values are not affected.
+/// https://www.sqlite.org/datatype3.html#collating_sequences
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Collation {
+    /// Byte by byte
+    #[default]
+    Binary,
+    /// ASCII characters are folded to lower case
+    NoCase,
+    /// Trailing spaces are ignored
+    RTrim,
+}
+
+impl Collation {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match &name.to_lowercase()[..] {
+            "binary" => Ok(Collation::Binary),
+            "nocase" => Ok(Collation::NoCase),
+            "rtrim" => Ok(Collation::RTrim),
+            _ => Err(format!("no such collation sequence: {name}")),
+        }
+    }
+
+    /// The value standing for every value equal to it, to group or hash values by.
+    pub fn key(&self, value: Value) -> Value {
+        match (self, value) {
+            (Collation::NoCase, Value::Text(text)) => Value::Text(text.to_ascii_lowercase()),
+            (Collation::RTrim, Value::Text(text)) => {
+                Value::Text(String::from(text.trim_end_matches(' ')))
+            }
+            (_, value) => value,
+        }
+    }
+
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        let (Value::Text(a), Value::Text(b)) = (a, b) else {
+            return a.compare(b);
+        };
+
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::NoCase => a
+                .bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            Collation::RTrim => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::Collation;
+    use crate::engine::Value;
+
+    #[test]
+    fn compare_text() {
+        let (a, b) = (Value::from("abc"), Value::from("ABC  "));
+
+        assert_eq!(Collation::Binary.compare(&a, &b), Ordering::Greater);
+        assert_eq!(Collation::NoCase.compare(&a, &b), Ordering::Less);
+        assert_eq!(
+            Collation::RTrim.compare(&b, &Value::from("ABC")),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Collation::NoCase.compare(&a, &Value::from("ABC")),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn key_text() {
+        assert_eq!(
+            Collation::NoCase.key(Value::from("AbC ")),
+            Value::from("abc ")
+        );
+        assert_eq!(
+            Collation::RTrim.key(Value::from("AbC  ")),
+            Value::from("AbC")
+        );
+        assert_eq!(
+            Collation::Binary.key(Value::from("AbC ")),
+            Value::from("AbC ")
+        );
+        assert_eq!(Collation::NoCase.key(Value::Integer(1)), Value::Integer(1));
+    }
+
+    #[test]
+    fn unknown_collation() {
+        assert!(Collation::from_name("NOCASE").is_ok());
+        assert_eq!(
+            Collation::from_name("german"),
+            Err(String::from("no such collation sequence: german"))
+        );
+    }
+}
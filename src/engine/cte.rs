@@ -0,0 +1,114 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{aggregate::hash_key, Record};
+
+/// The rows of a common table expression, computed before the query that reads them
+#[derive(Debug)]
+pub struct Cte {
+    pub name: String,
+    pub column_names: Vec<String>,
+    pub records: Vec<Record>,
+}
+
+impl Cte {
+    pub fn new(name: &str, column_names: Vec<String>, records: Vec<Record>) -> Self {
+        Self {
+            name: String::from(name),
+            column_names,
+            records,
+        }
+    }
+}
+
+/// The rows of both sides of a UNION ALL, of a UNION only the first of equal rows.
+pub fn union(records: Vec<Record>, all: bool) -> Vec<Record> {
+    let mut seen = HashSet::new();
+    records
+        .into_iter()
+        .filter(|record| all || seen.insert(hash_key(&record.values)))
+        .collect()
+}
+
+/// Computes a recursive common table expression from the rows of its initial SELECT.
+/// The rows are queued, and taken from the queue one at a time into the result.
+/// `step` runs the recursive SELECT with the row taken as the only row of the table,
+/// its rows are queued in turn, for a UNION only rows not queued before.
+/// Stops when the queue is empty or the result has `limit` rows.
+pub fn recurse(
+    initial: Vec<Record>,
+    all: bool,
+    limit: usize,
+    mut step: impl FnMut(Record) -> Result<Vec<Record>, String>,
+) -> Result<Vec<Record>, String> {
+    let mut seen = HashSet::new();
+    let mut is_new = |record: &Record| all || seen.insert(hash_key(&record.values));
+
+    let mut queue: VecDeque<Record> = initial.into_iter().filter(&mut is_new).collect();
+    let mut records = vec![];
+    while records.len() < limit {
+        let Some(record) = queue.pop_front() else {
+            break;
+        };
+        records.push(record.clone());
+        queue.extend(step(record)?.into_iter().filter(&mut is_new));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{Record, Value};
+
+    use super::{recurse, union};
+
+    fn records(values: &[i64]) -> Vec<Record> {
+        values
+            .iter()
+            .map(|value| Record::new(0, vec![Value::Integer(*value)]))
+            .collect()
+    }
+
+    fn values(records: &[Record]) -> Vec<i64> {
+        records.iter().map(|record| i64::from(&record[0])).collect()
+    }
+
+    #[test]
+    fn union_drops_duplicates() {
+        assert_eq!(values(&union(records(&[1, 2, 1, 3, 2]), false)), [1, 2, 3]);
+        assert_eq!(
+            values(&union(records(&[1, 2, 1, 3, 2]), true)),
+            [1, 2, 1, 3, 2]
+        );
+    }
+
+    #[test]
+    fn recurse_breadth_first() {
+        // A tree: 1 has children 2 and 3, 2 has child 4
+        let children = |record: Record| {
+            Ok(match i64::from(&record[0]) {
+                1 => records(&[2, 3]),
+                2 => records(&[4]),
+                _ => vec![],
+            })
+        };
+        let result = recurse(records(&[1]), true, usize::MAX, children).unwrap();
+        assert_eq!(values(&result), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn recurse_stops() {
+        // Counting up never ends without a limit
+        let next = |record: Record| Ok(records(&[i64::from(&record[0]) + 1]));
+        let result = recurse(records(&[1]), true, 5, next).unwrap();
+        assert_eq!(values(&result), [1, 2, 3, 4, 5]);
+
+        // A cycle ends once UNION has seen every row
+        let cycle = |record: Record| Ok(records(&[(i64::from(&record[0]) + 1) % 3]));
+        let result = recurse(records(&[0, 0]), false, usize::MAX, cycle).unwrap();
+        assert_eq!(values(&result), [0, 1, 2]);
+
+        let failing = |_| Err(String::from("no such column: x"));
+        let result = recurse(records(&[1]), true, usize::MAX, failing);
+        assert_eq!(result.err().unwrap(), "no such column: x");
+    }
+}
@@ -0,0 +1,454 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Debug, rc::Rc};
+
+use super::{
+    printf::{hex, printf, quote},
+    Value,
+};
+
+/// A scalar function, called with the values of its arguments, fails with an error message
+pub type ScalarFunction = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+/// The scalar functions SQL can call, looked up by name and number of arguments.
+/// A function can be registered for a fixed number of arguments or for any number,
+/// the fixed one is preferred.
+#[derive(Clone)]
+pub struct Functions {
+    functions: HashMap<(String, Option<usize>), ScalarFunction>,
+}
+
+impl Debug for Functions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<_> = self.functions.keys().map(|(name, _)| name).collect();
+        names.sort();
+        names.dedup();
+        f.debug_struct("Functions").field("names", &names).finish()
+    }
+}
+
+impl Default for Functions {
+    /// SQLite's core scalar functions.
+    /// https://www.sqlite.org/lang_corefunc.html
+    fn default() -> Self {
+        let mut functions = Self {
+            functions: HashMap::new(),
+        };
+
+        functions.register("length", Some(1), |args| {
+            Ok(match &args[0] {
+                Value::Null => Value::Null,
+                Value::Blob(blob) => Value::Integer(blob.len() as i64),
+                value => Value::Integer(text(value).chars().count() as i64),
+            })
+        });
+        // Only ASCII letters change their case
+        functions.register("lower", Some(1), |args| {
+            Ok(map_text(&args[0], |text| text.to_ascii_lowercase()))
+        });
+        functions.register("upper", Some(1), |args| {
+            Ok(map_text(&args[0], |text| text.to_ascii_uppercase()))
+        });
+        for n_args in [2, 3] {
+            functions.register("substr", Some(n_args), |args| Ok(substr(args)));
+            functions.register("substring", Some(n_args), |args| Ok(substr(args)));
+        }
+        for (name, trim_start, trim_end) in [
+            ("trim", true, true),
+            ("ltrim", true, false),
+            ("rtrim", false, true),
+        ] {
+            for n_args in [1, 2] {
+                functions.register(name, Some(n_args), move |args| {
+                    let characters = match args.get(1) {
+                        Some(Value::Null) => return Ok(Value::Null),
+                        Some(characters) => text(characters),
+                        None => String::from(" "),
+                    };
+                    Ok(map_text(&args[0], |mut text| {
+                        let is_trimmed = |c: char| characters.contains(c);
+                        if trim_start {
+                            text = text.trim_start_matches(is_trimmed).to_owned();
+                        }
+                        if trim_end {
+                            text = text.trim_end_matches(is_trimmed).to_owned();
+                        }
+                        text
+                    }))
+                });
+            }
+        }
+        functions.register("replace", Some(3), |args| {
+            if args.contains(&Value::Null) {
+                return Ok(Value::Null);
+            }
+            let (pattern, replacement) = (text(&args[1]), text(&args[2]));
+            Ok(match pattern.is_empty() {
+                true => Value::Text(text(&args[0])),
+                false => Value::Text(text(&args[0]).replace(&pattern, &replacement)),
+            })
+        });
+        functions.register("instr", Some(2), |args| {
+            Ok(match (&args[0], &args[1]) {
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                (Value::Blob(haystack), Value::Blob(needle)) => {
+                    let position = (0..=haystack.len().saturating_sub(needle.len()))
+                        .find(|start| haystack[*start..].starts_with(needle))
+                        .map_or(0, |start| start + 1);
+                    Value::Integer(position as i64)
+                }
+                (haystack, needle) => {
+                    let haystack = text(haystack);
+                    let position = haystack
+                        .find(&text(needle))
+                        .map_or(0, |start| haystack[..start].chars().count() + 1);
+                    Value::Integer(position as i64)
+                }
+            })
+        });
+        // The smallest integer has no positive counterpart
+        functions.register("abs", Some(1), |args| match &args[0] {
+            Value::Null => Ok(Value::Null),
+            Value::Integer(integer) => match integer.checked_abs() {
+                Some(abs) => Ok(Value::Integer(abs)),
+                None => Err(String::from("integer overflow")),
+            },
+            value => Ok(Value::Real(value.to_real().unwrap().abs())),
+        });
+        for n_args in [1, 2] {
+            functions.register("round", Some(n_args), |args| {
+                let (Some(real), digits) = (args[0].to_real(), args.get(1)) else {
+                    return Ok(Value::Null);
+                };
+                let digits = match digits.map(Value::to_integer) {
+                    Some(None) => return Ok(Value::Null),
+                    Some(Some(digits)) => digits.clamp(0, 30) as usize,
+                    None => 0,
+                };
+                // The exact decimal value is rounded, halfway cases away from zero.
+                // format! rounds those to even, they are the reals whose next digit
+                // is exactly a 5 and are moved to the next value up first.
+                let scale = 10f64.powi(digits as i32 + 1);
+                let scaled = real * scale;
+                let is_halfway = scaled.abs() % 10.0 == 5.0 && real.mul_add(scale, -scaled) == 0.0;
+                let real = match is_halfway {
+                    true => (scaled + 5f64.copysign(real)) / scale,
+                    false => real,
+                };
+                // Adding 0.0 turns -0.0 into 0.0
+                let rounded: f64 = format!("{real:.digits$}").parse().unwrap();
+                Ok(Value::Real(rounded + 0.0))
+            });
+        }
+        functions.register("coalesce", None, |args| {
+            let value = args.iter().find(|arg| **arg != Value::Null);
+            Ok(value.cloned().unwrap_or(Value::Null))
+        });
+        functions.register("ifnull", Some(2), |args| match &args[0] {
+            Value::Null => Ok(args[1].clone()),
+            value => Ok(value.clone()),
+        });
+        functions.register("nullif", Some(2), |args| {
+            match args[0].compare(&args[1]).is_eq() {
+                true => Ok(Value::Null),
+                false => Ok(args[0].clone()),
+            }
+        });
+        // With several arguments min and max are scalar functions, NULL if any argument is
+        for (name, ordering) in [("min", Ordering::Less), ("max", Ordering::Greater)] {
+            functions.register(name, None, move |args| {
+                if args.contains(&Value::Null) {
+                    return Ok(Value::Null);
+                }
+                let value = args.iter().reduce(|a, b| match b.compare(a) == ordering {
+                    true => b,
+                    false => a,
+                });
+                Ok(value.cloned().unwrap_or(Value::Null))
+            });
+        }
+        functions.register("typeof", Some(1), |args| {
+            Ok(Value::Text(String::from(match &args[0] {
+                Value::Null => "null",
+                Value::Integer(_) => "integer",
+                Value::Real(_) => "real",
+                Value::Text(_) => "text",
+                Value::Blob(_) => "blob",
+            })))
+        });
+        functions.register("hex", Some(1), |args| match &args[0] {
+            Value::Blob(blob) => Ok(Value::Text(hex(blob))),
+            value => Ok(Value::Text(hex(value
+                .to_text()
+                .unwrap_or_default()
+                .as_bytes()))),
+        });
+        functions.register("quote", Some(1), |args| Ok(Value::Text(quote(&args[0]))));
+        functions.register("printf", None, |args| {
+            match args.first().map(Value::to_text) {
+                Some(Some(format)) => Ok(Value::Text(printf(&format, &args[1..]))),
+                _ => Ok(Value::Null),
+            }
+        });
+        functions.register("iif", Some(3), |args| match bool::from(&args[0]) {
+            true => Ok(args[1].clone()),
+            false => Ok(args[2].clone()),
+        });
+
+        functions
+    }
+}
+
+impl Functions {
+    /// Adds a function, replacing one of the same name and number of arguments.
+    /// `n_args` is None if the function takes any number of arguments.
+    pub fn register(
+        &mut self,
+        name: &str,
+        n_args: Option<usize>,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        self.functions
+            .insert((name.to_lowercase(), n_args), Rc::new(function));
+    }
+
+    pub fn find(&self, name: &str, n_args: usize) -> Option<&ScalarFunction> {
+        let name = name.to_lowercase();
+        (self.functions.get(&(name.clone(), Some(n_args))))
+            .or_else(|| self.functions.get(&(name, None)))
+    }
+
+    /// Fails if no function of the name takes `n_args` arguments.
+    pub fn check(&self, name: &str, n_args: usize) -> Result<(), String> {
+        if self.find(name, n_args).is_some() {
+            return Ok(());
+        }
+        match self
+            .functions
+            .keys()
+            .any(|(other, _)| other.eq_ignore_ascii_case(name))
+        {
+            true => Err(format!("wrong number of arguments to function {name}()")),
+            false => Err(format!("no such function: {name}")),
+        }
+    }
+}
+
+/// Text arguments of non-text values are converted
+fn text(value: &Value) -> String {
+    value.to_text().unwrap_or_default()
+}
+
+fn map_text(value: &Value, f: impl FnOnce(String) -> String) -> Value {
+    match value.to_text() {
+        Some(text) => Value::Text(f(text)),
+        None => Value::Null,
+    }
+}
+
+/// `substr(X, Y, Z)`: Z characters of X starting at the Y-th, counted from 1.
+/// A negative Y counts from the end, a negative Z takes the characters before Y.
+fn substr(args: &[Value]) -> Value {
+    let (Some(start), Some(len)) = (
+        args[1].to_integer(),
+        args.get(2).map_or(Some(i64::MAX), Value::to_integer),
+    ) else {
+        return Value::Null;
+    };
+
+    let slice = |total: usize| {
+        let total = total as i64;
+        let is_before = len < 0;
+        let (mut start, mut len) = (start, len.checked_abs().unwrap_or(i64::MAX));
+        if start < 0 {
+            start += total;
+            if start < 0 {
+                len = (len + start).max(0);
+                start = 0;
+            }
+        } else if start > 0 {
+            start -= 1;
+        } else if len > 0 {
+            len -= 1;
+        }
+        if is_before {
+            start -= len;
+            if start < 0 {
+                len += start;
+                start = 0;
+            }
+        }
+        let start = start.min(total);
+        (start as usize, len.clamp(0, total - start) as usize)
+    };
+
+    match &args[0] {
+        Value::Null => Value::Null,
+        Value::Blob(blob) => {
+            let (start, len) = slice(blob.len());
+            Value::Blob(blob[start..start + len].to_vec())
+        }
+        value => {
+            let text = text(value);
+            let (start, len) = slice(text.chars().count());
+            Value::Text(text.chars().skip(start).take(len).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Value;
+
+    use super::Functions;
+
+    fn call(functions: &Functions, name: &str, args: &[Value]) -> Result<Value, String> {
+        functions.find(name, args.len()).unwrap()(args)
+    }
+
+    #[test]
+    fn text_functions() {
+        let functions = Functions::default();
+        let cases = [
+            ("length", vec![Value::Integer(123)], Value::Integer(3)),
+            ("length", vec![Value::from("héllo")], Value::Integer(5)),
+            ("length", vec![Value::Blob(vec![1, 2])], Value::Integer(2)),
+            ("upper", vec![Value::from("abé")], Value::from("ABé")),
+            ("lower", vec![Value::Null], Value::Null),
+            (
+                "substr",
+                vec![Value::from("hello"), Value::Integer(2)],
+                Value::from("ello"),
+            ),
+            (
+                "substr",
+                vec![Value::from("hello"), Value::Integer(-3), Value::Integer(2)],
+                Value::from("ll"),
+            ),
+            (
+                "substr",
+                vec![Value::from("hello"), Value::Integer(0), Value::Integer(2)],
+                Value::from("h"),
+            ),
+            (
+                "substr",
+                vec![Value::from("hello"), Value::Integer(3), Value::Integer(-2)],
+                Value::from("he"),
+            ),
+            (
+                "substr",
+                vec![Value::from("hello"), Value::Integer(-10), Value::Integer(7)],
+                Value::from("he"),
+            ),
+            ("trim", vec![Value::from("  a  ")], Value::from("a")),
+            (
+                "trim",
+                vec![Value::from("xxaxx"), Value::from("x")],
+                Value::from("a"),
+            ),
+            ("ltrim", vec![Value::from("  a ")], Value::from("a ")),
+            (
+                "rtrim",
+                vec![Value::from("xay"), Value::from("y")],
+                Value::from("xa"),
+            ),
+            (
+                "replace",
+                vec![Value::from("aaa"), Value::from("a"), Value::from("bb")],
+                Value::from("bbbbbb"),
+            ),
+            (
+                "replace",
+                vec![Value::from("abc"), Value::from(""), Value::from("x")],
+                Value::from("abc"),
+            ),
+            (
+                "instr",
+                vec![Value::from("héllo"), Value::from("l")],
+                Value::Integer(3),
+            ),
+            (
+                "instr",
+                vec![Value::from("hello"), Value::from("z")],
+                Value::Integer(0),
+            ),
+            ("hex", vec![Value::Integer(12)], Value::from("3132")),
+            ("hex", vec![Value::Null], Value::from("")),
+        ];
+
+        for (name, args, want) in cases {
+            assert_eq!(call(&functions, name, &args), Ok(want), "{name}({args:?})");
+        }
+    }
+
+    #[test]
+    fn numeric_functions() {
+        let functions = Functions::default();
+        let cases = [
+            ("abs", vec![Value::from("-5")], Value::Real(5.0)),
+            ("abs", vec![Value::Integer(-5)], Value::Integer(5)),
+            ("round", vec![Value::Real(2.5)], Value::Real(3.0)),
+            ("round", vec![Value::Real(-2.5)], Value::Real(-3.0)),
+            (
+                "round",
+                vec![Value::from("1.23456"), Value::Integer(3)],
+                Value::Real(1.235),
+            ),
+            (
+                "round",
+                vec![Value::Real(1.005), Value::Integer(2)],
+                Value::Real(1.0),
+            ),
+            (
+                "round",
+                vec![Value::Real(2.675), Value::Integer(2)],
+                Value::Real(2.67),
+            ),
+            (
+                "round",
+                vec![Value::Real(-0.125), Value::Integer(2)],
+                Value::Real(-0.13),
+            ),
+            ("round", vec![Value::Integer(5)], Value::Real(5.0)),
+        ];
+
+        for (name, args, want) in cases {
+            assert_eq!(call(&functions, name, &args), Ok(want), "{name}({args:?})");
+        }
+
+        assert_eq!(
+            call(&functions, "abs", &[Value::Integer(i64::MIN)]),
+            Err(String::from("integer overflow"))
+        );
+    }
+
+    #[test]
+    fn registry() {
+        let mut functions = Functions::default();
+        assert_eq!(
+            call(
+                &functions,
+                "COALESCE",
+                &[Value::Null, Value::Null, Value::Integer(3)]
+            ),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            functions.check("round", 3),
+            Err(String::from(
+                "wrong number of arguments to function round()"
+            ))
+        );
+        assert_eq!(
+            functions.check("nope", 1),
+            Err(String::from("no such function: nope"))
+        );
+
+        functions.register("double", Some(1), |args| match &args[0] {
+            Value::Integer(integer) => Ok(Value::Integer(integer * 2)),
+            _ => Ok(Value::Null),
+        });
+        assert_eq!(
+            call(&functions, "Double", &[Value::Integer(4)]),
+            Ok(Value::Integer(8))
+        );
+    }
+}
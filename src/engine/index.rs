@@ -1,23 +1,141 @@
-use crate::sql::{BinOp, Expr, Literal};
-
-use super::Value;
-
-pub fn deconstruct_simple_eq(expr: &Expr) -> Option<(&str, Value)> {
-    if let Expr::Binary(binop, l, r) = expr {
-        if let (BinOp::Eq, Expr::Literal(l), Expr::Literal(r)) = (binop, &**l, &**r) {
-            for (a, b) in [(l, r), (r, l)] {
-                if let Literal::Id(id) = a {
-                    let value = match b {
-                        Literal::Id(_) => continue,
-                        Literal::Integer(integer) => Value::Integer(*integer),
-                        Literal::Text(text) => Value::Text(text.clone()),
-                    };
-
-                    return Some((&id, value));
+use crate::sql::{BinOp, Expr, LikeOp, Literal};
+
+use super::{
+    pattern::{glob_prefix, like_prefix},
+    Value,
+};
+
+/// How an index narrows down the rows of a WHERE term
+#[derive(Debug, PartialEq)]
+pub enum IndexProbe {
+    /// Keys equal to one of the values, from `column = value` or `column IN (values)`
+    Keys(Vec<Value>),
+    /// Keys from the first value up to but excluding the second, from a LIKE or GLOB prefix
+    Range(Value, Value),
+}
+
+pub fn literal_value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => literal.constant_value(),
+        _ => None,
+    }
+}
+
+/// Returns the column and the probe of a term an index can answer:
+/// `column = constant`, `column IN (literals)` or `column LIKE 'prefix%'`.
+/// `constant` gives the value of the operand compared to a column, if it has a fixed one.
+/// The rows found still have to be checked against the term.
+pub fn deconstruct_index_term(
+    expr: &Expr,
+    constant: impl Fn(&str, &Expr) -> Option<Value>,
+) -> Option<(&str, IndexProbe)> {
+    match expr {
+        Expr::Binary(BinOp::Eq, l, r) => [(l, r), (r, l)].into_iter().find_map(|(a, b)| {
+            let Expr::Literal(Literal::Id(id)) = &**a else {
+                return None;
+            };
+            Some((&id[..], IndexProbe::Keys(vec![constant(id, b)?])))
+        }),
+        Expr::In {
+            expr,
+            not: false,
+            list,
+        } => {
+            let Expr::Literal(Literal::Id(id)) = &**expr else {
+                return None;
+            };
+            let values = list.iter().map(literal_value).collect::<Option<_>>()?;
+            Some((id, IndexProbe::Keys(values)))
+        }
+        Expr::Like {
+            op,
+            expr,
+            not: false,
+            pattern,
+            escape: None,
+        } => {
+            let (Expr::Literal(Literal::Id(id)), Expr::Literal(Literal::Text(pattern))) =
+                (&**expr, &**pattern)
+            else {
+                return None;
+            };
+            // Every case variant of a LIKE prefix sorts between its upper and its lower case
+            let (start, end) = match op {
+                LikeOp::Like => {
+                    let prefix = like_prefix(pattern);
+                    (prefix.to_ascii_uppercase(), prefix.to_ascii_lowercase())
+                }
+                LikeOp::Glob => {
+                    let prefix = glob_prefix(pattern);
+                    (prefix.to_owned(), prefix.to_owned())
                 }
+            };
+            if start.is_empty() {
+                return None;
             }
+            let end = successor(&end)?;
+            Some((id, IndexProbe::Range(Value::Text(start), Value::Text(end))))
         }
+        _ => None,
     }
+}
+
+/// The smallest text greater than every text starting with `prefix`
+fn successor(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    let last = chars.pop()?;
+    chars.push(char::from_u32(last as u32 + 1)?);
+    Some(chars.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{engine::Value, sql::parse_expr};
 
-    None
+    use super::{deconstruct_index_term, literal_value, IndexProbe};
+
+    #[test]
+    fn index_terms() {
+        let cases = [
+            (
+                "country = 'Italy'",
+                Some(("country", IndexProbe::Keys(vec![Value::from("Italy")]))),
+            ),
+            (
+                "3 = id",
+                Some(("id", IndexProbe::Keys(vec![Value::Integer(3)]))),
+            ),
+            (
+                "id IN (1, 2)",
+                Some((
+                    "id",
+                    IndexProbe::Keys(vec![Value::Integer(1), Value::Integer(2)]),
+                )),
+            ),
+            (
+                "name LIKE 'ab%c'",
+                Some((
+                    "name",
+                    IndexProbe::Range(Value::from("AB"), Value::from("ac")),
+                )),
+            ),
+            (
+                "name GLOB 'Ab*'",
+                Some((
+                    "name",
+                    IndexProbe::Range(Value::from("Ab"), Value::from("Ac")),
+                )),
+            ),
+            ("name LIKE '%b'", None),
+            ("name NOT IN (1)", None),
+            ("id IN (1, height)", None),
+            ("a = b", None),
+        ];
+
+        for (expr, want) in cases {
+            let expr = parse_expr(expr);
+            let got = deconstruct_index_term(&expr, |_, operand| literal_value(operand));
+            assert_eq!(got, want, "{expr:?}");
+        }
+    }
 }
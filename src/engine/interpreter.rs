@@ -1,69 +1,334 @@
 use std::{
-    iter,
-    ops::{Add, Div, Mul, Sub},
+    cmp::Ordering,
+    ops::{Add, BitAnd, BitOr, Div, Mul, Neg, Rem, Shl, Shr, Sub},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::sql::{BinOp, Expr, Literal};
+use crate::sql::{BinOp, CurrentTime, Expr, LikeOp, Literal, UnaryOp};
 
-use super::{Row, Table, Value};
+use super::{
+    affinity::Affinity,
+    collation::Collation,
+    pattern::{glob, like},
+    Row, TableHeader, Value,
+};
 
 impl BinOp {
+    fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq
+                | BinOp::Neq
+                | BinOp::Lt
+                | BinOp::Lte
+                | BinOp::Gt
+                | BinOp::Gte
+                | BinOp::Is
+                | BinOp::IsNot
+        )
+    }
+
     fn eval(&self, l: &Value, r: &Value) -> Value {
         match self {
             BinOp::Or => l.or(r),
             BinOp::And => l.and(r),
-            BinOp::Eq => Value::from(l == r),
-            BinOp::Neq => Value::from(l != r),
-            BinOp::Lt => Value::from(l < r),
-            BinOp::Lte => Value::from(l <= r),
-            BinOp::Gt => Value::from(l > r),
-            BinOp::Gte => Value::from(l >= r),
+            BinOp::Eq => l.compare_nullable(r, Ordering::is_eq),
+            BinOp::Neq => l.compare_nullable(r, Ordering::is_ne),
+            BinOp::Lt => l.compare_nullable(r, Ordering::is_lt),
+            BinOp::Lte => l.compare_nullable(r, Ordering::is_le),
+            BinOp::Gt => l.compare_nullable(r, Ordering::is_gt),
+            BinOp::Gte => l.compare_nullable(r, Ordering::is_ge),
             BinOp::Add => l.add(r),
             BinOp::Sub => l.sub(r),
             BinOp::Mul => l.mul(r),
             BinOp::Div => l.div(r),
+            BinOp::Is => Value::from(is(l, r)),
+            BinOp::IsNot => Value::from(!is(l, r)),
+            BinOp::Concat => l.concat(r),
+            BinOp::Rem => l.rem(r),
+            BinOp::BitAnd => l.bitand(r),
+            BinOp::BitOr => l.bitor(r),
+            BinOp::Shl => l.shl(r),
+            BinOp::Shr => l.shr(r),
         }
     }
 }
 
-impl Literal {
-    fn eval_select(self, table: &Table) -> Box<dyn Iterator<Item = Value>> {
+impl UnaryOp {
+    fn eval(&self, value: &Value) -> Value {
         match self {
-            Literal::Integer(x) => Box::new(iter::repeat(Value::Integer(x))),
-            Literal::Text(x) => Box::new(iter::repeat(Value::Text(x))),
-            Literal::Id(id) => Box::new(table.get(&id).cloned().collect::<Vec<_>>().into_iter()),
+            UnaryOp::Not => value.not(),
+            UnaryOp::Neg => value.neg(),
+            UnaryOp::Plus => value.clone(),
+            UnaryOp::BitNot => value.bit_not(),
         }
     }
 }
 
-impl Expr {
-    pub fn eval_select(self, table: &Table) -> Box<dyn Iterator<Item = Value>> {
-        match self {
-            Expr::Binary(op, l, r) => Box::new(
-                l.eval_select(table)
-                    .zip(r.eval_select(table))
-                    .map(move |(l, r)| op.eval(&l, &r)),
-            ),
-            Expr::Literal(literal) => literal.eval_select(table),
-        }
+/// `IS` compares like `=`, except that NULL is equal to NULL and not to any other value
+fn is(l: &Value, r: &Value) -> bool {
+    match (l, r) {
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        (l, r) => l.compare(r).is_eq(),
     }
 }
 
 impl Literal {
+    /// The value of a literal that depends on neither the row nor the time
+    pub fn constant_value(&self) -> Option<Value> {
+        match self {
+            Literal::Null => Some(Value::Null),
+            Literal::Integer(x) => Some(Value::Integer(*x)),
+            Literal::Real(x) => Some(Value::Real(*x)),
+            Literal::Text(x) => Some(Value::Text(x.clone())),
+            Literal::Blob(x) => Some(Value::Blob(x.clone())),
+            Literal::Id(_) | Literal::CurrentTime(_) => None,
+        }
+    }
+
     fn eval_where(&self, row: &Row) -> Value {
         match self {
-            Literal::Integer(x) => Value::Integer(*x),
-            Literal::Text(x) => Value::Text(x.clone()),
-            Literal::Id(id) => row[&id].clone(),
+            Literal::Id(id) => row[id].clone(),
+            Literal::CurrentTime(kind) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                Value::Text(format_time(*kind, now.as_secs() as i64))
+            }
+            literal => literal.constant_value().unwrap(),
+        }
+    }
+}
+
+/// The literal a subquery is replaced by, from a value of its result
+impl From<Value> for Literal {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Literal::Null,
+            Value::Integer(x) => Literal::Integer(x),
+            Value::Real(x) => Literal::Real(x),
+            Value::Text(x) => Literal::Text(x),
+            Value::Blob(x) => Literal::Blob(x),
         }
     }
 }
 
+/// Formats the UTC time `secs` seconds after 1970-01-01 00:00:00.
+/// The date follows http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn format_time(kind: CurrentTime, secs: i64) -> String {
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Days since 0000-03-01, years start in March so leap days come last
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + i64::from(month <= 2);
+
+    let date = format!("{year:04}-{month:02}-{day:02}");
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    match kind {
+        CurrentTime::Time => time,
+        CurrentTime::Date => date,
+        CurrentTime::Timestamp => format!("{date} {time}"),
+    }
+}
+
 impl Expr {
-    pub fn eval_where(&self, row: &Row) -> Value {
+    /// The affinity of the value of the expression, only columns have one
+    pub fn affinity(&self, table_header: &TableHeader) -> Affinity {
         match self {
-            Expr::Binary(op, l, r) => op.eval(&l.eval_where(row), &r.eval_where(row)),
-            Expr::Literal(literal) => literal.eval_where(row),
+            Expr::Literal(Literal::Id(id)) => table_header.affinity(id),
+            Expr::Cast { type_name, .. } => Affinity::from_type_name(type_name),
+            _ => Affinity::Blob,
+        }
+    }
+
+    /// The collation of the value of the expression, only columns have one
+    pub fn collation(&self, table_header: &TableHeader) -> Option<Collation> {
+        match self {
+            Expr::Literal(Literal::Id(id)) => table_header.collation(id),
+            _ => None,
         }
     }
+
+    /// Fails with the error of a function call.
+    pub fn eval_where(&self, row: &Row) -> Result<Value, String> {
+        Ok(match self {
+            // The operands are converted to a common affinity and compared with a common collation
+            Expr::Binary(op, l, r) if op.is_comparison() => {
+                let (l, r) = eval_compared(l, r, row)?;
+                op.eval(&l, &r)
+            }
+            Expr::Binary(op, l, r) => op.eval(&l.eval_where(row)?, &r.eval_where(row)?),
+            Expr::Unary(op, expr) => op.eval(&expr.eval_where(row)?),
+            // With an operand the first WHEN equal to it is taken, otherwise the first true one
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => {
+                for (when, then) in when_then {
+                    let found = match operand {
+                        Some(operand) => {
+                            let (operand, when) = eval_compared(operand, when, row)?;
+                            BinOp::Eq.eval(&operand, &when)
+                        }
+                        None => when.eval_where(row)?,
+                    };
+                    if bool::from(&found) {
+                        return then.eval_where(row);
+                    }
+                }
+                match else_result {
+                    Some(else_result) => else_result.eval_where(row)?,
+                    None => Value::Null,
+                }
+            }
+            Expr::Cast { expr, type_name } => {
+                Affinity::from_type_name(type_name).cast(expr.eval_where(row)?)
+            }
+            Expr::Between {
+                expr,
+                not,
+                low,
+                high,
+            } => {
+                let value = expr.eval_where(row)?;
+                let compare = |op: BinOp, bound: &Expr| {
+                    let affinity =
+                        Affinity::for_comparison(row.affinity(expr), row.affinity(bound));
+                    let collation = comparison_collation(expr, bound, row);
+                    Ok::<_, String>(op.eval(
+                        &collation.key(affinity.apply(value.clone())),
+                        &collation.key(affinity.apply(bound.eval_where(row)?)),
+                    ))
+                };
+                negate_if(
+                    compare(BinOp::Gte, low)?.and(&compare(BinOp::Lte, high)?),
+                    *not,
+                )
+            }
+            // The values in the list have no affinity and no collation
+            Expr::In { expr, not, list } => {
+                let affinity = Affinity::for_comparison(row.affinity(expr), Affinity::Blob);
+                let collation = row.collation(expr).unwrap_or_default();
+                let value = collation.key(affinity.apply(expr.eval_where(row)?));
+                let mut result = Value::from(false);
+                for item in list {
+                    let item = collation.key(affinity.apply(item.eval_where(row)?));
+                    match BinOp::Eq.eval(&value, &item) {
+                        Value::Null => result = Value::Null,
+                        found if bool::from(&found) => return Ok(negate_if(found, *not)),
+                        _ => {}
+                    }
+                }
+                negate_if(result, *not)
+            }
+            Expr::Like {
+                op,
+                expr,
+                not,
+                pattern,
+                escape,
+            } => {
+                let escape = match escape {
+                    Some(escape) => Some(escape.eval_where(row)?),
+                    None => None,
+                };
+                let (Some(text), Some(pattern)) = (
+                    expr.eval_where(row)?.to_text(),
+                    pattern.eval_where(row)?.to_text(),
+                ) else {
+                    return Ok(Value::Null);
+                };
+                let is_match = match (op, escape.map(|escape| escape.to_text())) {
+                    (LikeOp::Like, None) => like(&pattern, &text, None),
+                    (LikeOp::Like, Some(Some(escape))) => {
+                        let mut chars = escape.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(escape), None) => like(&pattern, &text, Some(escape)),
+                            // The escape must be a single character
+                            _ => false,
+                        }
+                    }
+                    (LikeOp::Like, Some(None)) => return Ok(Value::Null),
+                    (LikeOp::Glob, _) => glob(&pattern, &text),
+                };
+                Value::from(is_match != *not)
+            }
+            Expr::Literal(literal) => literal.eval_where(row),
+            // Aggregate calls are replaced by columns before evaluation
+            Expr::Function(function) => {
+                let args = (function.args.iter())
+                    .map(|arg| arg.eval_where(row))
+                    .collect::<Result<Vec<_>, String>>()?;
+                let Some(scalar) = row.functions.find(&function.name, args.len()) else {
+                    panic!("internal error: no such function: {}", function.name)
+                };
+                scalar(&args)?
+            }
+            // Subqueries are run by the engine before evaluation
+            Expr::Subquery(_) | Expr::InSelect { .. } | Expr::Exists(_) => {
+                panic!("internal error: subquery was not run")
+            }
+        })
+    }
+}
+
+/// Evaluates the operands of a comparison, converted to a common affinity,
+/// as the keys of the collation they are compared with
+fn eval_compared(l: &Expr, r: &Expr, row: &Row) -> Result<(Value, Value), String> {
+    let affinity = Affinity::for_comparison(row.affinity(l), row.affinity(r));
+    let collation = comparison_collation(l, r, row);
+    Ok((
+        collation.key(affinity.apply(l.eval_where(row)?)),
+        collation.key(affinity.apply(r.eval_where(row)?)),
+    ))
+}
+
+/// The collation of the left operand if it is a column, else that of the right operand
+fn comparison_collation(l: &Expr, r: &Expr, row: &Row) -> Collation {
+    (row.collation(l).or_else(|| row.collation(r))).unwrap_or_default()
+}
+
+fn negate_if(value: Value, not: bool) -> Value {
+    match not {
+        true => value.not(),
+        false => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::CurrentTime;
+
+    use super::format_time;
+
+    #[test]
+    fn current_time() {
+        assert_eq!(
+            format_time(CurrentTime::Timestamp, 0),
+            "1970-01-01 00:00:00"
+        );
+        assert_eq!(format_time(CurrentTime::Date, 951782400), "2000-02-29");
+        assert_eq!(format_time(CurrentTime::Time, 1700000000), "22:13:20");
+        assert_eq!(
+            format_time(CurrentTime::Timestamp, 1709251199),
+            "2024-02-29 23:59:59"
+        );
+    }
 }
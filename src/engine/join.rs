@@ -0,0 +1,447 @@
+use std::{collections::HashMap, mem};
+
+use crate::sql::{BinOp, Expr, JoinKind, Literal};
+
+use super::{
+    affinity::Affinity,
+    aggregate::hash_key,
+    collation::Collation,
+    function::Functions,
+    sorter::{compare_sort_keys, SortKey, Sorter, DEFAULT_SORT_MEMORY},
+    Record, Row, TableHeader, Value,
+};
+
+/// How the rows of the inner table matching a row of the outer side are found
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InnerAccess {
+    /// Every row of the inner table is tested
+    Scan,
+    /// The rows are looked up by the rowid given by the join key at position `key`
+    Rowid { key: usize },
+    /// The rowids are looked up in an index first
+    Index { key: usize, rootpage: u32 },
+}
+
+impl InnerAccess {
+    /// Position of the join key the inner rows are looked up by
+    pub fn key(&self) -> usize {
+        match self {
+            InnerAccess::Scan => panic!("internal error: a scan has no lookup key"),
+            InnerAccess::Rowid { key } | InnerAccess::Index { key, .. } => *key,
+        }
+    }
+}
+
+/// Which combinations of an outer row and inner rows with equal join keys are kept
+pub struct JoinFilter<'a> {
+    pub kind: JoinKind,
+    pub on: Option<&'a Expr>,
+    /// Positions of the USING columns in the outer and in the inner row,
+    /// with the affinity and the collation the two values are compared with
+    pub using_positions: &'a [(usize, usize, Affinity, Collation)],
+    pub table_header: &'a TableHeader,
+    pub inner_len: usize,
+    pub functions: &'a Functions,
+    /// The row of the enclosing query the ON clause may refer to
+    pub outer: Option<&'a Row<'a>>,
+}
+
+impl JoinFilter<'_> {
+    /// Appends the combinations of the outer record with the matching inner records,
+    /// in a LEFT JOIN the outer record padded with NULLs if none matches.
+    pub fn join_row<'r>(
+        &self,
+        outer_record: Record,
+        inner_records: impl IntoIterator<Item = &'r Record>,
+        records: &mut Vec<Record>,
+    ) -> Result<(), String> {
+        let mut is_matched = false;
+        for inner_record in inner_records {
+            let is_using_match =
+                (self.using_positions.iter()).all(|(outer_idx, inner_idx, affinity, collation)| {
+                    let outer_value = affinity.apply(outer_record.values[*outer_idx].clone());
+                    let inner_value = affinity.apply(inner_record.values[*inner_idx].clone());
+                    collation.compare(&outer_value, &inner_value).is_eq()
+                });
+            if !is_using_match {
+                continue;
+            }
+
+            let mut values = outer_record.values.clone();
+            values.extend(inner_record.values.iter().cloned());
+            let row = Row::new(self.table_header, Record::new(0, values), self.functions)
+                .with_outer(self.outer);
+            let is_on_match = match self.on {
+                Some(on) => bool::from(&on.eval_where(&row)?),
+                None => true,
+            };
+            if is_on_match {
+                records.push(row.record);
+                is_matched = true;
+            }
+        }
+
+        if !is_matched && self.kind == JoinKind::Left {
+            let mut values = outer_record.values;
+            values.extend(vec![Value::Null; self.inner_len]);
+            records.push(Record::new(0, values));
+        }
+        Ok(())
+    }
+}
+
+/// Joins by building a hash table of the inner rows on their join keys
+/// and probing it with the join keys of each outer row, the outer order is kept.
+pub fn hash_join(
+    outer: (&TableHeader, Vec<Record>),
+    inner: (&TableHeader, Vec<Record>),
+    equi_keys: &[(Expr, Expr)],
+    filter: &JoinFilter,
+) -> Result<Vec<Record>, String> {
+    let (outer_header, outer_records) = outer;
+    let (inner_header, inner_records) = inner;
+    let (outer_keys, inner_keys) = key_exprs(outer_header, inner_header, equi_keys);
+
+    let mut hash_table: HashMap<Vec<u8>, Vec<Record>> = HashMap::new();
+    for inner_record in inner_records {
+        let row = Row::new(inner_header, inner_record, filter.functions);
+        let keys = eval_keys(&inner_keys, &row)?;
+        // NULL equals nothing
+        if !keys.contains(&Value::Null) {
+            hash_table
+                .entry(hash_key(&keys))
+                .or_default()
+                .push(row.record);
+        }
+    }
+
+    let mut records = vec![];
+    for outer_record in outer_records {
+        let row = Row::new(outer_header, outer_record, filter.functions);
+        let keys = eval_keys(&outer_keys, &row)?;
+        let matches = match keys.contains(&Value::Null) {
+            true => None,
+            false => hash_table.get(&hash_key(&keys)),
+        };
+        filter.join_row(row.record, matches.into_iter().flatten(), &mut records)?;
+    }
+    Ok(records)
+}
+
+/// Joins by sorting both sides on their join keys and merging them,
+/// the rows come out in the order of the join keys.
+pub fn merge_join(
+    outer: (&TableHeader, Vec<Record>),
+    inner: (&TableHeader, Vec<Record>),
+    equi_keys: &[(Expr, Expr)],
+    filter: &JoinFilter,
+) -> Result<Vec<Record>, String> {
+    let sort_key = SortKey {
+        collation: Collation::Binary,
+        desc: false,
+        nulls_first: true,
+    };
+    let sort_keys = vec![sort_key; equi_keys.len()];
+    let (outer_keys, inner_keys) = key_exprs(outer.0, inner.0, equi_keys);
+    let outer_sorted = sort_by_keys(outer, &outer_keys, &sort_keys, filter.functions)?;
+    let mut inner_sorted =
+        sort_by_keys(inner, &inner_keys, &sort_keys, filter.functions)?.peekable();
+
+    let mut records = vec![];
+    let mut group: Option<(Vec<Value>, Vec<Record>)> = None;
+    for (keys, outer_record) in outer_sorted {
+        if keys.contains(&Value::Null) {
+            filter.join_row(outer_record, [], &mut records)?;
+            continue;
+        }
+
+        let is_same_group = match &group {
+            Some((group_keys, _)) => compare_sort_keys(&sort_keys, group_keys, &keys).is_eq(),
+            None => false,
+        };
+        if !is_same_group {
+            // Inner rows with NULL keys sort first and are skipped here
+            while let Some((inner_keys, _)) = inner_sorted.peek() {
+                if !compare_sort_keys(&sort_keys, inner_keys, &keys).is_lt() {
+                    break;
+                }
+                inner_sorted.next();
+            }
+
+            let mut inner_records = vec![];
+            while let Some((inner_keys, _)) = inner_sorted.peek() {
+                if !compare_sort_keys(&sort_keys, inner_keys, &keys).is_eq() {
+                    break;
+                }
+                inner_records.push(inner_sorted.next().unwrap().1);
+            }
+            group = Some((keys, inner_records));
+        }
+
+        let (_, inner_records) = group.as_ref().unwrap();
+        filter.join_row(outer_record, inner_records, &mut records)?;
+    }
+    Ok(records)
+}
+
+/// A join key expression with the affinity and the collation its values are compared with
+type KeyExpr<'e> = (&'e Expr, Affinity, Collation);
+
+/// The outer and the inner key expressions, each with the affinity of the comparison
+/// of the two sides and the collation of the outer side, or else of the inner side
+fn key_exprs<'e>(
+    outer_header: &TableHeader,
+    inner_header: &TableHeader,
+    equi_keys: &'e [(Expr, Expr)],
+) -> (Vec<KeyExpr<'e>>, Vec<KeyExpr<'e>>) {
+    equi_keys
+        .iter()
+        .map(|(outer, inner)| {
+            let affinity = Affinity::for_comparison(
+                outer.affinity(outer_header),
+                inner.affinity(inner_header),
+            );
+            let collation = (outer.collation(outer_header))
+                .or_else(|| inner.collation(inner_header))
+                .unwrap_or_default();
+            ((outer, affinity, collation), (inner, affinity, collation))
+        })
+        .unzip()
+}
+
+/// The values of the key expressions as the keys of their collations,
+/// equal values have equal keys
+fn eval_keys(keys: &[KeyExpr], row: &Row) -> Result<Vec<Value>, String> {
+    keys.iter()
+        .map(|(expr, affinity, collation)| Ok(collation.key(affinity.apply(expr.eval_where(row)?))))
+        .collect()
+}
+
+/// Sorts the records on the values of the key expressions, yielding the keys with each record.
+fn sort_by_keys(
+    input: (&TableHeader, Vec<Record>),
+    keys: &[KeyExpr],
+    sort_keys: &[SortKey],
+    functions: &Functions,
+) -> Result<impl Iterator<Item = (Vec<Value>, Record)>, String> {
+    let (table_header, records) = input;
+    let key_cnt = sort_keys.len();
+
+    // The keys travel in front of the values
+    let mut sorter = Sorter::new(sort_keys.to_vec(), DEFAULT_SORT_MEMORY);
+    for record in records {
+        let row = Row::new(table_header, record, functions);
+        let mut values = eval_keys(keys, &row)?;
+        let keys = values.clone();
+        values.extend(row.record.values);
+        sorter.push(keys, Record::new(row.record.rowid, values));
+    }
+
+    Ok(sorter.finish().map(move |mut record| {
+        let values = record.values.split_off(key_cnt);
+        let keys = mem::replace(&mut record.values, values);
+        (keys, record)
+    }))
+}
+
+/// Splits an expression at its top-level ANDs.
+pub fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Binary(BinOp::And, l, r) => {
+            let mut terms = conjuncts(l);
+            terms.extend(conjuncts(r));
+            terms
+        }
+        expr => vec![expr],
+    }
+}
+
+/// Returns the columns the expression refers to.
+pub fn column_refs(expr: &Expr) -> Vec<&str> {
+    match expr {
+        Expr::Literal(Literal::Id(id)) => vec![id],
+        expr => expr.children().into_iter().flat_map(column_refs).collect(),
+    }
+}
+
+/// Finds the terms `inner_expr = outer_expr` of the join condition
+/// where each side only depends on one side of the join,
+/// so rows can be matched by the values of the two expressions.
+/// Returns the pairs of outer and inner expressions.
+pub fn equi_join_keys<'a>(
+    on: &'a Expr,
+    outer_header: &TableHeader,
+    inner_header: &TableHeader,
+) -> Vec<(&'a Expr, &'a Expr)> {
+    let is_over = |expr: &Expr, header: &TableHeader, other: &TableHeader| {
+        column_refs(expr)
+            .into_iter()
+            .all(|id| header.resolve(id).is_ok() && other.resolve(id).is_err())
+    };
+
+    conjuncts(on)
+        .into_iter()
+        .filter_map(|conjunct| {
+            let Expr::Binary(BinOp::Eq, l, r) = conjunct else {
+                return None;
+            };
+
+            [(&**l, &**r), (&**r, &**l)]
+                .into_iter()
+                .find(|(outer, inner)| {
+                    !column_refs(inner).is_empty()
+                        && is_over(inner, inner_header, outer_header)
+                        && is_over(outer, outer_header, inner_header)
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        engine::{collation::Collation, function::Functions, Record, TableHeader, Value},
+        sql::{parse_expr, Expr, JoinKind},
+    };
+
+    use super::{equi_join_keys, hash_join, merge_join, JoinFilter};
+
+    #[test]
+    fn equi_join_term() {
+        let outer_header = TableHeader::qualified("a", &["id", "b_id"]);
+        let inner_header = TableHeader::qualified("b", &["id", "name"]);
+
+        let on = parse_expr("b.name <> 'x' AND a.b_id + 1 = b.id AND upper(b.name) = 'X'");
+        let equi_keys = equi_join_keys(&on, &outer_header, &inner_header);
+        assert_eq!(
+            equi_keys,
+            [
+                (&parse_expr("a.b_id + 1"), &parse_expr("b.id")),
+                (&parse_expr("'X'"), &parse_expr("upper(b.name)"))
+            ]
+        );
+
+        // Both sides depend on the inner table
+        let on = parse_expr("b.id = b.name");
+        assert!(equi_join_keys(&on, &outer_header, &inner_header).is_empty());
+
+        // Unqualified names present on both sides are ambiguous
+        let on = parse_expr("id = b_id");
+        assert!(equi_join_keys(&on, &outer_header, &inner_header).is_empty());
+    }
+
+    fn records(rows: &[(i64, Option<&str>)]) -> Vec<Record> {
+        rows.iter()
+            .map(|(id, name)| {
+                let name = name.map_or(Value::Null, |name| Value::Text(String::from(name)));
+                Record::new(*id, vec![Value::Integer(*id), name])
+            })
+            .collect()
+    }
+
+    fn to_strings(records: Vec<Record>) -> Vec<String> {
+        records.iter().map(|record| record.to_string()).collect()
+    }
+
+    #[test]
+    fn hash_and_merge_join() {
+        let outer_header = TableHeader::qualified("a", &["id", "name"]);
+        let inner_header = TableHeader::qualified("b", &["id", "name"]);
+        let table_header = outer_header.join(&inner_header, &[]);
+        let outer = records(&[(1, Some("x")), (2, Some("y")), (3, None), (4, Some("x"))]);
+        let inner = records(&[(5, Some("x")), (6, None), (7, Some("z")), (8, Some("x"))]);
+
+        let on = parse_expr("a.name = b.name AND b.id > a.id + 1");
+        let equi_keys: Vec<(Expr, Expr)> = equi_join_keys(&on, &outer_header, &inner_header)
+            .into_iter()
+            .map(|(outer, inner)| (outer.clone(), inner.clone()))
+            .collect();
+        let filter = JoinFilter {
+            kind: JoinKind::Left,
+            on: Some(&on),
+            using_positions: &[],
+            table_header: &table_header,
+            inner_len: inner_header.len(),
+            functions: &Functions::default(),
+            outer: None,
+        };
+
+        let records = hash_join(
+            (&outer_header, outer.clone()),
+            (&inner_header, inner.clone()),
+            &equi_keys,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(
+            to_strings(records),
+            [
+                "1|x|5|x",
+                "1|x|8|x",
+                "2|y|null|null",
+                "3|null|null|null",
+                "4|x|8|x"
+            ]
+        );
+
+        // NULL keys come first
+        let records = merge_join(
+            (&outer_header, outer),
+            (&inner_header, inner),
+            &equi_keys,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(
+            to_strings(records),
+            [
+                "3|null|null|null",
+                "1|x|5|x",
+                "1|x|8|x",
+                "4|x|8|x",
+                "2|y|null|null"
+            ]
+        );
+    }
+
+    #[test]
+    fn join_with_collation() {
+        let outer_header = TableHeader::qualified("a", &["id", "name"])
+            .with_collations(vec![Collation::Binary, Collation::NoCase]);
+        let inner_header = TableHeader::qualified("b", &["id", "name"]);
+        let table_header = outer_header.join(&inner_header, &[]);
+        let outer = records(&[(1, Some("x")), (2, Some("Y"))]);
+        let inner = records(&[(5, Some("X")), (6, Some("y")), (7, Some("x"))]);
+
+        // The NOCASE collation of the outer column applies
+        let on = parse_expr("a.name = b.name");
+        let equi_keys = vec![(parse_expr("a.name"), parse_expr("b.name"))];
+        let filter = JoinFilter {
+            kind: JoinKind::Inner,
+            on: Some(&on),
+            using_positions: &[],
+            table_header: &table_header,
+            inner_len: inner_header.len(),
+            functions: &Functions::default(),
+            outer: None,
+        };
+
+        let want = ["1|x|5|X", "1|x|7|x", "2|Y|6|y"];
+        let records = hash_join(
+            (&outer_header, outer.clone()),
+            (&inner_header, inner.clone()),
+            &equi_keys,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(to_strings(records), want);
+        let records = merge_join(
+            (&outer_header, outer),
+            (&inner_header, inner),
+            &equi_keys,
+            &filter,
+        )
+        .unwrap();
+        assert_eq!(to_strings(records), want);
+    }
+}
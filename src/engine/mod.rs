@@ -1,16 +1,27 @@
+mod affinity;
+mod aggregate;
+mod collation;
+mod cte;
 mod dbinfo;
+#[allow(clippy::module_inception)]
 mod engine;
+mod function;
 mod index;
 mod interpreter;
+mod join;
+mod pattern;
+mod planner;
+mod printf;
 mod record;
 mod row;
+mod sorter;
 mod sqlite_object;
 mod sqlite_schema;
 mod table;
 mod table_header;
 mod value;
 
-use std::fs::File;
+use std::path::Path;
 
 pub use dbinfo::DBInfo;
 pub use engine::Engine;
@@ -22,18 +33,48 @@ pub use table::Table;
 pub use table_header::TableHeader;
 pub use value::Value;
 
+pub use crate::sqlite_file::FileBackend;
+
 use crate::{sqlite_file::SQLiteFile, sqlite_storage::SQLiteStorage};
 
 pub trait Storage {
     fn get_dbinfo(&mut self) -> DBInfo;
     fn get_schema(&mut self) -> SQLiteSchema;
     fn search_table(&mut self, page_no: u32, rowids: Option<&[i64]>) -> Vec<Record>;
+    /// Like `search_table`, but yields the records one at a time as they are read.
+    fn scan_table<'a>(
+        &'a mut self,
+        page_no: u32,
+        rowids: Option<Vec<i64>>,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        Box::new(self.search_table(page_no, rowids.as_deref()).into_iter())
+    }
     fn search_index(&mut self, page_no: u32, value: &Value) -> Vec<i64>;
+    /// Rowids of the index keys from `start` up to but excluding `end`, in key order.
+    fn search_index_range(&mut self, page_no: u32, start: &Value, end: &Value) -> Vec<i64>;
+    fn max_rowid(&mut self, page_no: u32) -> Option<i64>;
+    /// Number of leaf pages of the b-tree, used to estimate its size.
+    fn count_leaf_pages(&mut self, page_no: u32) -> usize;
+    fn insert_record(&mut self, page_no: u32, record: &Record);
+    fn insert_index_key(&mut self, page_no: u32, key: &[Value]);
+    fn delete_record(&mut self, page_no: u32, rowid: i64);
+    fn delete_index_key(&mut self, page_no: u32, key: &[Value]);
+    fn begin_transaction(&mut self) -> Result<(), String>;
+    fn commit(&mut self);
+    fn rollback(&mut self);
+    /// Within a transaction, marks the start of a statement that can be rolled back alone.
+    fn begin_statement(&mut self);
+    fn commit_statement(&mut self);
+    fn rollback_statement(&mut self);
+    fn set_cache_size(&mut self, cache_size: i64);
 }
 
 pub fn new_engine(file_path: &str) -> Engine<SQLiteStorage> {
-    let file = File::open(file_path).unwrap();
-    let sqlite_file = SQLiteFile::new(file);
+    new_engine_with_backend(file_path, FileBackend::Pread)
+}
+
+pub fn new_engine_with_backend(file_path: &str, backend: FileBackend) -> Engine<SQLiteStorage> {
+    let sqlite_file = SQLiteFile::open(Path::new(file_path), backend);
     let storage = SQLiteStorage::new(sqlite_file);
     Engine::new(storage)
 }
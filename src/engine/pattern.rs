@@ -0,0 +1,193 @@
+/// One element of a LIKE or GLOB pattern
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// `%` or `*`, any sequence of characters
+    Any,
+    /// `_` or `?`, a single character
+    One,
+    /// `[...]` of GLOB, a character in or, if negated, not in one of the ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Char(char),
+}
+
+impl Token {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        match self {
+            Token::Any | Token::One => true,
+            Token::Class { negated, ranges } => {
+                ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) != *negated
+            }
+            Token::Char(p) if ignore_case => p.eq_ignore_ascii_case(&c),
+            Token::Char(p) => *p == c,
+        }
+    }
+}
+
+/// Whether `text` matches the LIKE `pattern`, ignoring the case of ASCII letters.
+/// `%` matches any sequence and `_` any single character, unless preceded by `escape`.
+pub fn like(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(c) => Token::Char(c),
+                // An escape character must escape something
+                None => return false,
+            },
+            '%' => Token::Any,
+            '_' => Token::One,
+            c => Token::Char(c),
+        });
+    }
+
+    matches(&tokens, text, true)
+}
+
+/// Whether `text` matches the GLOB `pattern`, case-sensitively.
+/// `*` matches any sequence, `?` any single character
+/// and `[...]` one of a set of characters such as `[a-z_]`, or with `[^...]` any other character.
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => Token::Any,
+            '?' => Token::One,
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = vec![];
+                // A `]` right after the opening bracket is part of the set
+                let mut first = true;
+                loop {
+                    let from = match chars.next() {
+                        Some(']') if !first => break,
+                        Some(from) => from,
+                        // An unterminated set matches nothing
+                        None => return false,
+                    };
+                    first = false;
+
+                    let to = match chars.peek() {
+                        Some('-') => {
+                            chars.next();
+                            match chars.next_if(|c| *c != ']') {
+                                Some(to) => to,
+                                None => {
+                                    ranges.push(('-', '-'));
+                                    from
+                                }
+                            }
+                        }
+                        _ => from,
+                    };
+                    ranges.push((from, to));
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Char(c),
+        });
+    }
+
+    matches(&tokens, text, false)
+}
+
+/// Matches the tokens left to right, on a mismatch the last `Any` takes one more character.
+fn matches(tokens: &[Token], text: &str, ignore_case: bool) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position after the last `Any` and the text it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Any) => {
+                p += 1;
+                backtrack = Some((p, t));
+                continue;
+            }
+            Some(token) if token.matches(text[t], ignore_case) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        match backtrack {
+            Some((any_p, any_t)) => {
+                backtrack = Some((any_p, any_t + 1));
+                p = any_p;
+                t = any_t + 1;
+            }
+            None => return false,
+        }
+    }
+
+    tokens[p..].iter().all(|token| *token == Token::Any)
+}
+
+/// The text before the first wildcard, which every matching text starts with
+pub fn like_prefix(pattern: &str) -> &str {
+    &pattern[..pattern.find(['%', '_']).unwrap_or(pattern.len())]
+}
+
+/// The text before the first wildcard of a GLOB pattern
+pub fn glob_prefix(pattern: &str) -> &str {
+    &pattern[..pattern.find(['*', '?', '[']).unwrap_or(pattern.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob, like};
+
+    #[test]
+    fn like_patterns() {
+        let cases = [
+            ("abc", "ABC", true),
+            ("a%", "abc", true),
+            ("a%", "bac", false),
+            ("%b%", "abc", true),
+            ("%c", "abcabc", true),
+            ("a_c", "abc", true),
+            ("a_c", "ac", false),
+            ("%a%b", "aXbXab", true),
+            ("%", "", true),
+            ("_", "", false),
+            ("é", "É", false),
+        ];
+
+        for (pattern, text, want) in cases {
+            assert_eq!(like(pattern, text, None), want, "{text} LIKE {pattern}");
+        }
+
+        assert!(like("10!%", "10%", Some('!')));
+        assert!(!like("10!%", "100", Some('!')));
+        assert!(like("a!_%", "a_b", Some('!')));
+        assert!(!like("a!", "a", Some('!')));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        let cases = [
+            ("a*", "abc", true),
+            ("A*", "abc", false),
+            ("a?c", "abc", true),
+            ("*[0-9]", "abc7", true),
+            ("*[0-9]", "abc", false),
+            ("[^a-c]*", "dog", true),
+            ("[^a-c]*", "cat", false),
+            ("[]x]", "]", true),
+            ("[a-]", "-", true),
+            ("[abc", "a", false),
+            ("*.rs", "main.rs", true),
+        ];
+
+        for (pattern, text, want) in cases {
+            assert_eq!(glob(pattern, text), want, "{text} GLOB {pattern}");
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use crate::sql::Expr;
+
+use super::{
+    affinity::Affinity, collation::Collation, join::InnerAccess, sorter::DEFAULT_SORT_MEMORY,
+    Record, TableHeader, Value,
+};
+
+/// Rows assumed per leaf page of a table without statistics
+pub const ROWS_PER_PAGE: f64 = 40.0;
+/// Rows assumed to share a key of an index without statistics
+pub const ROWS_PER_KEY: f64 = 10.0;
+/// Bytes assumed per row when deciding whether a hash table fits in memory
+const ROW_SIZE: f64 = 100.0;
+
+/// Row counts collected by ANALYZE in the sqlite_stat1 table.
+/// https://www.sqlite.org/fileformat2.html#stat1tab
+#[derive(Debug, Default)]
+pub struct Statistics {
+    table_rows: HashMap<String, f64>,
+    rows_per_key: HashMap<String, f64>,
+}
+
+impl Statistics {
+    /// Reads the `(tbl, idx, stat)` records of sqlite_stat1.
+    /// The stat starts with the number of rows of the table,
+    /// for an index it is followed by the average number of rows sharing a key prefix.
+    pub fn from_records(records: impl Iterator<Item = Record>) -> Self {
+        let mut statistics = Self::default();
+
+        for record in records {
+            let (Value::Text(tbl), Value::Text(stat)) = (&record.values[0], &record.values[2])
+            else {
+                continue;
+            };
+            let numbers: Vec<f64> = stat
+                .split_whitespace()
+                .map_while(|number| number.parse().ok())
+                .collect();
+
+            if let Some(rows) = numbers.first() {
+                statistics.table_rows.insert(tbl.clone(), *rows);
+            }
+            if let (Value::Text(idx), Some(rows_per_key)) = (&record.values[1], numbers.get(1)) {
+                statistics.rows_per_key.insert(idx.clone(), *rows_per_key);
+            }
+        }
+
+        statistics
+    }
+
+    pub fn table_rows(&self, table_name: &str) -> Option<f64> {
+        self.table_rows.get(table_name).copied()
+    }
+
+    pub fn rows_per_key(&self, index_name: &str) -> Option<f64> {
+        self.rows_per_key.get(index_name).copied()
+    }
+}
+
+/// Estimated size of one side of a join
+#[derive(Clone, Copy, Debug)]
+pub struct Input {
+    pub rows: f64,
+    /// Whether the rows already come in the order of the join keys
+    pub is_sorted: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinStrategy {
+    NestedLoop(InnerAccess),
+    HashJoin,
+    MergeJoin,
+}
+
+/// How a join is computed, decided before any row is read
+#[derive(Debug)]
+pub struct JoinPlan {
+    pub inner_header: TableHeader,
+    /// The outer columns followed by the inner columns
+    pub table_header: TableHeader,
+    /// Positions of the USING columns in the outer and in the inner row,
+    /// with their comparison affinity and collation
+    pub using_positions: Vec<(usize, usize, Affinity, Collation)>,
+    /// Pairs of expressions over the outer and the inner row that must be equal
+    pub equi_keys: Vec<(Expr, Expr)>,
+    pub strategy: JoinStrategy,
+    /// The line shown by EXPLAIN QUERY PLAN
+    pub detail: String,
+}
+
+/// Picks the cheapest way to join, measured in rows visited.
+/// `access` is how a nested-loop join finds the inner rows, looking up `rows_per_key` rows per outer row.
+/// Hash and merge joins need join keys, a hash join also an inner side that fits in memory.
+pub fn choose_join_strategy(
+    outer: Input,
+    inner: Input,
+    access: InnerAccess,
+    rows_per_key: f64,
+    has_equi_keys: bool,
+) -> JoinStrategy {
+    let search = inner.rows.max(2.0).log2();
+    let nested_loop = match access {
+        InnerAccess::Scan => outer.rows * inner.rows,
+        InnerAccess::Rowid { .. } => outer.rows * search,
+        // Every rowid found in the index is looked up in the table
+        InnerAccess::Index { .. } => outer.rows * (search + rows_per_key * search),
+    };
+
+    let mut costs = vec![(JoinStrategy::NestedLoop(access), nested_loop)];
+    if has_equi_keys {
+        if inner.rows * ROW_SIZE <= DEFAULT_SORT_MEMORY as f64 {
+            // Building the hash table is more expensive than probing it
+            costs.push((JoinStrategy::HashJoin, 2.0 * inner.rows + outer.rows));
+        }
+        costs.push((JoinStrategy::MergeJoin, sort_cost(outer) + sort_cost(inner)));
+    }
+
+    costs
+        .into_iter()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(strategy, _)| strategy)
+        .unwrap()
+}
+
+/// Estimated number of rows coming out of a join
+pub fn estimate_join_rows(
+    outer: Input,
+    inner: Input,
+    access: InnerAccess,
+    rows_per_key: f64,
+    has_equi_keys: bool,
+    is_left: bool,
+) -> f64 {
+    let rows = match access {
+        _ if !has_equi_keys => outer.rows * inner.rows,
+        InnerAccess::Scan => outer.rows.max(inner.rows),
+        InnerAccess::Rowid { .. } | InnerAccess::Index { .. } => outer.rows * rows_per_key,
+    };
+
+    match is_left {
+        true => rows.max(outer.rows),
+        false => rows,
+    }
+}
+
+fn sort_cost(input: Input) -> f64 {
+    match input.is_sorted {
+        true => input.rows,
+        false => input.rows * input.rows.max(2.0).log2(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{join::InnerAccess, Record, Value};
+
+    use super::{choose_join_strategy, Input, JoinStrategy, Statistics};
+
+    fn input(rows: f64, is_sorted: bool) -> Input {
+        Input { rows, is_sorted }
+    }
+
+    #[test]
+    fn statistics_from_records() {
+        let text = |s: &str| Value::Text(String::from(s));
+        let records = vec![
+            Record::new(
+                1,
+                vec![text("orders"), text("idx_orders_code"), text("2000 1")],
+            ),
+            Record::new(2, vec![text("customers"), Value::Null, text("5")]),
+        ];
+
+        let statistics = Statistics::from_records(records.into_iter());
+        assert_eq!(statistics.table_rows("orders"), Some(2000.0));
+        assert_eq!(statistics.table_rows("customers"), Some(5.0));
+        assert_eq!(statistics.rows_per_key("idx_orders_code"), Some(1.0));
+        assert_eq!(statistics.table_rows("mountains"), None);
+    }
+
+    #[test]
+    fn choose_strategy() {
+        let index = InnerAccess::Index {
+            key: 0,
+            rootpage: 2,
+        };
+
+        // Few outer rows are looked up in a large inner table
+        let strategy = choose_join_strategy(input(5.0, false), input(1e6, false), index, 1.0, true);
+        assert_eq!(strategy, JoinStrategy::NestedLoop(index));
+
+        // Many outer rows sharing the index keys are cheaper to match in a hash table
+        let strategy =
+            choose_join_strategy(input(1e4, false), input(1e4, false), index, 100.0, true);
+        assert_eq!(strategy, JoinStrategy::HashJoin);
+
+        // Both sides come in key order
+        let strategy = choose_join_strategy(input(1e4, true), input(1e4, true), index, 100.0, true);
+        assert_eq!(strategy, JoinStrategy::MergeJoin);
+
+        // The hash table would not fit in memory
+        let strategy =
+            choose_join_strategy(input(1e7, false), input(1e7, false), index, 100.0, true);
+        assert_eq!(strategy, JoinStrategy::MergeJoin);
+
+        // Without join keys only a nested loop is possible
+        let strategy = choose_join_strategy(
+            input(1e4, true),
+            input(1e4, true),
+            InnerAccess::Scan,
+            1.0,
+            false,
+        );
+        assert_eq!(strategy, JoinStrategy::NestedLoop(InnerAccess::Scan));
+    }
+}
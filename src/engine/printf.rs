@@ -0,0 +1,334 @@
+use super::{affinity::real_to_text, Value};
+
+/// The flags, width and precision of one `%` conversion
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    thousands: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Formats the arguments like SQLite's printf(), a missing argument counts as NULL.
+/// https://www.sqlite.org/printf.html
+pub fn printf(format: &str, args: &[Value]) -> String {
+    let mut args = args.iter();
+    let mut next_arg = || args.next().cloned().unwrap_or(Value::Null);
+
+    let mut output = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut spec = Spec::default();
+        while let Some(flag) = chars.next_if(|c| "-+ 0#,!".contains(*c)) {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                ',' => spec.thousands = true,
+                _ => {}
+            }
+        }
+
+        let mut number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+            if chars.next_if_eq(&'*').is_some() {
+                let width = next_arg().to_integer().unwrap_or(0);
+                return Some(width);
+            }
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            digits.parse().ok()
+        };
+        if let Some(width) = number(&mut chars) {
+            // A negative width left-justifies
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        }
+        if chars.next_if_eq(&'.').is_some() {
+            spec.precision = Some(number(&mut chars).unwrap_or(0).max(0) as usize);
+        }
+        while chars.next_if_eq(&'l').is_some() {}
+
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        let (prefix, body) = match conversion {
+            'd' | 'i' => {
+                let integer = next_arg().to_integer().unwrap_or(0);
+                let digits = integer.unsigned_abs().to_string();
+                let digits = match spec.precision {
+                    Some(precision) => format!("{digits:0>precision$}"),
+                    None => digits,
+                };
+                let digits = match spec.thousands {
+                    true => group_thousands(&digits),
+                    false => digits,
+                };
+                (sign(integer < 0, &spec), digits)
+            }
+            'u' => (
+                String::new(),
+                (next_arg().to_integer().unwrap_or(0) as u64).to_string(),
+            ),
+            'x' | 'X' | 'o' => {
+                let integer = next_arg().to_integer().unwrap_or(0) as u64;
+                let (prefix, digits) = match conversion {
+                    'x' => ("0x", format!("{integer:x}")),
+                    'X' => ("0X", format!("{integer:X}")),
+                    _ => ("0", format!("{integer:o}")),
+                };
+                let prefix = match spec.alternate && integer != 0 {
+                    true => prefix.to_owned(),
+                    false => String::new(),
+                };
+                (prefix, digits)
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let real = next_arg().to_real().unwrap_or(0.0);
+                let precision = spec.precision.unwrap_or(6);
+                let digits = match conversion {
+                    'f' | 'F' => format!("{:.precision$}", real.abs()),
+                    'e' | 'E' => format_exponent(real.abs(), precision),
+                    _ => format_general(real.abs(), precision, spec.alternate),
+                };
+                let digits = match conversion.is_ascii_uppercase() {
+                    true => digits.to_uppercase(),
+                    false => digits,
+                };
+                (sign(real.is_sign_negative() && real != 0.0, &spec), digits)
+            }
+            's' | 'z' => {
+                let text = next_arg().to_text().unwrap_or_default();
+                (String::new(), truncate(text, spec.precision))
+            }
+            'c' => {
+                let text = next_arg().to_text().unwrap_or_default();
+                (String::new(), text.chars().take(1).collect())
+            }
+            'q' | 'Q' | 'w' => {
+                let quote = if conversion == 'w' { '"' } else { '\'' };
+                let text = match (next_arg().to_text(), conversion) {
+                    (None, 'Q') => String::from("NULL"),
+                    (text, _) => {
+                        let text = truncate(text.unwrap_or_default(), spec.precision);
+                        let escaped = text.replace(quote, &format!("{quote}{quote}"));
+                        match conversion {
+                            'Q' => format!("'{escaped}'"),
+                            _ => escaped,
+                        }
+                    }
+                };
+                (String::new(), text)
+            }
+            '%' => (String::new(), String::from("%")),
+            conversion => (String::new(), format!("%{conversion}")),
+        };
+
+        let len = prefix.chars().count() + body.chars().count();
+        let padding = spec.width.saturating_sub(len);
+        let is_numeric = "diuxXofFeEgG".contains(conversion);
+        if spec.left {
+            output.push_str(&prefix);
+            output.push_str(&body);
+            output.push_str(&" ".repeat(padding));
+        } else if spec.zero && is_numeric {
+            output.push_str(&prefix);
+            output.push_str(&"0".repeat(padding));
+            output.push_str(&body);
+        } else {
+            output.push_str(&" ".repeat(padding));
+            output.push_str(&prefix);
+            output.push_str(&body);
+        }
+    }
+
+    output
+}
+
+fn sign(is_negative: bool, spec: &Spec) -> String {
+    String::from(match is_negative {
+        true => "-",
+        false if spec.plus => "+",
+        false if spec.space => " ",
+        false => "",
+    })
+}
+
+fn truncate(text: String, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => text.chars().take(precision).collect(),
+        None => text,
+    }
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    let first_group = digits.len() % 3;
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && idx % 3 == first_group {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// `%e`: 1.234568e+04 rather than Rust's 1.234568e4
+fn format_exponent(real: f64, precision: usize) -> String {
+    let text = format!("{real:.precision$e}");
+    let (mantissa, exponent) = text.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    format!(
+        "{mantissa}e{}{:02}",
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+/// `%g`: the shorter of `%e` and `%f` for `precision` significant digits, without trailing zeros
+pub fn format_general(real: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let exponent: i32 = format!("{real:.*e}", precision - 1)
+        .split_once('e')
+        .unwrap()
+        .1
+        .parse()
+        .unwrap();
+
+    let text = if exponent < -4 || exponent >= precision as i32 {
+        format_exponent(real, precision - 1)
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        format!("{real:.decimals$}")
+    };
+    if alternate {
+        return text;
+    }
+
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, format!("e{exponent}")),
+        None => (&text[..], String::new()),
+    };
+    let mantissa = match mantissa.contains('.') {
+        true => mantissa.trim_end_matches('0').trim_end_matches('.'),
+        false => mantissa,
+    };
+    format!("{mantissa}{exponent}")
+}
+
+/// The value as an SQL literal, as returned by quote()
+pub fn quote(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("NULL"),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Real(real) => real_to_text(*real + 0.0),
+        Value::Text(text) => format!("'{}'", text.replace('\'', "''")),
+        Value::Blob(blob) => format!("X'{}'", hex(blob)),
+    }
+}
+
+/// Upper case hexadecimal digits of the bytes
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Value;
+
+    use super::{printf, quote};
+
+    #[test]
+    fn printf_conversions() {
+        let cases = [
+            (
+                "%d|%5s|%-5s|%05.1f|%x",
+                vec![
+                    Value::Integer(42),
+                    Value::from("ab"),
+                    Value::from("cd"),
+                    Value::Real(1.23456),
+                    Value::Integer(255),
+                ],
+                "42|   ab|cd   |001.2|ff",
+            ),
+            (
+                "%e|%g|%g|%g",
+                vec![
+                    Value::Real(12345.678),
+                    Value::Real(0.0001),
+                    Value::Real(123456789.0),
+                    Value::Integer(100),
+                ],
+                "1.234568e+04|0.0001|1.23457e+08|100",
+            ),
+            (
+                "%.2g|%g|%g",
+                vec![
+                    Value::Real(0.000012345),
+                    Value::Real(1e20),
+                    Value::Real(1.5),
+                ],
+                "1.2e-05|1e+20|1.5",
+            ),
+            (
+                "%,d %+d % d %c %%",
+                vec![
+                    Value::Integer(1234567),
+                    Value::Integer(5),
+                    Value::Integer(5),
+                    Value::from("xyz"),
+                ],
+                "1,234,567 +5  5 x %",
+            ),
+            (
+                "%.3s|%10.2e|%#x|%o",
+                vec![
+                    Value::from("abcdef"),
+                    Value::Real(1234.5),
+                    Value::Integer(255),
+                    Value::Integer(8),
+                ],
+                "abc|  1.23e+03|0xff|10",
+            ),
+            (
+                "%q %Q %Q",
+                vec![Value::from("it's"), Value::from("a"), Value::Null],
+                "it''s 'a' NULL",
+            ),
+            (
+                "%d %d",
+                vec![Value::from("12abc"), Value::Real(3.9)],
+                "12 3",
+            ),
+            ("%s %s|%x", vec![Value::Integer(1)], "1 |0"),
+            ("%x", vec![Value::Integer(-1)], "ffffffffffffffff"),
+            ("%s", vec![Value::Real(1.0)], "1.0"),
+        ];
+
+        for (format, args, want) in cases {
+            assert_eq!(printf(format, &args), want, "{format}");
+        }
+    }
+
+    #[test]
+    fn quote_values() {
+        assert_eq!(quote(&Value::from("it's")), "'it''s'");
+        assert_eq!(quote(&Value::Null), "NULL");
+        assert_eq!(quote(&Value::Real(1e100)), "1.0e+100");
+        assert_eq!(quote(&Value::Real(-0.0)), "0.0");
+        assert_eq!(quote(&Value::Blob(vec![0x0a, 0xff])), "X'0AFF'");
+    }
+}
@@ -4,7 +4,7 @@ use itertools::Itertools;
 
 use super::Value;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Record {
     pub rowid: i64,
     pub values: Vec<Value>,
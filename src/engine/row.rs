@@ -1,16 +1,64 @@
 use std::ops::Index;
 
-use super::{Record, TableHeader, Value};
+use crate::sql::{Expr, Literal};
 
+use super::{
+    affinity::Affinity, collation::Collation, function::Functions, Record, TableHeader, Value,
+};
+
+/// A record together with what its expressions are evaluated with
 #[derive(Debug)]
 pub struct Row<'a> {
     pub header: &'a TableHeader,
     pub record: Record,
+    pub functions: &'a Functions,
+    /// The row of the enclosing query, for the columns a correlated subquery refers to
+    pub outer: Option<&'a Row<'a>>,
 }
 
 impl<'a> Row<'a> {
-    pub fn new(header: &'a TableHeader, record: Record) -> Self {
-        Self { header, record }
+    pub fn new(header: &'a TableHeader, record: Record, functions: &'a Functions) -> Self {
+        Self {
+            header,
+            record,
+            functions,
+            outer: None,
+        }
+    }
+
+    /// Makes the columns of the enclosing row visible behind those of this row.
+    pub fn with_outer(mut self, outer: Option<&'a Row<'a>>) -> Self {
+        self.outer = outer;
+        self
+    }
+
+    /// The row a column belongs to, this one or the nearest enclosing one that has it
+    pub fn scope(&self, name: &str) -> &Row<'a> {
+        match self.outer {
+            Some(outer) if !self.header.contains(name) => outer.scope(name),
+            _ => self,
+        }
+    }
+
+    /// Whether the column belongs to this row or to an enclosing one
+    pub fn has_column(&self, name: &str) -> bool {
+        self.scope(name).header.contains(name)
+    }
+
+    /// The affinity of the value of the expression, that of the column of its row for a column
+    pub fn affinity(&self, expr: &Expr) -> Affinity {
+        match expr {
+            Expr::Literal(Literal::Id(id)) => self.scope(id).header.affinity(id),
+            expr => expr.affinity(self.header),
+        }
+    }
+
+    /// The collation of a column of its row, none for other expressions
+    pub fn collation(&self, expr: &Expr) -> Option<Collation> {
+        match expr {
+            Expr::Literal(Literal::Id(id)) => self.scope(id).header.collation(id),
+            _ => None,
+        }
     }
 }
 
@@ -18,6 +66,7 @@ impl<'a> Index<&str> for Row<'a> {
     type Output = Value;
 
     fn index(&self, index: &str) -> &Self::Output {
-        &self.record[self.header[index]]
+        let row = self.scope(index);
+        &row.record[row.header[index]]
     }
 }
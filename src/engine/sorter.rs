@@ -0,0 +1,336 @@
+use std::{
+    cmp::Ordering,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    mem,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+use crate::sqlite_storage::{parse_record, serialize_record};
+
+use super::{collation::Collation, Record, Value};
+
+/// Memory used for sorting before sorted runs are spilled to temporary files
+pub const DEFAULT_SORT_MEMORY: usize = 16 * 1024 * 1024;
+
+/// Sort order of one ORDER BY term
+#[derive(Clone, Debug)]
+pub struct SortKey {
+    pub collation: Collation,
+    pub desc: bool,
+    pub nulls_first: bool,
+}
+
+/// Compares the sort keys of two rows term by term.
+pub fn compare_sort_keys(sort_keys: &[SortKey], a: &[Value], b: &[Value]) -> Ordering {
+    for (sort_key, (a, b)) in sort_keys.iter().zip(a.iter().zip(b)) {
+        let ordering = match (a, b) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            // The position of NULLs does not depend on the direction
+            (Value::Null, _) if sort_key.nulls_first => return Ordering::Less,
+            (Value::Null, _) => return Ordering::Greater,
+            (_, Value::Null) if sort_key.nulls_first => return Ordering::Greater,
+            (_, Value::Null) => return Ordering::Less,
+            (a, b) if sort_key.desc => sort_key.collation.compare(a, b).reverse(),
+            (a, b) => sort_key.collation.compare(a, b),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+type Entry = (Vec<Value>, Record);
+
+/// External merge sort of records by their sort keys.
+///
+/// Records are buffered in memory until they exceed the memory budget,
+/// then the buffer is sorted and written to a temporary file as a run.
+/// The runs are merged while the sorted records are read.
+/// Records with equal keys keep their insertion order.
+pub struct Sorter {
+    sort_keys: Vec<SortKey>,
+    memory_budget: usize,
+    entries: Vec<Entry>,
+    entries_size: usize,
+    runs: Vec<File>,
+}
+
+impl Sorter {
+    pub fn new(sort_keys: Vec<SortKey>, memory_budget: usize) -> Self {
+        Self {
+            sort_keys,
+            memory_budget,
+            entries: vec![],
+            entries_size: 0,
+            runs: vec![],
+        }
+    }
+
+    pub fn push(&mut self, keys: Vec<Value>, record: Record) {
+        self.entries_size += entry_size(&keys, &record);
+        self.entries.push((keys, record));
+
+        if self.entries_size > self.memory_budget {
+            self.spill();
+        }
+    }
+
+    pub fn finish(mut self) -> Box<dyn Iterator<Item = Record>> {
+        self.sort_entries();
+
+        if self.runs.is_empty() {
+            return Box::new(self.entries.into_iter().map(|(_, record)| record));
+        }
+
+        // The records still in memory are the most recent run
+        let key_cnt = self.sort_keys.len();
+        let mut runs: Vec<Box<dyn Iterator<Item = Entry>>> = mem::take(&mut self.runs)
+            .into_iter()
+            .map(|file| Box::new(RunReader::new(file, key_cnt)) as Box<dyn Iterator<Item = Entry>>)
+            .collect();
+        runs.push(Box::new(mem::take(&mut self.entries).into_iter()));
+
+        Box::new(Merger::new(self.sort_keys, runs).map(|(_, record)| record))
+    }
+
+    fn sort_entries(&mut self) {
+        let sort_keys = &self.sort_keys;
+        self.entries
+            .sort_by(|(a, _), (b, _)| compare_sort_keys(sort_keys, a, b));
+    }
+
+    /// Writes the buffered records to a temporary file as a sorted run.
+    fn spill(&mut self) {
+        self.sort_entries();
+
+        let mut writer = BufWriter::new(create_temp_file());
+        for (keys, record) in self.entries.drain(..) {
+            let mut values = vec![Value::Integer(record.rowid)];
+            values.extend(keys);
+            values.extend(record.values);
+
+            let bytes = serialize_record(&values);
+            writer
+                .write_all(&(bytes.len() as u32).to_be_bytes())
+                .unwrap();
+            writer.write_all(&bytes).unwrap();
+        }
+
+        let mut file = writer.into_inner().unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        self.runs.push(file);
+        self.entries_size = 0;
+    }
+}
+
+/// Reads the entries of a run in the order they were written.
+struct RunReader {
+    reader: BufReader<File>,
+    key_cnt: usize,
+}
+
+impl RunReader {
+    fn new(file: File, key_cnt: usize) -> Self {
+        Self {
+            reader: BufReader::new(file),
+            key_cnt,
+        }
+    }
+}
+
+impl Iterator for RunReader {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(err) => panic!("{err}"),
+        }
+
+        let mut bytes = vec![0; u32::from_be_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes).unwrap();
+
+        let mut values = parse_record(&bytes);
+        let record_values = values.split_off(1 + self.key_cnt);
+        let keys = values.split_off(1);
+        let rowid = i64::from(&values[0]);
+        Some((keys, Record::new(rowid, record_values)))
+    }
+}
+
+/// Merges sorted runs, on equal keys the entry of the earlier run comes first.
+struct Merger {
+    sort_keys: Vec<SortKey>,
+    runs: Vec<Box<dyn Iterator<Item = Entry>>>,
+    heads: Vec<Option<Entry>>,
+}
+
+impl Merger {
+    fn new(sort_keys: Vec<SortKey>, mut runs: Vec<Box<dyn Iterator<Item = Entry>>>) -> Self {
+        let heads = runs.iter_mut().map(|run| run.next()).collect();
+        Self {
+            sort_keys,
+            runs,
+            heads,
+        }
+    }
+}
+
+impl Iterator for Merger {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut min: Option<usize> = None;
+        for (idx, head) in self.heads.iter().enumerate() {
+            let Some((keys, _)) = head else {
+                continue;
+            };
+            let is_less = match min {
+                Some(min) => {
+                    let (min_keys, _) = self.heads[min].as_ref().unwrap();
+                    compare_sort_keys(&self.sort_keys, keys, min_keys) == Ordering::Less
+                }
+                None => true,
+            };
+            if is_less {
+                min = Some(idx);
+            }
+        }
+
+        let min = min?;
+        let next = self.runs[min].next();
+        mem::replace(&mut self.heads[min], next)
+    }
+}
+
+/// Rough number of bytes a buffered entry occupies
+fn entry_size(keys: &[Value], record: &Record) -> usize {
+    let value_size = |value: &Value| {
+        mem::size_of::<Value>()
+            + match value {
+                Value::Text(text) => text.len(),
+                Value::Blob(blob) => blob.len(),
+                _ => 0,
+            }
+    };
+
+    mem::size_of::<Entry>()
+        + keys
+            .iter()
+            .chain(&record.values)
+            .map(value_size)
+            .sum::<usize>()
+}
+
+/// Creates a file that is removed as soon as it is closed.
+fn create_temp_file() -> File {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = format!(
+        "sqlite-rust-sort-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+    );
+    let path = std::env::temp_dir().join(file_name);
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{collation::Collation, Record, Value};
+
+    use super::{SortKey, Sorter};
+
+    fn sort(values: Vec<Value>, sort_key: SortKey, memory_budget: usize) -> Vec<(i64, Value)> {
+        let mut sorter = Sorter::new(vec![sort_key], memory_budget);
+        for (rowid, value) in values.into_iter().enumerate() {
+            sorter.push(vec![value.clone()], Record::new(rowid as i64, vec![value]));
+        }
+
+        sorter
+            .finish()
+            .map(|record| (record.rowid, record.values[0].clone()))
+            .collect()
+    }
+
+    fn asc() -> SortKey {
+        SortKey {
+            collation: Collation::Binary,
+            desc: false,
+            nulls_first: true,
+        }
+    }
+
+    #[test]
+    fn sort_in_memory() {
+        let values = vec![
+            Value::Text(String::from("b")),
+            Value::Integer(3),
+            Value::Null,
+            Value::Real(2.5),
+            Value::Text(String::from("a")),
+        ];
+
+        let sorted: Vec<_> = sort(values, asc(), usize::MAX)
+            .into_iter()
+            .map(|(rowid, _)| rowid)
+            .collect();
+        assert_eq!(sorted, [2, 3, 1, 4, 0]);
+    }
+
+    #[test]
+    fn sort_desc_nulls_first() {
+        let values = vec![Value::Integer(1), Value::Null, Value::Integer(2)];
+        let sort_key = SortKey {
+            desc: true,
+            ..asc()
+        };
+
+        let sorted: Vec<_> = sort(values, sort_key, usize::MAX)
+            .into_iter()
+            .map(|(rowid, _)| rowid)
+            .collect();
+        assert_eq!(sorted, [1, 2, 0]);
+    }
+
+    #[test]
+    fn sort_spills_to_runs() {
+        let values: Vec<_> = (0..1000)
+            .map(|i| Value::Text(format!("{:03}", (i * 7919) % 1000)))
+            .collect();
+
+        // Every few records a run is written to a temporary file
+        let sorted = sort(values, asc(), 1024);
+
+        let want: Vec<_> = (0..1000)
+            .map(|i| Value::Text(format!("{:03}", i)))
+            .collect();
+        let got: Vec<_> = sorted.into_iter().map(|(_, value)| value).collect();
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn sort_spilled_runs_are_stable() {
+        let values: Vec<_> = (0..500).map(|i| Value::Integer(i % 3)).collect();
+
+        let sorted = sort(values, asc(), 512);
+        for pair in sorted.windows(2) {
+            let ((a_rowid, a), (b_rowid, b)) = (&pair[0], &pair[1]);
+            assert!(a.compare(b).is_lt() || (a == b && a_rowid < b_rowid));
+        }
+    }
+}
@@ -1,12 +1,17 @@
-use crate::sql::sql::{ColumnDef, CreateTableStmt};
+use crate::sql::{
+    parse_create_index_stmt, parse_create_table_stmt, parse_create_view_stmt,
+    sql::{ColumnDef, CreateIndexStmt, CreateTableStmt, CreateViewStmt},
+};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum SQLiteObjectType {
     Table,
     Index,
+    View,
+    Trigger,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SQLiteObject {
     pub object_type: SQLiteObjectType,
     pub name: String,
@@ -33,22 +38,74 @@ impl SQLiteObject {
     }
 
     pub fn is_table(&self) -> bool {
-        return matches!(self.object_type, SQLiteObjectType::Table);
+        matches!(self.object_type, SQLiteObjectType::Table)
     }
 
     pub fn is_index(&self) -> bool {
-        return matches!(self.object_type, SQLiteObjectType::Index);
+        matches!(self.object_type, SQLiteObjectType::Index)
+    }
+
+    pub fn is_view(&self) -> bool {
+        matches!(self.object_type, SQLiteObjectType::View)
+    }
+
+    pub fn is_trigger(&self) -> bool {
+        matches!(self.object_type, SQLiteObjectType::Trigger)
+    }
+
+    /// Fails if the SQL of a table or view cannot be parsed,
+    /// the other methods expect it to be checked.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_table() {
+            parse_create_table_stmt(&self.sql)
+                .map_err(|err| format!("cannot read table {}: {}", self.name, err))?;
+        }
+        if self.is_view() {
+            self.get_create_view_stmt()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_create_table_stmt(&self) -> CreateTableStmt {
+        parse_create_table_stmt(&self.sql).expect("internal error: unchecked table schema")
     }
 
     pub fn get_column_defs(&self) -> Vec<ColumnDef> {
-        let stmt = CreateTableStmt::parse(&self.sql);
-        stmt.column_defs
+        self.get_create_table_stmt().column_defs
     }
 
-    pub fn get_column_names(&self) -> Vec<&str> {
+    pub fn get_rowid_column(&self) -> Option<usize> {
+        if !self.is_table() {
+            return None;
+        }
+        self.get_create_table_stmt().rowid_column()
+    }
+
+    pub fn is_without_rowid(&self) -> bool {
+        self.is_table() && self.get_create_table_stmt().without_rowid
+    }
+
+    pub fn get_create_view_stmt(&self) -> Result<CreateViewStmt, String> {
+        parse_create_view_stmt(&self.sql)
+            .map_err(|err| format!("cannot read view {}: {}", self.name, err))
+    }
+
+    /// The columns of a table, or of a view: those listed after its name or those of its SELECT
+    pub fn get_column_names(&self) -> Vec<String> {
+        if self.is_view() {
+            let stmt =
+                (self.get_create_view_stmt()).expect("internal error: unchecked view schema");
+            return stmt.column_names.unwrap_or(stmt.select_stmt.column_names);
+        }
+
         self.get_column_defs()
             .into_iter()
             .map(|column_def| column_def.column_name)
             .collect()
     }
+
+    /// Fails for the expression and partial indexes the parser does not cover.
+    pub fn get_create_index_stmt(&self) -> Result<CreateIndexStmt, String> {
+        parse_create_index_stmt(&self.sql)
+    }
 }
@@ -2,6 +2,7 @@ use crate::sql::parse_create_index_stmt;
 
 use super::SQLiteObject;
 
+#[derive(Clone, Debug)]
 pub struct SQLiteSchema {
     pub sqlite_objects: Vec<SQLiteObject>,
 }
@@ -14,27 +15,66 @@ impl SQLiteSchema {
     pub fn get_sqlite_object(&self, name: &str) -> Option<&SQLiteObject> {
         self.sqlite_objects
             .iter()
-            .find(|sqlite_object| sqlite_object.name == name)
+            .find(|sqlite_object| sqlite_object.name.eq_ignore_ascii_case(name))
     }
 
+    /// An index to look up the rowids of rows by a single column.
+    /// Automatic indexes are skipped, so are the indexes of WITHOUT ROWID tables,
+    /// which hold primary keys instead of rowids, and those of collated columns
+    /// or in descending order, whose keys are not in ascending BINARY order.
     pub fn find_index(&self, table_name: &str, indexed_column: &str) -> Option<&SQLiteObject> {
+        let sqlite_object_table = self.get_sqlite_object(table_name)?;
+        if !sqlite_object_table.is_table()
+            || sqlite_object_table.is_without_rowid()
+            || (sqlite_object_table.get_column_defs().iter()).any(|column_def| {
+                column_def.column_name.eq_ignore_ascii_case(indexed_column)
+                    && column_def.is_collated()
+            })
+        {
+            return None;
+        }
+
         self.sqlite_objects
             .iter()
-            .filter(|sqlite_object| sqlite_object.is_index())
+            .filter(|sqlite_object| sqlite_object.is_index() && !sqlite_object.sql.is_empty())
             .find(|sqlite_object| {
-                let stmt = parse_create_index_stmt(&sqlite_object.sql);
+                let Ok(stmt) = parse_create_index_stmt(&sqlite_object.sql) else {
+                    return false;
+                };
 
-                if stmt.table_name == table_name
+                stmt.table_name.eq_ignore_ascii_case(table_name)
                     && stmt.indexed_columns.len() == 1
-                    && stmt.indexed_columns[0] == indexed_column
-                {
-                    true
-                } else {
-                    false
-                }
+                    && (stmt.indexed_columns[0].column_name).eq_ignore_ascii_case(indexed_column)
+                    && !stmt.indexed_columns[0].desc
+                    && !stmt.indexed_columns[0].is_collated()
             })
     }
 
+    pub fn get_indexes<'a>(
+        &'a self,
+        table_name: &'a str,
+    ) -> impl Iterator<Item = &'a SQLiteObject> {
+        self.sqlite_objects.iter().filter(move |sqlite_object| {
+            sqlite_object.is_index() && sqlite_object.tbl_name.eq_ignore_ascii_case(table_name)
+        })
+    }
+
+    pub fn get_triggers<'a>(
+        &'a self,
+        table_name: &'a str,
+    ) -> impl Iterator<Item = &'a SQLiteObject> {
+        self.sqlite_objects.iter().filter(move |sqlite_object| {
+            sqlite_object.is_trigger() && sqlite_object.tbl_name.eq_ignore_ascii_case(table_name)
+        })
+    }
+
+    pub fn get_view_names(&self) -> impl Iterator<Item = &str> {
+        self.sqlite_objects
+            .iter()
+            .filter(|sqlite_object| sqlite_object.is_view())
+            .map(|sqlite_object| &sqlite_object.name[..])
+    }
+
     pub fn get_table_names(&self) -> impl Iterator<Item = &str> {
         self.sqlite_objects
             .iter()
@@ -1,23 +1,177 @@
 use std::{collections::HashMap, ops::Index};
 
-#[derive(Debug)]
-pub struct TableHeader(HashMap<String, usize>);
+use super::{affinity::Affinity, collation::Collation};
+
+#[derive(Clone, Debug)]
+pub struct TableHeader {
+    // Column name to its position, None if the columns of several joined tables share the name
+    columns: HashMap<String, Option<usize>>,
+    affinities: Vec<Affinity>,
+    collations: Vec<Collation>,
+}
 
 impl TableHeader {
     pub fn new(column_names: &[&str]) -> Self {
-        Self(
-            column_names
-                .into_iter()
+        Self {
+            columns: column_names
+                .iter()
                 .enumerate()
-                .map(|(idx, name)| (String::from(*name), idx))
+                .map(|(idx, name)| (key(name), Some(idx)))
                 .collect(),
-        )
+            affinities: vec![Affinity::Blob; column_names.len()],
+            collations: vec![Collation::Binary; column_names.len()],
+        }
+    }
+
+    /// Sets the affinities of the columns in order.
+    pub fn with_affinities(mut self, affinities: Vec<Affinity>) -> Self {
+        assert_eq!(affinities.len(), self.affinities.len());
+        self.affinities = affinities;
+        self
+    }
+
+    /// Sets the declared collations of the columns in order.
+    pub fn with_collations(mut self, collations: Vec<Collation>) -> Self {
+        assert_eq!(collations.len(), self.collations.len());
+        self.collations = collations;
+        self
+    }
+
+    /// The columns of a table, reachable both by their name
+    /// and qualified by the table name or alias (`qualifier.name`).
+    pub fn qualified(qualifier: &str, column_names: &[impl AsRef<str>]) -> Self {
+        let column_names: Vec<&str> = column_names.iter().map(AsRef::as_ref).collect();
+        let mut table_header = Self::new(&column_names);
+        for (idx, name) in column_names.iter().enumerate() {
+            let name = format!("{qualifier}.{name}");
+            table_header.columns.insert(key(&name), Some(idx));
+        }
+        table_header
+    }
+
+    /// The columns of the left side followed by the columns of the right side.
+    /// Names present on both sides become ambiguous,
+    /// except for the columns of a USING clause, which refer to the left side.
+    pub fn join(&self, right: &TableHeader, using: &[String]) -> Self {
+        let mut columns = self.columns.clone();
+
+        for (name, idx) in &right.columns {
+            let idx = idx.map(|idx| idx + self.len());
+            match columns.get(name) {
+                None => {
+                    columns.insert(name.clone(), idx);
+                }
+                Some(_) if using.iter().any(|column| key(column) == *name) => {}
+                Some(_) => {
+                    columns.insert(name.clone(), None);
+                }
+            }
+        }
+
+        let mut affinities = self.affinities.clone();
+        affinities.extend(&right.affinities);
+        let mut collations = self.collations.clone();
+        collations.extend(&right.collations);
+        Self {
+            columns,
+            affinities,
+            collations,
+        }
+    }
+
+    pub fn resolve(&self, name: &str) -> Result<usize, String> {
+        match self.columns.get(&key(name)) {
+            Some(Some(idx)) => Ok(*idx),
+            Some(None) => Err(format!("ambiguous column name: {name}")),
+            None => Err(format!("no such column: {name}")),
+        }
+    }
+
+    /// Whether the name refers to a column, possibly an ambiguous one
+    pub fn contains(&self, name: &str) -> bool {
+        self.columns.contains_key(&key(name))
+    }
+
+    /// The affinity of a column, none for unknown columns
+    pub fn affinity(&self, name: &str) -> Affinity {
+        match self.columns.get(&key(name)) {
+            Some(Some(idx)) => self.affinities[*idx],
+            _ => Affinity::Blob,
+        }
     }
+
+    /// The collation of a column, none for unknown columns
+    pub fn collation(&self, name: &str) -> Option<Collation> {
+        match self.columns.get(&key(name)) {
+            Some(Some(idx)) => Some(self.collations[*idx]),
+            _ => None,
+        }
+    }
+
+    /// Appends a column after the existing ones.
+    pub fn push(&mut self, column_name: String) {
+        self.columns.insert(key(&column_name), Some(self.len()));
+        self.affinities.push(Affinity::Blob);
+        self.collations.push(Collation::Binary);
+    }
+
+    pub fn len(&self) -> usize {
+        self.affinities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.affinities.is_empty()
+    }
+}
+
+/// Names are looked up ignoring the case of ASCII letters like SQLite does
+fn key(name: &str) -> String {
+    name.to_ascii_lowercase()
 }
 
 impl Index<&str> for TableHeader {
     type Output = usize;
     fn index(&self, index: &str) -> &Self::Output {
-        &self.0[index]
+        match self.columns.get(&key(index)) {
+            Some(Some(idx)) => idx,
+            _ => panic!("{}", self.resolve(index).unwrap_err()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TableHeader;
+
+    #[test]
+    fn join_qualified() {
+        let left = TableHeader::qualified("a", &["id", "name"]);
+        let right = TableHeader::qualified("b", &["id", "a_id", "name"]);
+
+        let table_header = left.join(&right, &[String::from("name")]);
+        assert_eq!(table_header.len(), 5);
+        assert_eq!(table_header.resolve("a.id"), Ok(0));
+        assert_eq!(table_header.resolve("b.id"), Ok(2));
+        assert_eq!(table_header.resolve("a_id"), Ok(3));
+        assert_eq!(table_header.resolve("name"), Ok(1));
+        assert_eq!(table_header.resolve("b.name"), Ok(4));
+        assert_eq!(
+            table_header.resolve("id"),
+            Err(String::from("ambiguous column name: id"))
+        );
+        assert_eq!(
+            table_header.resolve("c.id"),
+            Err(String::from("no such column: c.id"))
+        );
+    }
+
+    #[test]
+    fn resolve_ignoring_case() {
+        let table_header = TableHeader::qualified("Mountains", &["Name", "first name"]);
+
+        assert_eq!(table_header.resolve("NAME"), Ok(0));
+        assert_eq!(table_header.resolve("mountains.name"), Ok(0));
+        assert_eq!(table_header.resolve("MOUNTAINS.First Name"), Ok(1));
+        assert_eq!(table_header["name"], 0);
     }
 }
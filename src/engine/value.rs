@@ -1,9 +1,12 @@
 use std::{
+    cmp::Ordering,
     fmt::Display,
-    ops::{Add, Div, Mul, Sub},
+    ops::{Add, BitAnd, BitOr, Div, Mul, Neg, Rem, Shl, Shr, Sub},
 };
 
-#[derive(Clone, Debug, PartialEq, PartialOrd)]
+use super::affinity::{integer_prefix, number_prefix, real_prefix, real_to_text};
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Null,
     Integer(i64),
@@ -18,12 +21,16 @@ impl From<bool> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(String::from(text))
+    }
+}
+
+/// Whether a row passes a WHERE, ON or HAVING clause, NULL counts as false.
 impl From<&Value> for bool {
     fn from(value: &Value) -> Self {
-        match value {
-            Value::Integer(i) if *i == 0 || *i == 1 => *i == 1,
-            _ => panic!(),
-        }
+        value.truth() == Some(true)
     }
 }
 
@@ -37,55 +44,283 @@ impl From<&Value> for i64 {
 }
 
 impl Value {
+    /// Compares values the way SQLite sorts them.
+    /// Values of different storage classes are ordered NULL < INTEGER/REAL < TEXT < BLOB,
+    /// integers and reals are compared by their numeric value.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Real(b)) => compare_integer_real(*a, *b),
+            (Value::Real(a), Value::Integer(b)) => compare_integer_real(*b, *a).reverse(),
+            (Value::Real(a), Value::Real(b)) => a.total_cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            (a, b) => a.storage_class_rank().cmp(&b.storage_class_rank()),
+        }
+    }
+
+    fn storage_class_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+
+    /// The truth value of a boolean operand, None for NULL.
+    /// Other values are true if they convert to a non-zero number, so 'abc' is false and '1x' true.
+    pub fn truth(&self) -> Option<bool> {
+        match self {
+            Value::Integer(integer) => Some(*integer != 0),
+            value => value.to_real().map(|real| real != 0.0),
+        }
+    }
+
+    /// NULL unless either side is true
     pub fn or(&self, rhs: &Value) -> Value {
-        Value::from(self.into() || rhs.into())
+        match (self.truth(), rhs.truth()) {
+            (Some(true), _) | (_, Some(true)) => Value::from(true),
+            (Some(false), Some(false)) => Value::from(false),
+            _ => Value::Null,
+        }
     }
+
+    /// NULL unless either side is false
     pub fn and(&self, rhs: &Value) -> Value {
-        Value::from(self.into() && rhs.into())
+        match (self.truth(), rhs.truth()) {
+            (Some(false), _) | (_, Some(false)) => Value::from(false),
+            (Some(true), Some(true)) => Value::from(true),
+            _ => Value::Null,
+        }
+    }
+
+    /// NULL stays NULL
+    pub fn not(&self) -> Value {
+        self.truth()
+            .map_or(Value::Null, |truth| Value::from(!truth))
+    }
+
+    /// The value converted to text, None for NULL
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Value::Null => None,
+            Value::Integer(integer) => Some(integer.to_string()),
+            Value::Real(real) => Some(real_to_text(*real)),
+            Value::Text(text) => Some(text.clone()),
+            Value::Blob(blob) => Some(String::from_utf8_lossy(blob).into_owned()),
+        }
+    }
+
+    /// The value converted to an integer, reals are truncated and text is read up to the first non-numeric character.
+    /// None for NULL.
+    pub fn to_integer(&self) -> Option<i64> {
+        match self {
+            Value::Null => None,
+            Value::Integer(integer) => Some(*integer),
+            Value::Real(real) => Some(*real as i64),
+            Value::Text(text) => Some(integer_prefix(text)),
+            Value::Blob(blob) => Some(integer_prefix(&String::from_utf8_lossy(blob))),
+        }
+    }
+
+    /// The value converted to a real, None for NULL
+    pub fn to_real(&self) -> Option<f64> {
+        match self {
+            Value::Null => None,
+            Value::Integer(integer) => Some(*integer as f64),
+            Value::Real(real) => Some(*real),
+            Value::Text(text) => Some(real_prefix(text)),
+            Value::Blob(blob) => Some(real_prefix(&String::from_utf8_lossy(blob))),
+        }
+    }
+
+    /// The value converted to an integer or a real for arithmetic, None for NULL.
+    /// Text is read up to the first non-numeric character, '3' becomes 3 and '3.0' becomes 3.0.
+    pub fn to_numeric(&self) -> Option<Value> {
+        match self {
+            Value::Null => None,
+            Value::Integer(_) | Value::Real(_) => Some(self.clone()),
+            Value::Text(text) => Some(number_prefix(text)),
+            Value::Blob(blob) => Some(number_prefix(&String::from_utf8_lossy(blob))),
+        }
+    }
+
+    /// `||`, NULL if either side is NULL
+    pub fn concat(&self, rhs: &Value) -> Value {
+        match (self.to_text(), rhs.to_text()) {
+            (Some(l), Some(r)) => Value::Text(l + &r),
+            _ => Value::Null,
+        }
+    }
+
+    /// `~`, the bits of the value converted to an integer inverted
+    pub fn bit_not(&self) -> Value {
+        self.to_integer()
+            .map_or(Value::Null, |integer| Value::Integer(!integer))
+    }
+
+    /// Compares two values, NULL if either is NULL
+    pub fn compare_nullable(&self, other: &Value, test: fn(Ordering) -> bool) -> Value {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            _ => Value::from(test(self.compare(other))),
+        }
+    }
+}
+
+/// Casting either side would lose precision: large integers are not exact as f64,
+/// reals beyond the i64 range do not fit an integer.
+fn compare_integer_real(a: i64, b: f64) -> Ordering {
+    if b.is_nan() {
+        return Ordering::Greater;
+    }
+    if b < -9.223_372_036_854_776e18 {
+        return Ordering::Greater;
+    }
+    if b >= 9.223_372_036_854_776e18 {
+        return Ordering::Less;
+    }
+
+    let floor = b.floor();
+    match a.cmp(&(floor as i64)) {
+        Ordering::Equal if b > floor => Ordering::Less,
+        ordering => ordering,
+    }
+}
+
+/// Applies an arithmetic operator to the operands converted to numbers, NULL if either is NULL.
+/// Integers are computed on reals if the result overflows.
+fn arithmetic(
+    l: &Value,
+    r: &Value,
+    integer_op: fn(i64, i64) -> Option<i64>,
+    real_op: fn(f64, f64) -> f64,
+) -> Value {
+    match (l.to_numeric(), r.to_numeric()) {
+        (Some(Value::Integer(x)), Some(Value::Integer(y))) => integer_op(x, y)
+            .map_or_else(|| Value::Real(real_op(x as f64, y as f64)), Value::Integer),
+        (Some(x), Some(y)) => Value::Real(real_op(x.to_real().unwrap(), y.to_real().unwrap())),
+        _ => Value::Null,
+    }
+}
+
+/// Applies a bitwise operator to the operands converted to integers, NULL if either is NULL.
+fn bitwise(l: &Value, r: &Value, op: fn(i64, i64) -> i64) -> Value {
+    match (l.to_integer(), r.to_integer()) {
+        (Some(x), Some(y)) => Value::Integer(op(x, y)),
+        _ => Value::Null,
+    }
+}
+
+/// Shifts left by `n` bits, a negative `n` shifts right
+fn shift_left(integer: i64, n: i64) -> i64 {
+    match n {
+        64.. => 0,
+        0.. => integer << n,
+        _ => shift_right(integer, n.saturating_neg()),
+    }
+}
+
+/// Shifts right by `n` bits keeping the sign, a negative `n` shifts left
+fn shift_right(integer: i64, n: i64) -> i64 {
+    match n {
+        64.. => integer >> 63,
+        0.. => integer >> n,
+        _ => shift_left(integer, n.saturating_neg()),
     }
 }
 
-impl<'a, 'b> Add<&'b Value> for &'a Value {
+impl<'b> Add<&'b Value> for &Value {
     type Output = Value;
     fn add(self, rhs: &'b Value) -> Self::Output {
-        match (self, rhs) {
-            (Value::Integer(x), Value::Integer(y)) => Value::Integer(x + y),
-            (Value::Real(x), Value::Real(y)) => Value::Real(x + y),
-            (Value::Text(x), Value::Text(y)) => Value::Text(x.to_owned() + y),
-            _ => panic!(),
-        }
+        arithmetic(self, rhs, i64::checked_add, |x, y| x + y)
     }
 }
 
-impl<'a, 'b> Sub<&'b Value> for &'a Value {
+impl<'b> Sub<&'b Value> for &Value {
     type Output = Value;
     fn sub(self, rhs: &'b Value) -> Self::Output {
-        match (self, rhs) {
-            (Value::Integer(x), Value::Integer(y)) => Value::Integer(x - y),
-            (Value::Real(x), Value::Real(y)) => Value::Real(x - y),
-            _ => panic!(),
-        }
+        arithmetic(self, rhs, i64::checked_sub, |x, y| x - y)
     }
 }
 
-impl<'a, 'b> Mul<&'b Value> for &'a Value {
+impl<'b> Mul<&'b Value> for &Value {
     type Output = Value;
     fn mul(self, rhs: &'b Value) -> Self::Output {
-        match (self, rhs) {
-            (Value::Integer(x), Value::Integer(y)) => Value::Integer(x * y),
-            (Value::Real(x), Value::Real(y)) => Value::Real(x * y),
-            _ => panic!(),
-        }
+        arithmetic(self, rhs, i64::checked_mul, |x, y| x * y)
     }
 }
 
-impl<'a, 'b> Div<&'b Value> for &'a Value {
+/// Division by zero is NULL
+impl<'b> Div<&'b Value> for &Value {
     type Output = Value;
     fn div(self, rhs: &'b Value) -> Self::Output {
-        match (self, rhs) {
-            (Value::Integer(x), Value::Integer(y)) => Value::Integer(x * y),
-            (Value::Real(x), Value::Real(y)) => Value::Real(x * y),
-            _ => panic!(),
+        match rhs.to_real() {
+            Some(0.0) => Value::Null,
+            _ => arithmetic(self, rhs, i64::checked_div, |x, y| x / y),
+        }
+    }
+}
+
+/// The remainder of the operands converted to integers, a real if either operand is real.
+/// Division by zero is NULL.
+impl<'b> Rem<&'b Value> for &Value {
+    type Output = Value;
+    fn rem(self, rhs: &'b Value) -> Self::Output {
+        let (Some(l), Some(r)) = (self.to_numeric(), rhs.to_numeric()) else {
+            return Value::Null;
+        };
+        let (x, y) = (l.to_integer().unwrap(), r.to_integer().unwrap());
+        if y == 0 {
+            return Value::Null;
+        }
+        // i64::MIN % -1 overflows
+        let remainder = x.checked_rem(y).unwrap_or(0);
+        match (l, r) {
+            (Value::Integer(_), Value::Integer(_)) => Value::Integer(remainder),
+            _ => Value::Real(remainder as f64),
+        }
+    }
+}
+
+impl<'b> BitAnd<&'b Value> for &Value {
+    type Output = Value;
+    fn bitand(self, rhs: &'b Value) -> Self::Output {
+        bitwise(self, rhs, |x, y| x & y)
+    }
+}
+
+impl<'b> BitOr<&'b Value> for &Value {
+    type Output = Value;
+    fn bitor(self, rhs: &'b Value) -> Self::Output {
+        bitwise(self, rhs, |x, y| x | y)
+    }
+}
+
+impl<'b> Shl<&'b Value> for &Value {
+    type Output = Value;
+    fn shl(self, rhs: &'b Value) -> Self::Output {
+        bitwise(self, rhs, shift_left)
+    }
+}
+
+impl<'b> Shr<&'b Value> for &Value {
+    type Output = Value;
+    fn shr(self, rhs: &'b Value) -> Self::Output {
+        bitwise(self, rhs, shift_right)
+    }
+}
+
+impl Neg for &Value {
+    type Output = Value;
+    fn neg(self) -> Self::Output {
+        match self.to_numeric() {
+            Some(Value::Integer(integer)) => integer
+                .checked_neg()
+                .map_or(Value::Real(-(integer as f64)), Value::Integer),
+            Some(Value::Real(real)) => Value::Real(-real),
+            _ => Value::Null,
         }
     }
 }
@@ -95,9 +330,114 @@ impl Display for Value {
         f.write_str(&match self {
             Self::Null => String::from("null"),
             Self::Integer(i) => i.to_string(),
-            Self::Real(r) => r.to_string(),
+            Self::Real(r) => real_to_text(*r),
             Self::Text(text) => text.clone(),
             Self::Blob(blob) => format!("{:x?}", blob),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::Value;
+
+    #[test]
+    fn compare_storage_classes() {
+        let values = [
+            Value::Null,
+            Value::Integer(-3),
+            Value::Real(-2.5),
+            Value::Integer(2),
+            Value::Real(2.5),
+            Value::Text(String::from("10")),
+            Value::Text(String::from("9")),
+            Value::Blob(vec![0]),
+        ];
+
+        for pair in values.windows(2) {
+            assert_eq!(pair[0].compare(&pair[1]), Ordering::Less);
+            assert_eq!(pair[1].compare(&pair[0]), Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn compare_integer_real() {
+        assert_eq!(
+            Value::Integer(2).compare(&Value::Real(2.0)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Value::Integer(i64::MAX).compare(&Value::Real(9.3e18)),
+            Ordering::Less
+        );
+        assert_eq!(
+            Value::Integer(9007199254740993).compare(&Value::Real(9007199254740992.0)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn three_valued_logic() {
+        let (t, f, null) = (Value::Integer(1), Value::Integer(0), Value::Null);
+
+        assert_eq!(null.and(&f), f);
+        assert_eq!(null.and(&t), null);
+        assert_eq!(t.and(&t), t);
+        assert_eq!(null.or(&t), t);
+        assert_eq!(null.or(&f), null);
+        assert_eq!(f.or(&f), f);
+
+        assert_eq!(null.compare_nullable(&null, Ordering::is_eq), null);
+        assert_eq!(t.compare_nullable(&null, Ordering::is_ne), null);
+        assert_eq!(t.compare_nullable(&f, Ordering::is_gt), t);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let text = |s: &str| Value::Text(String::from(s));
+        let (int, real) = (Value::Integer, Value::Real);
+
+        assert_eq!(&text("3") + &int(1), int(4));
+        assert_eq!(&text("3.0") + &int(1), real(4.0));
+        assert_eq!(&text("abc") + &int(1), int(1));
+        assert_eq!(&int(i64::MAX) + &int(1), real(9.223372036854776e18));
+        assert_eq!(&int(7) / &int(2), int(3));
+        assert_eq!(&real(7.0) / &int(2), real(3.5));
+        assert_eq!(&int(5) / &int(0), Value::Null);
+        assert_eq!(&int(-7) % &int(3), int(-1));
+        assert_eq!(&real(5.5) % &int(2), real(1.0));
+        assert_eq!(&int(5) % &int(0), Value::Null);
+        assert_eq!(&Value::Null * &int(2), Value::Null);
+        assert_eq!(-&text("3"), int(-3));
+        assert_eq!(-&int(i64::MIN), real(9.223372036854776e18));
+    }
+
+    #[test]
+    fn bitwise() {
+        let int = Value::Integer;
+
+        assert_eq!(&int(6) & &int(3), int(2));
+        assert_eq!(&int(6) | &Value::Text(String::from("3.9")), int(7));
+        assert_eq!(&int(1) << &int(64), int(0));
+        assert_eq!(&int(-1) >> &int(70), int(-1));
+        assert_eq!(&int(1) << &int(-1), int(0));
+        assert_eq!(&int(4) >> &int(-1), int(8));
+        assert_eq!(int(5).bit_not(), int(-6));
+        assert_eq!(Value::Null.bit_not(), Value::Null);
+    }
+
+    #[test]
+    fn truth_of_values() {
+        let text = |s: &str| Value::Text(String::from(s));
+
+        assert_eq!(Value::Null.truth(), None);
+        assert_eq!(Value::Integer(-2).truth(), Some(true));
+        assert_eq!(Value::Real(0.5).truth(), Some(true));
+        assert_eq!(text("abc").truth(), Some(false));
+        assert_eq!(text("1x").truth(), Some(true));
+        assert_eq!(text(" 0.0").truth(), Some(false));
+        assert!(!bool::from(&Value::Null));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::bytes::from_be_bytes::from_be_bytes;
+
+const MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+const SECTOR_SIZE: usize = 512;
+
+/// Rollback journal: the original content of every page is saved here
+/// before the page is modified for the first time in a transaction.
+///
+/// The journal is written in the format SQLite uses,
+/// so that a journal left behind by a crash (a "hot" journal) can be rolled back by SQLite as well.
+/// https://www.sqlite.org/fileformat.html#the_rollback_journal
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+    page_size: usize,
+    nonce: u32,
+    journaled_pages: HashSet<u32>,
+    // Database size in pages when the transaction started
+    db_page_cnt: u32,
+}
+
+impl Journal {
+    pub fn create(path: PathBuf, page_size: usize, db_page_cnt: u32) -> Self {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos()
+            ^ std::process::id();
+
+        // The header fills the first sector
+        let mut header = vec![0; SECTOR_SIZE];
+        header[..8].copy_from_slice(&MAGIC);
+        // Page count -1: the page records extend to the end of the file
+        header[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        header[12..16].copy_from_slice(&nonce.to_be_bytes());
+        header[16..20].copy_from_slice(&db_page_cnt.to_be_bytes());
+        header[20..24].copy_from_slice(&(SECTOR_SIZE as u32).to_be_bytes());
+        header[24..28].copy_from_slice(&(page_size as u32).to_be_bytes());
+        file.write_all(&header).unwrap();
+        file.sync_all().unwrap();
+
+        Self {
+            file,
+            path,
+            page_size,
+            nonce,
+            journaled_pages: HashSet::new(),
+            db_page_cnt,
+        }
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Pages that did not exist when the transaction started are removed on rollback
+    /// and don't need to be saved.
+    pub fn needs(&self, page_no: u32) -> bool {
+        page_no <= self.db_page_cnt && !self.journaled_pages.contains(&page_no)
+    }
+
+    /// Appends the original content of a page.
+    /// The journal is synced before the caller overwrites the page in the database file.
+    pub fn append(&mut self, page_no: u32, page: &[u8]) {
+        let mut record = page_no.to_be_bytes().to_vec();
+        record.extend_from_slice(page);
+        record.extend(checksum(self.nonce, page).to_be_bytes());
+
+        self.file.seek(SeekFrom::End(0)).unwrap();
+        self.file.write_all(&record).unwrap();
+        self.file.sync_all().unwrap();
+
+        self.journaled_pages.insert(page_no);
+    }
+
+    /// Writes the saved pages back to the database file and deletes the journal.
+    pub fn rollback(self, db_file: &mut File) {
+        let Self { file, path, .. } = self;
+        drop(file);
+        replay(db_file, &path);
+    }
+
+    /// The transaction is committed once the journal is deleted.
+    pub fn commit(self) {
+        let Self { file, path, .. } = self;
+        drop(file);
+        fs::remove_file(path).unwrap();
+    }
+}
+
+/// Statement journal: the content of the pages before the running statement modified them,
+/// kept in memory so that a failed statement can be undone without ending the transaction.
+#[derive(Debug)]
+pub struct StatementJournal {
+    page_size: usize,
+    pages: HashMap<u32, Vec<u8>>,
+    // Database size in pages when the statement started
+    db_page_cnt: u32,
+}
+
+impl StatementJournal {
+    pub fn new(page_size: usize, db_page_cnt: u32) -> Self {
+        Self {
+            page_size,
+            pages: HashMap::new(),
+            db_page_cnt,
+        }
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Pages added by the statement are removed on rollback and don't need to be saved.
+    pub fn needs(&self, page_no: u32) -> bool {
+        page_no <= self.db_page_cnt && !self.pages.contains_key(&page_no)
+    }
+
+    pub fn append(&mut self, page_no: u32, page: &[u8]) {
+        self.pages.insert(page_no, page.to_vec());
+    }
+
+    /// Writes the saved pages back to the database file and removes the pages added since.
+    pub fn rollback(self, db_file: &mut File) {
+        for (page_no, page) in self.pages {
+            let start = (page_no as u64 - 1) * self.page_size as u64;
+            db_file.seek(SeekFrom::Start(start)).unwrap();
+            db_file.write_all(&page).unwrap();
+        }
+        db_file
+            .set_len(self.db_page_cnt as u64 * self.page_size as u64)
+            .unwrap();
+    }
+}
+
+/// Path of the rollback journal belonging to a database file
+pub fn journal_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push("-journal");
+    PathBuf::from(path)
+}
+
+/// Rolls back a hot journal, if there is one, and deletes it.
+/// Page records with a wrong checksum (partially written before a crash) end the playback.
+pub fn replay(db_file: &mut File, path: &Path) {
+    let Ok(mut file) = File::open(path) else {
+        return;
+    };
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes).unwrap();
+    drop(file);
+
+    if bytes.len() >= 28 && bytes[..8] == MAGIC {
+        let window = &mut &bytes[8..28];
+        let page_cnt: u32 = from_be_bytes(window);
+        let nonce: u32 = from_be_bytes(window);
+        let db_page_cnt: u32 = from_be_bytes(window);
+        let sector_size: u32 = from_be_bytes(window);
+        let page_size: u32 = from_be_bytes(&mut &bytes[24..28]);
+
+        let page_size = page_size as usize;
+        let record_len = 4 + page_size + 4;
+        let records = bytes.get(sector_size as usize..).unwrap_or_default();
+        let records = records.chunks_exact(record_len).take(page_cnt as usize);
+
+        for record in records {
+            let window = &mut &record[..];
+            let page_no: u32 = from_be_bytes(window);
+            let page = &window[..page_size];
+            let stored_checksum: u32 = from_be_bytes(&mut &window[page_size..]);
+            if page_no == 0 || stored_checksum != checksum(nonce, page) {
+                break;
+            }
+
+            let start = (page_no as u64 - 1) * page_size as u64;
+            db_file.seek(SeekFrom::Start(start)).unwrap();
+            db_file.write_all(page).unwrap();
+        }
+
+        db_file
+            .set_len(db_page_cnt as u64 * page_size as u64)
+            .unwrap();
+        db_file.sync_all().unwrap();
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+/// The nonce plus every 200th byte of the page, starting from the end.
+fn checksum(nonce: u32, page: &[u8]) -> u32 {
+    (1..)
+        .map(|i| i * 200)
+        .take_while(|offset| *offset < page.len())
+        .fold(nonce, |checksum, offset| {
+            checksum.wrapping_add(page[page.len() - offset] as u32)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checksum;
+
+    #[test]
+    fn checksum_samples_every_200th_byte() {
+        let mut page = vec![0; 512];
+        page[312] = 1;
+        page[112] = 2;
+        page[113] = 100;
+
+        assert_eq!(checksum(7, &page), 10);
+    }
+}
@@ -1,5 +1,11 @@
 mod bytes;
 pub mod engine;
+mod journal;
+#[cfg(unix)]
+mod mmap;
 mod sql;
 mod sqlite_file;
 mod sqlite_storage;
+#[cfg(test)]
+mod test_util;
+mod wal;
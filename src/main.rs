@@ -13,7 +13,7 @@ fn main() -> Result<()> {
     let file_path = &args[1];
     let cmd = &args[2];
 
-    let mut engine = new_engine(&file_path);
+    let mut engine = new_engine(file_path);
     engine.exec(cmd);
 
     Ok(())
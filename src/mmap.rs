@@ -0,0 +1,97 @@
+use std::{ffi::c_void, fmt::Debug, fs::File, io, ops::Deref, os::unix::io::AsRawFd, ptr, slice};
+
+const PROT_READ: i32 = 1;
+const MAP_SHARED: i32 = 1;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: i32,
+        flags: i32,
+        fd: i32,
+        offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+}
+
+/// Read-only shared mapping of a whole file.
+///
+/// Writes to the file would change the bytes under the slices borrowed from the mapping,
+/// so the file must not be written while a mapping of it is alive.
+/// The mapping does not grow with the file and has to be replaced after the file was extended.
+pub struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Returns None for an empty file, which cannot be mapped.
+    pub fn map(file: &File) -> io::Result<Option<Self>> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        // SAFETY: the kernel picks the address, the length is not zero
+        // and the descriptor stays open for the duration of the call.
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Some(Self { ptr, len }))
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is readable for `len` bytes until it is unmapped on drop,
+        // and the file is not written while it is mapped, so the bytes do not change.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the range was mapped in `map` and is unmapped once,
+        // no slice borrowed from it outlives `self`.
+        unsafe {
+            munmap(self.ptr, self.len);
+        }
+    }
+}
+
+impl Debug for Mmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mmap").field("len", &self.len).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::PathBuf};
+
+    use super::Mmap;
+
+    #[test]
+    fn map_file() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let file = File::open(root.join("dbs/mountains.db")).unwrap();
+
+        let mmap = Mmap::map(&file).unwrap().unwrap();
+        assert_eq!(mmap.len(), 16384);
+        assert_eq!(&mmap[..16], b"SQLite format 3\0");
+    }
+}
@@ -1,24 +1,58 @@
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum BinOp {
-    Or,  /* OR */
-    And, /* AND */
-    Eq,  /* =, == */
-    Neq, /* <>, != */
-    Lt,  /* < */
-    Lte, /* <= */
-    Gt,  /* > */
-    Gte, /* >= */
-    Add, /* + */
-    Sub, /* - */
-    Mul, /* * */
-    Div, /* / */
-}
-
-#[derive(Debug, PartialEq)]
+    Or,     /* OR */
+    And,    /* AND */
+    Eq,     /* =, == */
+    Neq,    /* <>, != */
+    Lt,     /* < */
+    Lte,    /* <= */
+    Gt,     /* > */
+    Gte,    /* >= */
+    Add,    /* + */
+    Sub,    /* - */
+    Mul,    /* * */
+    Div,    /* / */
+    Is,     /* IS */
+    IsNot,  /* IS NOT */
+    Concat, /* || */
+    Rem,    /* % */
+    BitAnd, /* & */
+    BitOr,  /* | */
+    Shl,    /* << */
+    Shr,    /* >> */
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Not,    /* NOT */
+    Neg,    /* - */
+    Plus,   /* + */
+    BitNot, /* ~ */
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LikeOp {
+    Like, /* LIKE */
+    Glob, /* GLOB */
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Literal {
+    Null,
     Integer(i64),
+    Real(f64),
     Text(String),
+    Blob(Vec<u8>),
     Id(String),
+    /// `CURRENT_TIME`, `CURRENT_DATE` and `CURRENT_TIMESTAMP` in UTC, evaluated when used
+    CurrentTime(CurrentTime),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CurrentTime {
+    Time,      /* HH:MM:SS */
+    Date,      /* YYYY-MM-DD */
+    Timestamp, /* YYYY-MM-DD HH:MM:SS */
 }
 
 #[cfg(test)]
@@ -37,10 +71,187 @@ impl Literal {
 }
 
 // #[derive(Debug, PartialEq)]
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Binary(BinOp, Box<Expr>, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    /// `expr [NOT] BETWEEN low AND high`
+    Between {
+        expr: Box<Expr>,
+        not: bool,
+        low: Box<Expr>,
+        high: Box<Expr>,
+    },
+    /// `expr [NOT] IN (list)`
+    In {
+        expr: Box<Expr>,
+        not: bool,
+        list: Vec<Expr>,
+    },
+    /// `expr [NOT] LIKE pattern [ESCAPE escape]` or `expr [NOT] GLOB pattern`
+    Like {
+        op: LikeOp,
+        expr: Box<Expr>,
+        not: bool,
+        pattern: Box<Expr>,
+        escape: Option<Box<Expr>>,
+    },
+    /// `CASE [operand] WHEN when THEN then ... [ELSE else_result] END`,
+    /// without an operand each `when` is a condition
+    Case {
+        operand: Option<Box<Expr>>,
+        when_then: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+    /// `CAST(expr AS type_name)`
+    Cast {
+        expr: Box<Expr>,
+        type_name: String,
+    },
     Literal(Literal),
+    Function(Function),
+    /// `(SELECT ...)`, the first column of the first row or NULL
+    Subquery(Box<SelectStmt>),
+    /// `expr [NOT] IN (SELECT ...)`
+    InSelect {
+        expr: Box<Expr>,
+        not: bool,
+        select: Box<SelectStmt>,
+    },
+    /// `EXISTS (SELECT ...)`
+    Exists(Box<SelectStmt>),
+}
+
+impl Expr {
+    /// The direct subexpressions, in the order they appear in the SQL text
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Binary(_, l, r) => vec![l, r],
+            Expr::Unary(_, expr) => vec![expr],
+            Expr::Between {
+                expr, low, high, ..
+            } => vec![expr, low, high],
+            Expr::In { expr, list, .. } => [&**expr].into_iter().chain(list).collect(),
+            Expr::Like {
+                expr,
+                pattern,
+                escape,
+                ..
+            } => [expr, pattern]
+                .into_iter()
+                .chain(escape)
+                .map(|e| &**e)
+                .collect(),
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => (operand.as_deref().into_iter())
+                .chain(when_then.iter().flat_map(|(when, then)| [when, then]))
+                .chain(else_result.as_deref())
+                .collect(),
+            Expr::Cast { expr, .. } => vec![expr],
+            Expr::Literal(_) => vec![],
+            Expr::Function(function) => function.args.iter().collect(),
+            // A subquery is a scope of its own
+            Expr::InSelect { expr, .. } => vec![expr],
+            Expr::Subquery(_) | Expr::Exists(_) => vec![],
+        }
+    }
+
+    /// The query of a subquery expression
+    pub fn subquery(&self) -> Option<&SelectStmt> {
+        match self {
+            Expr::Subquery(select) | Expr::InSelect { select, .. } | Expr::Exists(select) => {
+                Some(select)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the expression with every direct subexpression replaced by the result of `f`.
+    pub fn try_map_children<E>(
+        &self,
+        mut f: impl FnMut(&Expr) -> Result<Expr, E>,
+    ) -> Result<Expr, E> {
+        let mut map = |expr: &Expr| f(expr).map(Box::new);
+
+        Ok(match self {
+            Expr::Binary(op, l, r) => Expr::Binary(op.clone(), map(l)?, map(r)?),
+            Expr::Unary(op, expr) => Expr::Unary(*op, map(expr)?),
+            Expr::Between {
+                expr,
+                not,
+                low,
+                high,
+            } => Expr::Between {
+                expr: map(expr)?,
+                not: *not,
+                low: map(low)?,
+                high: map(high)?,
+            },
+            Expr::In { expr, not, list } => Expr::In {
+                expr: map(expr)?,
+                not: *not,
+                list: list
+                    .iter()
+                    .map(|item| map(item).map(|item| *item))
+                    .collect::<Result<_, _>>()?,
+            },
+            Expr::Like {
+                op,
+                expr,
+                not,
+                pattern,
+                escape,
+            } => Expr::Like {
+                op: *op,
+                expr: map(expr)?,
+                not: *not,
+                pattern: map(pattern)?,
+                escape: escape.as_deref().map(&mut map).transpose()?,
+            },
+            Expr::Case {
+                operand,
+                when_then,
+                else_result,
+            } => Expr::Case {
+                operand: operand.as_deref().map(&mut map).transpose()?,
+                when_then: when_then
+                    .iter()
+                    .map(|(when, then)| Ok((*map(when)?, *map(then)?)))
+                    .collect::<Result<_, _>>()?,
+                else_result: else_result.as_deref().map(&mut map).transpose()?,
+            },
+            Expr::Cast { expr, type_name } => Expr::Cast {
+                expr: map(expr)?,
+                type_name: type_name.clone(),
+            },
+            Expr::InSelect { expr, not, select } => Expr::InSelect {
+                expr: map(expr)?,
+                not: *not,
+                select: select.clone(),
+            },
+            Expr::Literal(_) | Expr::Subquery(_) | Expr::Exists(_) => self.clone(),
+            Expr::Function(function) => Expr::Function(Function {
+                name: function.name.clone(),
+                distinct: function.distinct,
+                args: function
+                    .args
+                    .iter()
+                    .map(|arg| map(arg).map(|arg| *arg))
+                    .collect::<Result<_, _>>()?,
+            }),
+        })
+    }
+}
+
+/// A function call, `COUNT(*)` has no arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub distinct: bool,
+    pub args: Vec<Expr>,
 }
 
 #[cfg(test)]
@@ -52,25 +263,141 @@ impl Expr {
     pub fn new_literal(literal: Literal) -> Expr {
         Expr::Literal(literal)
     }
+
+    pub fn new_function(name: &str, args: Vec<Expr>) -> Expr {
+        Expr::Function(Function {
+            name: String::from(name),
+            distinct: false,
+            args,
+        })
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub collation: Option<String>,
+    pub desc: bool,
+    // None if the NULLS FIRST/LAST clause is omitted
+    pub nulls_first: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitClause {
+    pub limit: Expr,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableRef {
+    /// Empty for a subquery without an alias
+    pub table_name: String,
+    pub alias: Option<String>,
+    /// `(SELECT ...)` in place of a table
+    pub subquery: Option<Box<SelectStmt>>,
+}
+
+impl TableRef {
+    /// The name columns are qualified with
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.table_name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinKind {
+    Inner, /* JOIN, INNER JOIN, comma */
+    Left,  /* LEFT [OUTER] JOIN */
+    Cross, /* CROSS JOIN */
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JoinConstraint {
+    On(Expr),
+    Using(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: TableRef,
+    pub constraint: Option<JoinConstraint>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FromClause {
+    pub table: TableRef,
+    pub joins: Vec<Join>,
+}
+
+/// `table_name [(column_names)] AS (select_stmt)` of a WITH clause,
+/// recursive if the second SELECT of a UNION reads the table itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommonTableExpr {
+    pub table_name: String,
+    pub column_names: Option<Vec<String>>,
+    pub select_stmt: SelectStmt,
+}
+
+/// `UNION [ALL] select_stmt`, the ORDER BY and LIMIT of the SELECT apply to the whole union
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnionClause {
+    pub all: bool,
+    pub select_stmt: SelectStmt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct SelectStmt {
+    /// The common table expressions of the WITH clause
+    pub with_clause: Vec<CommonTableExpr>,
     pub select_clause: Vec<Expr>,
-    pub from_clause: String,
+    /// The names of the result columns
+    pub column_names: Vec<String>,
+    /// None for a SELECT without FROM, which yields a single row
+    pub from_clause: Option<FromClause>,
     pub where_clause: Option<Expr>,
+    pub group_by_clause: Vec<Expr>,
+    pub having_clause: Option<Expr>,
+    pub order_by_clause: Vec<OrderingTerm>,
+    pub limit_clause: Option<LimitClause>,
+    pub union: Option<Box<UnionClause>>,
 }
 
-impl SelectStmt {
-    pub fn new_select(
-        select_clause: Vec<Expr>,
-        from_clause: &str,
-        where_clause: Option<Expr>,
-    ) -> Self {
-        Self {
-            select_clause,
-            from_clause: String::from(from_clause),
-            where_clause,
-        }
-    }
+#[derive(Debug)]
+pub struct InsertStmt {
+    pub table_name: String,
+    pub column_names: Option<Vec<String>>,
+    pub values: Vec<Vec<Expr>>,
+}
+
+#[derive(Debug)]
+pub struct UpdateStmt {
+    pub table_name: String,
+    pub assignments: Vec<(String, Expr)>,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct DeleteStmt {
+    pub table_name: String,
+    pub where_clause: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct PragmaStmt {
+    pub name: String,
+    pub value: Option<Literal>,
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Select(Box<SelectStmt>),
+    ExplainQueryPlan(Box<SelectStmt>),
+    Insert(InsertStmt),
+    Update(UpdateStmt),
+    Delete(DeleteStmt),
+    Begin,
+    Commit,
+    Rollback,
+    Pragma(PragmaStmt),
 }
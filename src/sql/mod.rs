@@ -1,7 +1,18 @@
 mod ast;
+// peg's generated parser wraps rule actions in closures it calls immediately
+#[allow(clippy::redundant_closure_call)]
 mod parser;
 
+#[allow(clippy::module_inception)]
 pub mod sql;
 
-pub use ast::{BinOp, Expr, Literal};
-pub use parser::{parse_create_index_stmt, parse_select_stmt};
+pub use ast::{
+    BinOp, CommonTableExpr, CurrentTime, DeleteStmt, Expr, FromClause, Function, InsertStmt, Join,
+    JoinConstraint, JoinKind, LikeOp, LimitClause, Literal, OrderingTerm, PragmaStmt, SelectStmt,
+    Stmt, TableRef, UnaryOp, UnionClause, UpdateStmt,
+};
+#[cfg(test)]
+pub use parser::parse_expr;
+pub use parser::{
+    parse_create_index_stmt, parse_create_table_stmt, parse_create_view_stmt, parse_stmt,
+};
@@ -1,6 +1,13 @@
 use super::{
-    ast::SelectStmt,
-    sql::CreateIndexStmt,
+    ast::{
+        CommonTableExpr, CurrentTime, DeleteStmt, FromClause, Function, InsertStmt, Join,
+        JoinConstraint, JoinKind, LikeOp, LimitClause, OrderingTerm, PragmaStmt, SelectStmt, Stmt,
+        TableRef, UnaryOp, UnionClause, UpdateStmt,
+    },
+    sql::{
+        ColumnConstraint, ColumnDef, CreateIndexStmt, CreateTableStmt, CreateViewStmt,
+        ForeignKeyClause, IndexedColumn, TableConstraint,
+    },
     {BinOp, Expr, Literal},
 };
 
@@ -16,8 +23,8 @@ parser! {
         rule i(literal: &'static str)
             = input:$([_]*<{literal.len()}>) {? if input.eq_ignore_ascii_case(literal) { Ok(()) } else { Err(literal) } }
 
-        // whitespace
-        rule _ = [' ' | '\n' | '\t']*
+        // whitespace and comments, a block comment may be left open at the end
+        rule _ = ([' ' | '\n' | '\r' | '\t'] / "--" [^ '\n']* / "/*" (!"*/" [_])* ("*/" / ![_]))*
 
         // character
         rule alpha() -> &'input str = a:$(['a'..='z' | 'A'..='Z']) { a }
@@ -32,37 +39,192 @@ parser! {
         rule tok_comma() -> &'input str = _ t:$"," {t}
         rule tok_semi() -> &'input str = _ t:$";" {t}
 
-        rule tok_or() -> &'input str = _ t:$"OR" {t}
-        rule tok_and() -> &'input str = _ t:$"AND" {t}
+        rule tok_or() -> &'input str = _ t:$i("or") !alphanum_() {t}
+        rule tok_and() -> &'input str = _ t:$i("and") !alphanum_() {t}
         rule tok_eq() -> &'input str = _ t:$("==" / "=") {t}
         rule tok_neq() -> &'input str = _ t:$("<>" / "!=") {t}
-        rule tok_lt() -> &'input str = _ t:$"<" {t}
+        rule tok_lt() -> &'input str = _ t:$"<" !['<' | '=' | '>'] {t}
         rule tok_lte() -> &'input str = _ t:$"<=" {t}
-        rule tok_gt() -> &'input str = _ t:$">" {t}
+        rule tok_gt() -> &'input str = _ t:$">" !['>' | '='] {t}
         rule tok_gte() -> &'input str = _ t:$">=" {t}
         rule tok_add() -> &'input str = _ t:$"+" {t}
         rule tok_sub() -> &'input str = _ t:$"-" {t}
         rule tok_star() -> &'input str = _ t:$"*" {t}
         rule tok_div() -> &'input str = _ t:$"/" {t}
+        rule tok_rem() -> &'input str = _ t:$"%" {t}
+        rule tok_concat() -> &'input str = _ t:$"||" {t}
+        rule tok_bit_and() -> &'input str = _ t:$"&" {t}
+        rule tok_bit_or() -> &'input str = _ t:$"|" !"|" {t}
+        rule tok_bit_not() -> &'input str = _ t:$"~" {t}
+        rule tok_shl() -> &'input str = _ t:$"<<" {t}
+        rule tok_shr() -> &'input str = _ t:$">>" {t}
 
+        rule hex_digit() = ['0'..='9' | 'a'..='f' | 'A'..='F']
+
+        // A hexadecimal integer is the bits of a 64-bit integer, a decimal one too large for 64 bits is a real
         rule tok_integer() -> Literal
-            = _ i:$(num()+) { Literal::Integer(i.parse().unwrap()) }
+            = _ "0" ['x' | 'X'] h:$(hex_digit()+) {?
+                u64::from_str_radix(h, 16).map(|h| Literal::Integer(h as i64)).map_err(|_| "hex literal too big")
+            }
+            / _ i:$(num()+) { i.parse().map_or_else(|_| Literal::Real(i.parse().unwrap()), Literal::Integer) }
+
+        rule exponent() = ['e' | 'E'] ['+' | '-']? num()+
 
+        rule tok_real() -> Literal
+            = _ r:$(num()+ "." num()* exponent()? / "." num()+ exponent()? / num()+ exponent()) {
+                Literal::Real(r.parse().unwrap())
+            }
+
+        // A quote inside a string is written twice
         rule tok_string() -> Literal
-            = _ "'" s:$([^ '\'']*) "'" { Literal::Text(s.into()) }
+            = _ "'" s:$(([^ '\''] / "''")*) "'" { Literal::Text(s.replace("''", "'")) }
+
+        rule tok_blob() -> Literal
+            = _ ['x' | 'X'] "'" h:$(hex_digit()*) "'" {?
+                match h.len() % 2 {
+                    0 => Ok(Literal::Blob(
+                        (0..h.len()).step_by(2).map(|i| u8::from_str_radix(&h[i..i + 2], 16).unwrap()).collect(),
+                    )),
+                    _ => Err("an even number of hex digits"),
+                }
+            }
+
+        rule tok_quoted_word() -> String
+            = _ "'" s:$(([^ '\''] / "''")*) "'" { s.replace("''", "'") }
+
+        rule tok_word() -> &'input str
+            = _ w:$(alpha_() alphanum_()*) { w }
+
+        // A name may be quoted in "double quotes", [brackets] or `backticks`,
+        // a quote inside double quotes or backticks is written twice
+        rule tok_name() -> String
+            = _ "\"" n:$(([^ '"'] / "\"\"")*) "\"" { n.replace("\"\"", "\"") }
+            / _ "[" n:$([^ ']']*) "]" { n.into() }
+            / _ "`" n:$(([^ '`'] / "``")*) "`" { n.replace("``", "`") }
+            / w:tok_word() { w.into() }
+
+        // Outside of expressions a name may also be a 'string'
+        rule tok_schema_name() -> String
+            = tok_name() / tok_quoted_word()
 
+        // A column name may be qualified by a table name or alias
         rule tok_id() -> Literal
-            = _ i:$(alpha_() alphanum_()*) { Literal::Id(i.into()) }
+            = q:(q:tok_name() "." { q })? n:tok_name() {
+                Literal::Id(match q {
+                    Some(q) => format!("{q}.{n}"),
+                    None => n,
+                })
+            }
 
         // keyword
+        rule kw_abort() = _ i("abort") !alphanum_()
+        rule kw_action() = _ i("action") !alphanum_()
+        rule kw_always() = _ i("always") !alphanum_()
+        rule kw_all() = _ i("all") !alphanum_()
+        rule kw_and() = _ i("and") !alphanum_()
+        rule kw_as() = _ i("as") !alphanum_()
+        rule kw_asc() = _ i("asc")
+        rule kw_autoincrement() = _ i("autoincrement") !alphanum_()
+        rule kw_begin() = _ i("begin")
+        rule kw_between() = _ i("between") !alphanum_()
+        rule kw_by() = _ i("by")
+        rule kw_cascade() = _ i("cascade") !alphanum_()
+        rule kw_case() = _ i("case") !alphanum_()
+        rule kw_cast() = _ i("cast") !alphanum_()
+        rule kw_check() = _ i("check") !alphanum_()
+        rule kw_collate() = _ i("collate") !alphanum_()
+        rule kw_commit() = _ i("commit")
+        rule kw_conflict() = _ i("conflict") !alphanum_()
+        rule kw_constraint() = _ i("constraint") !alphanum_()
         rule kw_create() = _ i("create")
+        rule kw_cross() = _ i("cross")
+        rule kw_current_date() = _ i("current_date") !alphanum_()
+        rule kw_current_time() = _ i("current_time") !alphanum_()
+        rule kw_current_timestamp() = _ i("current_timestamp") !alphanum_()
+        rule kw_default() = _ i("default") !alphanum_()
+        rule kw_deferrable() = _ i("deferrable") !alphanum_()
+        rule kw_deferred() = _ i("deferred") !alphanum_()
+        rule kw_delete() = _ i("delete")
+        rule kw_desc() = _ i("desc")
+        rule kw_distinct() = _ i("distinct")
+        rule kw_else() = _ i("else") !alphanum_()
+        rule kw_end() = _ i("end")
+        rule kw_escape() = _ i("escape") !alphanum_()
+        rule kw_exists() = _ i("exists") !alphanum_()
+        rule kw_explain() = _ i("explain")
+        rule kw_fail() = _ i("fail") !alphanum_()
+        rule kw_false() = _ i("false") !alphanum_()
+        rule kw_first() = _ i("first")
+        rule kw_foreign() = _ i("foreign") !alphanum_()
         rule kw_from() = _ i("from")
+        rule kw_generated() = _ i("generated") !alphanum_()
+        rule kw_glob() = _ i("glob") !alphanum_()
+        rule kw_group() = _ i("group")
+        rule kw_having() = _ i("having")
+        rule kw_if() = _ i("if") !alphanum_()
+        rule kw_ignore() = _ i("ignore") !alphanum_()
+        rule kw_immediate() = _ i("immediate") !alphanum_()
+        rule kw_in() = _ i("in") !alphanum_()
         rule kw_index() = _ i("index")
+        rule kw_initially() = _ i("initially") !alphanum_()
+        rule kw_inner() = _ i("inner")
+        rule kw_insert() = _ i("insert")
+        rule kw_into() = _ i("into")
+        rule kw_is() = _ i("is") !alphanum_()
+        rule kw_isnull() = _ i("isnull") !alphanum_()
+        rule kw_join() = _ i("join")
+        rule kw_key() = _ i("key") !alphanum_()
+        rule kw_last() = _ i("last")
+        rule kw_left() = _ i("left")
+        rule kw_like() = _ i("like") !alphanum_()
+        rule kw_limit() = _ i("limit")
+        rule kw_match() = _ i("match") !alphanum_()
+        rule kw_no() = _ i("no") !alphanum_()
+        rule kw_not() = _ i("not") !alphanum_()
+        rule kw_notnull() = _ i("notnull") !alphanum_()
+        rule kw_null() = _ i("null") !alphanum_()
+        rule kw_nulls() = _ i("nulls")
+        rule kw_offset() = _ i("offset")
         rule kw_on() = _ i("on")
-        rule kw_select() = _ i("select")
+        rule kw_order() = _ i("order")
+        rule kw_outer() = _ i("outer")
+        rule kw_plan() = _ i("plan")
+        rule kw_pragma() = _ i("pragma")
+        rule kw_primary() = _ i("primary") !alphanum_()
+        rule kw_query() = _ i("query")
+        rule kw_recursive() = _ i("recursive") !alphanum_()
+        rule kw_references() = _ i("references") !alphanum_()
+        rule kw_replace() = _ i("replace") !alphanum_()
+        rule kw_restrict() = _ i("restrict") !alphanum_()
+        rule kw_rollback() = _ i("rollback")
+        rule kw_rowid() = _ i("rowid") !alphanum_()
+        rule kw_select() = _ i("select") !alphanum_()
+        rule kw_set() = _ i("set")
+        rule kw_stored() = _ i("stored") !alphanum_()
+        rule kw_strict() = _ i("strict") !alphanum_()
         rule kw_table() = _ i("table")
-        rule kw_unique() = _ i("unique")
+        rule kw_temp() = _ i("temp") !alphanum_()
+        rule kw_temporary() = _ i("temporary") !alphanum_()
+        rule kw_then() = _ i("then") !alphanum_()
+        rule kw_transaction() = _ i("transaction")
+        rule kw_true() = _ i("true") !alphanum_()
+        rule kw_union() = _ i("union") !alphanum_()
+        rule kw_unique() = _ i("unique") !alphanum_()
+        rule kw_update() = _ i("update")
+        rule kw_using() = _ i("using")
+        rule kw_values() = _ i("values")
+        rule kw_view() = _ i("view") !alphanum_()
+        rule kw_virtual() = _ i("virtual") !alphanum_()
+        rule kw_when() = _ i("when") !alphanum_()
         rule kw_where() = _ i("where")
+        rule kw_with() = _ i("with") !alphanum_()
+        rule kw_without() = _ i("without") !alphanum_()
+
+        // keywords that end a table reference rather than being its alias
+        rule reserved()
+            = _ (i("cross") / i("group") / i("having") / i("inner") / i("join") / i("left")
+            / i("limit") / i("on") / i("order") / i("outer") / i("union") / i("using") / i("where"))
+            !alphanum_()
 
         // --------------------
         // syntacitc grammar
@@ -73,55 +235,457 @@ parser! {
             --
             l:(@) tok_and() r:@ { Expr::Binary(BinOp::And, Box::new(l), Box::new(r))}
             --
+            kw_not() e:@ { Expr::Unary(UnaryOp::Not, Box::new(e)) }
+            --
             l:(@) tok_eq()  r:@ { Expr::Binary(BinOp::Eq, Box::new(l), Box::new(r))}
             l:(@) tok_neq() r:@ { Expr::Binary(BinOp::Neq, Box::new(l), Box::new(r))}
+            l:(@) kw_is() kw_not() r:@ { Expr::Binary(BinOp::IsNot, Box::new(l), Box::new(r))}
+            l:(@) kw_is() r:@ { Expr::Binary(BinOp::Is, Box::new(l), Box::new(r))}
+            e:(@) kw_isnull() { Expr::Binary(BinOp::Is, Box::new(e), Box::new(Expr::Literal(Literal::Null)))}
+            e:(@) (kw_notnull() / kw_not() kw_null()) {
+                Expr::Binary(BinOp::IsNot, Box::new(e), Box::new(Expr::Literal(Literal::Null)))
+            }
+            e:(@) n:not() kw_between() l:comparison() kw_and() h:comparison() {
+                Expr::Between { expr: Box::new(e), not: n, low: Box::new(l), high: Box::new(h) }
+            }
+            e:(@) n:not() kw_in() s:subquery() {
+                Expr::InSelect { expr: Box::new(e), not: n, select: Box::new(s) }
+            }
+            e:(@) n:not() kw_in() tok_left_paren() l:exprs() tok_right_paren() {
+                Expr::In { expr: Box::new(e), not: n, list: l }
+            }
+            e:(@) n:not() kw_like() p:comparison() x:(kw_escape() x:comparison() { x })? {
+                Expr::Like {
+                    op: LikeOp::Like,
+                    expr: Box::new(e),
+                    not: n,
+                    pattern: Box::new(p),
+                    escape: x.map(Box::new),
+                }
+            }
+            e:(@) n:not() kw_glob() p:comparison() {
+                Expr::Like { op: LikeOp::Glob, expr: Box::new(e), not: n, pattern: Box::new(p), escape: None }
+            }
             --
+            c:comparison() { c }
+        }
+
+        // The operands of BETWEEN, LIKE and GLOB bind tighter than the AND of BETWEEN
+        rule comparison() -> Expr = precedence!{
             l:(@) tok_lt()  r:@ { Expr::Binary(BinOp::Lt, Box::new(l), Box::new(r))}
             l:(@) tok_lte()  r:@ { Expr::Binary(BinOp::Lte, Box::new(l), Box::new(r))}
             l:(@) tok_gt()  r:@ { Expr::Binary(BinOp::Gt, Box::new(l), Box::new(r))}
             l:(@) tok_gte()  r:@ { Expr::Binary(BinOp::Gte, Box::new(l), Box::new(r))}
             --
+            l:(@) tok_bit_and() r:@ { Expr::Binary(BinOp::BitAnd, Box::new(l), Box::new(r))}
+            l:(@) tok_bit_or() r:@ { Expr::Binary(BinOp::BitOr, Box::new(l), Box::new(r))}
+            l:(@) tok_shl() r:@ { Expr::Binary(BinOp::Shl, Box::new(l), Box::new(r))}
+            l:(@) tok_shr() r:@ { Expr::Binary(BinOp::Shr, Box::new(l), Box::new(r))}
+            --
             l:(@) tok_add() r:@ { Expr::Binary(BinOp::Add, Box::new(l), Box::new(r))}
             l:(@) tok_sub() r:@ { Expr::Binary(BinOp::Sub, Box::new(l), Box::new(r))}
             --
             l:(@) tok_star() r:@ { Expr::Binary(BinOp::Mul, Box::new(l), Box::new(r))}
             l:(@) tok_div() r:@ { Expr::Binary(BinOp::Div, Box::new(l), Box::new(r))}
+            l:(@) tok_rem() r:@ { Expr::Binary(BinOp::Rem, Box::new(l), Box::new(r))}
+            --
+            l:(@) tok_concat() r:@ { Expr::Binary(BinOp::Concat, Box::new(l), Box::new(r))}
             --
-            i:tok_integer() { Expr::Literal(i)  }
-            s:tok_string() { Expr::Literal(s) }
+            // The only integer whose absolute value does not fit 64 bits
+            tok_sub() _ "9223372036854775808" !['0'..='9' | '.' | 'e' | 'E'] {
+                Expr::Literal(Literal::Integer(i64::MIN))
+            }
+            tok_sub() e:@ { negate(e) }
+            tok_add() e:@ { Expr::Unary(UnaryOp::Plus, Box::new(e)) }
+            tok_bit_not() e:@ { Expr::Unary(UnaryOp::BitNot, Box::new(e)) }
+            --
+            c:case() { c }
+            kw_cast() tok_left_paren() e:expr() kw_as() t:type_name() tok_right_paren() {
+                Expr::Cast { expr: Box::new(e), type_name: t }
+            }
+            l:literal() { Expr::Literal(l) }
+            f:function() { Expr::Function(f) }
+            kw_exists() s:subquery() { Expr::Exists(Box::new(s)) }
             i:tok_id() { Expr::Literal(i) }
+            s:subquery() { Expr::Subquery(Box::new(s)) }
             tok_left_paren() e:expr() tok_right_paren() { e }
         }
 
+        rule literal() -> Literal
+            = kw_null() { Literal::Null }
+            / kw_true() { Literal::Integer(1) }
+            / kw_false() { Literal::Integer(0) }
+            / kw_current_timestamp() { Literal::CurrentTime(CurrentTime::Timestamp) }
+            / kw_current_time() { Literal::CurrentTime(CurrentTime::Time) }
+            / kw_current_date() { Literal::CurrentTime(CurrentTime::Date) }
+            / tok_blob() / tok_real() / tok_integer() / tok_string()
+
+        rule not() -> bool
+            = kw_not() { true } / { false }
+
+        rule case() -> Expr
+            = kw_case() o:(!kw_when() o:expr() { o })?
+            w:(kw_when() w:expr() kw_then() t:expr() { (w, t) })+
+            e:(kw_else() e:expr() { e })? kw_end()
+            {
+                Expr::Case { operand: o.map(Box::new), when_then: w, else_result: e.map(Box::new) }
+            }
+
+        // A name of one or more words with an optional size such as VARCHAR(255) or DECIMAL(10, 5)
+        // The words of a type name may be quoted
+        rule type_name() -> String
+            = w:(!column_constraint_start() w:(tok_name() / tok_quoted_word()) { w })+
+            s:$(tok_left_paren() _ tok_sub()? _ num()+ (tok_comma() _ tok_sub()? _ num()+)? tok_right_paren())? {
+                w.join(" ") + s.unwrap_or_default().trim()
+            }
+
+        rule function() -> Function
+            = n:tok_name() tok_left_paren() tok_star() tok_right_paren() {
+                Function { name: n, distinct: false, args: vec![] }
+            }
+            / n:tok_name() tok_left_paren() d:(kw_distinct() { true })? a:(expr() ** tok_comma()) tok_right_paren() {
+                Function { name: n, distinct: d.unwrap_or(false), args: a }
+            }
+
         rule exprs() -> Vec<Expr>
             = exprs:(expr() ** tok_comma()) { exprs }
 
-        rule select_clause() -> Vec<Expr>
-            = kw_select() _ ("count(*)" / "COUNT(*)") {vec![]} / kw_select() e:exprs() { e }
+        // A result column is named by its alias, the name of the column it refers to or its text
+        rule result_column() -> (Expr, String)
+            = t:&($(expr())) e:expr() a:(kw_as() a:tok_name() { a })? {
+                let name = match (a, &e) {
+                    (Some(a), _) => a,
+                    (None, Expr::Literal(Literal::Id(id))) => id.rsplit('.').next().unwrap().into(),
+                    (None, _) => t.trim().into(),
+                };
+                (e, name)
+            }
+
+        rule select_clause() -> (Vec<Expr>, Vec<String>)
+            = kw_select() c:(result_column() ** tok_comma()) { c.into_iter().unzip() }
+
+        rule alias() -> String
+            = kw_as()? !reserved() a:tok_name() { a }
+
+        rule table_ref() -> TableRef
+            = t:tok_name() a:alias()? {
+                TableRef { table_name: t, alias: a, subquery: None }
+            }
+            / s:subquery() a:alias()? {
+                TableRef { table_name: String::new(), alias: a, subquery: Some(Box::new(s)) }
+            }
 
-        rule from_clause() -> &'input str
-            = kw_from() f:$tok_id() { f.trim() }
+        rule join_kind() -> JoinKind
+            = tok_comma() { JoinKind::Inner }
+            / kw_cross() kw_join() { JoinKind::Cross }
+            / kw_left() kw_outer()? kw_join() { JoinKind::Left }
+            / kw_inner()? kw_join() { JoinKind::Inner }
+
+        rule join_constraint() -> JoinConstraint
+            = kw_on() e:expr() { JoinConstraint::On(e) }
+            / kw_using() tok_left_paren() c:(tok_name() ++ tok_comma()) tok_right_paren() {
+                JoinConstraint::Using(c)
+            }
+
+        rule join() -> Join
+            = k:join_kind() t:table_ref() c:join_constraint()? {
+                Join { kind: k, table: t, constraint: c }
+            }
+
+        rule from_clause() -> FromClause
+            = kw_from() t:table_ref() j:join()* { FromClause { table: t, joins: j } }
 
         rule where_clause() -> Expr
             = kw_where() w:expr() { w }
 
+        rule group_by_clause() -> Vec<Expr>
+            = kw_group() kw_by() g:(expr() ++ tok_comma()) { g }
+
+        rule having_clause() -> Expr
+            = kw_having() h:expr() { h }
+
+        rule ordering_term() -> OrderingTerm
+            = e:expr()
+            c:(kw_collate() c:tok_name() { c })?
+            d:(kw_asc() { false } / kw_desc() { true })?
+            n:(kw_nulls() n:(kw_first() { true } / kw_last() { false }) { n })?
+            {
+                OrderingTerm {
+                    expr: e,
+                    collation: c,
+                    desc: d.unwrap_or(false),
+                    nulls_first: n,
+                }
+            }
+
+        rule order_by_clause() -> Vec<OrderingTerm>
+            = kw_order() kw_by() o:(ordering_term() ++ tok_comma()) { o }
+
+        rule limit_clause() -> LimitClause
+            = kw_limit() o:expr() tok_comma() l:expr() { LimitClause { limit: l, offset: Some(o) } }
+            / kw_limit() l:expr() o:(kw_offset() o:expr() { o })? { LimitClause { limit: l, offset: o } }
+
+        rule union_clause() -> UnionClause
+            = kw_union() all:(kw_all() { true })? select_stmt:select() {
+                UnionClause { all: all.unwrap_or(false), select_stmt }
+            }
+
+        // A SELECT may be combined with one more by UNION
+        rule compound_select() -> SelectStmt
+            = s:select() u:union_clause()? { SelectStmt { union: u.map(Box::new), ..s } }
+
+        rule common_table_expr() -> CommonTableExpr
+            = table_name:tok_name() column_names:column_names()? kw_as()
+            tok_left_paren() select_stmt:compound_select() tok_right_paren()
+            {
+                CommonTableExpr {
+                    table_name,
+                    column_names,
+                    select_stmt,
+                }
+            }
+
+        // Whether a common table expression is recursive does not depend on RECURSIVE
+        rule with_clause() -> Vec<CommonTableExpr>
+            = kw_with() kw_recursive()? c:(common_table_expr() ++ tok_comma()) { c }
+
+        rule select() -> SelectStmt
+            = c:with_clause()? s:select_clause() f:from_clause()? w:where_clause()?
+            g:group_by_clause()? h:having_clause()?
+            o:order_by_clause()? l:limit_clause()?
+            {
+                SelectStmt {
+                    with_clause: c.unwrap_or_default(),
+                    select_clause: s.0,
+                    column_names: s.1,
+                    from_clause: f,
+                    where_clause: w,
+                    group_by_clause: g.unwrap_or_default(),
+                    having_clause: h,
+                    order_by_clause: o.unwrap_or_default(),
+                    limit_clause: l,
+                    union: None,
+                }
+            }
+
         pub rule select_stmt() -> SelectStmt
-            = s:select_clause() f:from_clause() w:where_clause()? tok_semi()? _ { SelectStmt::new_select(s, f, w) }
+            = s:compound_select() tok_semi()? _ { s }
+
+        rule subquery() -> SelectStmt
+            = tok_left_paren() s:compound_select() tok_right_paren() { s }
+
+        rule column_names() -> Vec<String>
+            = tok_left_paren() c:(tok_name() ++ tok_comma()) tok_right_paren() { c }
+
+        rule values_row() -> Vec<Expr>
+            = tok_left_paren() e:(expr() ++ tok_comma()) tok_right_paren() { e }
+
+        pub rule insert_stmt() -> InsertStmt
+            = kw_insert() kw_into() t:tok_name() c:column_names()?
+            kw_values() v:(values_row() ++ tok_comma())
+            tok_semi()? _
+            {
+                InsertStmt {
+                    table_name: t,
+                    column_names: c,
+                    values: v,
+                }
+            }
+
+        rule assignment() -> (String, Expr)
+            = c:tok_name() tok_eq() e:expr() { (c, e) }
+
+        pub rule update_stmt() -> UpdateStmt
+            = kw_update() t:tok_name() kw_set() a:(assignment() ++ tok_comma())
+            w:where_clause()? tok_semi()? _
+            {
+                UpdateStmt {
+                    table_name: t,
+                    assignments: a,
+                    where_clause: w,
+                }
+            }
+
+        pub rule delete_stmt() -> DeleteStmt
+            = kw_delete() kw_from() t:tok_name() w:where_clause()? tok_semi()? _
+            {
+                DeleteStmt {
+                    table_name: t,
+                    where_clause: w,
+                }
+            }
+
+        rule pragma_value() -> Literal
+            = tok_sub() i:tok_integer() {?
+                match i {
+                    Literal::Integer(i) => Ok(Literal::Integer(-i)),
+                    _ => Err("integer"),
+                }
+            }
+            / tok_integer() / tok_string() / tok_id()
+
+        pub rule pragma_stmt() -> PragmaStmt
+            = kw_pragma() n:tok_name() v:(tok_eq() v:pragma_value() { v })? tok_semi()? _
+            {
+                PragmaStmt {
+                    name: n,
+                    value: v,
+                }
+            }
+
+        pub rule stmt() -> Stmt
+            = s:select_stmt() { Stmt::Select(Box::new(s)) }
+            / kw_explain() kw_query() kw_plan() s:select_stmt() { Stmt::ExplainQueryPlan(Box::new(s)) }
+            / i:insert_stmt() { Stmt::Insert(i) }
+            / u:update_stmt() { Stmt::Update(u) }
+            / d:delete_stmt() { Stmt::Delete(d) }
+            / kw_begin() kw_transaction()? tok_semi()? _ { Stmt::Begin }
+            / (kw_commit() / kw_end()) kw_transaction()? tok_semi()? _ { Stmt::Commit }
+            / kw_rollback() kw_transaction()? tok_semi()? _ { Stmt::Rollback }
+            / p:pragma_stmt() { Stmt::Pragma(p) }
 
-        pub rule create_index_stmt() -> CreateIndexStmt<'input>
-            = kw_create() kw_unique()? kw_index()
-            tok_id() kw_on() table_name:$tok_id()
-            tok_left_paren() indexed_columns:($tok_id() ++ tok_comma()) tok_right_paren()
+        // An indexed column may have a collation and sort order
+        rule indexed_column_def() -> IndexedColumn
+            = column_name:tok_name()
+            collation:(kw_collate() c:tok_name() { c })?
+            desc:(kw_asc() { false } / kw_desc() { true })?
+            {
+                IndexedColumn {
+                    column_name,
+                    collation,
+                    desc: desc.unwrap_or(false),
+                }
+            }
+
+        rule indexed_column() -> String
+            = c:indexed_column_def() { c.column_name }
+
+        pub rule create_index_stmt() -> CreateIndexStmt
+            = kw_create() unique:(kw_unique() { true })? kw_index()
+            tok_schema_name() kw_on() table_name:tok_schema_name()
+            tok_left_paren() indexed_columns:(indexed_column_def() ++ tok_comma()) tok_right_paren()
             tok_semi()? _
             {
                 CreateIndexStmt {
-                    table_name: table_name.trim(),
-                    indexed_columns: indexed_columns
-                        .into_iter()
-                        .map(|indexed_column| indexed_column.trim())
-                        .collect(),
+                    unique: unique.unwrap_or(false),
+                    table_name,
+                    indexed_columns,
                 }
             }
+
+        // Words that end a type name or column definition
+        rule column_constraint_start()
+            = kw_constraint() / kw_primary() / kw_not() / kw_null() / kw_unique() / kw_check()
+            / kw_default() / kw_collate() / kw_references() / kw_generated() / kw_as()
+
+        rule table_constraint_start()
+            = kw_constraint() / kw_primary() / kw_unique() / kw_check() / kw_foreign()
+
+        rule constraint_name()
+            = kw_constraint() tok_name()
+
+        rule conflict_clause()
+            = kw_on() kw_conflict() (kw_rollback() / kw_abort() / kw_fail() / kw_ignore() / kw_replace())
+
+        rule foreign_key_action()
+            = kw_on() (kw_delete() / kw_update())
+            (kw_set() kw_null() / kw_set() kw_default() / kw_cascade() / kw_restrict() / kw_no() kw_action())
+            / kw_match() tok_name()
+
+        rule foreign_key_clause() -> ForeignKeyClause
+            = kw_references() t:tok_name() c:column_names()? foreign_key_action()*
+            (kw_not()? kw_deferrable() (kw_initially() (kw_deferred() / kw_immediate()))?)?
+            {
+                ForeignKeyClause { table_name: t, column_names: c.unwrap_or_default() }
+            }
+
+        // A DEFAULT is a signed number, a literal or an expression in parentheses
+        rule default_value() -> Expr
+            = tok_left_paren() e:expr() tok_right_paren() { e }
+            / tok_sub() l:literal() { negate(Expr::Literal(l)) }
+            / tok_add()? l:literal() { Expr::Literal(l) }
+
+        // A NULL constraint is accepted but has no meaning
+        rule column_constraint() -> Option<ColumnConstraint>
+            = constraint_name()? c:(
+                kw_primary() kw_key() d:(kw_asc() { false } / kw_desc() { true })? conflict_clause()?
+                a:(kw_autoincrement() { true })? {
+                    ColumnConstraint::PrimaryKey { desc: d.unwrap_or(false), autoincrement: a.unwrap_or(false) }
+                }
+                / kw_not() kw_null() conflict_clause()? { ColumnConstraint::NotNull }
+                / kw_unique() conflict_clause()? { ColumnConstraint::Unique }
+                / kw_check() tok_left_paren() e:expr() tok_right_paren() { ColumnConstraint::Check(e) }
+                / kw_default() e:default_value() { ColumnConstraint::Default(e) }
+                / kw_collate() n:tok_name() { ColumnConstraint::Collate(n) }
+                / f:foreign_key_clause() { ColumnConstraint::References(f) }
+                / (kw_generated() kw_always())? kw_as() tok_left_paren() e:expr() tok_right_paren()
+                s:(kw_stored() { true } / kw_virtual() { false })? {
+                    ColumnConstraint::Generated { expr: e, stored: s.unwrap_or(false) }
+                }
+            ) { Some(c) }
+            / kw_null() conflict_clause()? { None }
+
+        rule column_def() -> ColumnDef
+            = !table_constraint_start() n:tok_schema_name() t:type_name()? c:column_constraint()* {
+                ColumnDef {
+                    column_name: n,
+                    type_name: t.unwrap_or_default(),
+                    constraints: c.into_iter().flatten().collect(),
+                }
+            }
+
+        rule table_constraint() -> TableConstraint
+            = constraint_name()? t:(
+                kw_primary() kw_key() tok_left_paren() c:(indexed_column() ++ tok_comma()) tok_right_paren()
+                conflict_clause()? { TableConstraint::PrimaryKey(c) }
+                / kw_unique() tok_left_paren() c:(indexed_column() ++ tok_comma()) tok_right_paren()
+                conflict_clause()? { TableConstraint::Unique(c) }
+                / kw_check() tok_left_paren() e:expr() tok_right_paren() { TableConstraint::Check(e) }
+                / kw_foreign() kw_key() c:column_names() f:foreign_key_clause() {
+                    TableConstraint::ForeignKey { column_names: c, clause: f }
+                }
+            ) { t }
+
+        pub rule create_view_stmt() -> CreateViewStmt
+            = kw_create() (kw_temporary() / kw_temp())? kw_view() (kw_if() kw_not() kw_exists())?
+            (tok_name() ".")? view_name:tok_schema_name() column_names:column_names()?
+            kw_as() select_stmt:select_stmt()
+            {
+                CreateViewStmt {
+                    view_name,
+                    column_names,
+                    select_stmt,
+                }
+            }
+
+        // STRICT tables are read like any other
+        rule table_option() -> bool
+            = kw_without() kw_rowid() { true }
+            / kw_strict() { false }
+
+        pub rule create_table_stmt() -> CreateTableStmt
+            = kw_create() (kw_temporary() / kw_temp())? kw_table() (kw_if() kw_not() kw_exists())?
+            (tok_name() ".")? table_name:tok_schema_name()
+            tok_left_paren() column_defs:(column_def() ++ tok_comma())
+            table_constraints:(tok_comma() t:table_constraint() { t })* tok_right_paren()
+            o:(table_option() ** tok_comma()) tok_semi()? _
+            {
+                CreateTableStmt {
+                    table_name,
+                    column_defs,
+                    table_constraints,
+                    without_rowid: o.contains(&true),
+                }
+            }
+    }
+}
+
+/// A minus in front of a number is part of the literal
+fn negate(expr: Expr) -> Expr {
+    match expr {
+        Expr::Literal(Literal::Integer(i)) if i != i64::MIN => Expr::Literal(Literal::Integer(-i)),
+        Expr::Literal(Literal::Real(r)) => Expr::Literal(Literal::Real(-r)),
+        expr => Expr::Unary(UnaryOp::Neg, Box::new(expr)),
     }
 }
 
@@ -130,20 +694,44 @@ pub fn parse_expr(sql: &str) -> Expr {
     parser::expr(sql).expect("syntax error")
 }
 
+#[cfg(test)]
 pub fn parse_select_stmt(sql: &str) -> SelectStmt {
     parser::select_stmt(sql).expect("syntax error")
 }
 
-pub fn parse_create_index_stmt(sql: &str) -> CreateIndexStmt {
-    parser::create_index_stmt(sql).expect("syntax error")
+pub fn parse_stmt(sql: &str) -> Result<Stmt, String> {
+    parser::stmt(sql).map_err(|err| err.to_string())
+}
+
+pub fn parse_create_index_stmt(sql: &str) -> Result<CreateIndexStmt, String> {
+    parser::create_index_stmt(sql).map_err(|err| err.to_string())
+}
+
+pub fn parse_create_table_stmt(sql: &str) -> Result<CreateTableStmt, String> {
+    parser::create_table_stmt(sql).map_err(|err| err.to_string())
+}
+
+pub fn parse_create_view_stmt(sql: &str) -> Result<CreateViewStmt, String> {
+    parser::create_view_stmt(sql).map_err(|err| err.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::sql::parser::parse_select_stmt;
 
-    use super::super::ast::{BinOp, Expr, Literal};
-    use super::{parse_create_index_stmt, parse_expr};
+    use super::super::ast::{
+        BinOp, CurrentTime, Expr, FromClause, Join, JoinConstraint, JoinKind, LikeOp, Literal,
+        SelectStmt, Stmt, TableRef, UnaryOp,
+    };
+    use super::super::sql::{ColumnConstraint, ForeignKeyClause, TableConstraint};
+    use super::{
+        parse_create_index_stmt, parse_create_table_stmt, parse_create_view_stmt, parse_expr,
+        parse_stmt, parser,
+    };
+
+    fn from_clause(stmt: &SelectStmt) -> &FromClause {
+        stmt.from_clause.as_ref().unwrap()
+    }
 
     #[test]
     fn parser_pass_1() {
@@ -291,6 +879,205 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    #[test]
+    fn predicates() {
+        let id = |id| Box::new(Expr::new_literal(Literal::new_id(id)));
+        let integer = |i| Box::new(Expr::new_literal(Literal::new_integer(i)));
+        let text = |t| Box::new(Expr::new_literal(Literal::new_text(t)));
+        let null = || Expr::new_literal(Literal::Null);
+
+        let got = parse_expr("a IS NOT NULL AND b ISNULL");
+        let want = Expr::new_binary(
+            BinOp::And,
+            Expr::new_binary(BinOp::IsNot, *id("a"), null()),
+            Expr::new_binary(BinOp::Is, *id("b"), null()),
+        );
+        assert_eq!(got, want);
+
+        let got = parse_expr("a NOT BETWEEN 1 AND 2 + 1 AND b");
+        let want = Expr::new_binary(
+            BinOp::And,
+            Expr::Between {
+                expr: id("a"),
+                not: true,
+                low: integer(1),
+                high: Box::new(Expr::new_binary(BinOp::Add, *integer(2), *integer(1))),
+            },
+            *id("b"),
+        );
+        assert_eq!(got, want);
+
+        let got = parse_expr("a in (1, 'x')");
+        let want = Expr::In {
+            expr: id("a"),
+            not: false,
+            list: vec![*integer(1), *text("x")],
+        };
+        assert_eq!(got, want);
+
+        let got = parse_expr("a NOT LIKE 'x!%' ESCAPE '!'");
+        let want = Expr::Like {
+            op: LikeOp::Like,
+            expr: id("a"),
+            not: true,
+            pattern: text("x!%"),
+            escape: Some(text("!")),
+        };
+        assert_eq!(got, want);
+
+        let got = parse_expr("a GLOB 'x*' = 1");
+        let want = Expr::new_binary(
+            BinOp::Eq,
+            Expr::Like {
+                op: LikeOp::Glob,
+                expr: id("a"),
+                not: false,
+                pattern: text("x*"),
+                escape: None,
+            },
+            *integer(1),
+        );
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn operators() {
+        let id = |id| Box::new(Expr::new_literal(Literal::new_id(id)));
+        let integer = |i| Box::new(Expr::new_literal(Literal::new_integer(i)));
+        let text = |t| Box::new(Expr::new_literal(Literal::new_text(t)));
+
+        let got = parse_expr("NOT a = -b || 'x' * 2");
+        let want = Expr::Unary(
+            UnaryOp::Not,
+            Box::new(Expr::new_binary(
+                BinOp::Eq,
+                *id("a"),
+                Expr::new_binary(
+                    BinOp::Mul,
+                    Expr::new_binary(
+                        BinOp::Concat,
+                        Expr::Unary(UnaryOp::Neg, id("b")),
+                        *text("x"),
+                    ),
+                    *integer(2),
+                ),
+            )),
+        );
+        assert_eq!(got, want);
+
+        let got = parse_expr("a | 1 << 2 < ~b & c % 3");
+        let want = Expr::new_binary(
+            BinOp::Lt,
+            Expr::new_binary(
+                BinOp::Shl,
+                Expr::new_binary(BinOp::BitOr, *id("a"), *integer(1)),
+                *integer(2),
+            ),
+            Expr::new_binary(
+                BinOp::BitAnd,
+                Expr::Unary(UnaryOp::BitNot, id("b")),
+                Expr::new_binary(BinOp::Rem, *id("c"), *integer(3)),
+            ),
+        );
+        assert_eq!(got, want);
+
+        let got = parse_expr("CASE a WHEN 1 THEN 'one' WHEN 2 THEN 'two' ELSE 'many' END");
+        let want = Expr::Case {
+            operand: Some(id("a")),
+            when_then: vec![(*integer(1), *text("one")), (*integer(2), *text("two"))],
+            else_result: Some(text("many")),
+        };
+        assert_eq!(got, want);
+
+        let got = parse_expr("case when a > 1 then b end");
+        let want = Expr::Case {
+            operand: None,
+            when_then: vec![(Expr::new_binary(BinOp::Gt, *id("a"), *integer(1)), *id("b"))],
+            else_result: None,
+        };
+        assert_eq!(got, want);
+
+        let got = parse_expr("CAST(a + 1 AS decimal(10, 2))");
+        let want = Expr::Cast {
+            expr: Box::new(Expr::new_binary(BinOp::Add, *id("a"), *integer(1))),
+            type_name: String::from("decimal(10, 2)"),
+        };
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn literals() {
+        let cases = [
+            ("9.99", Literal::Real(9.99)),
+            (".5e1", Literal::Real(5.0)),
+            ("1E-2", Literal::Real(0.01)),
+            ("5.", Literal::Real(5.0)),
+            ("-42", Literal::Integer(-42)),
+            ("- 2.5", Literal::Real(-2.5)),
+            ("0x1F", Literal::Integer(31)),
+            ("0xFFFFFFFFFFFFFFFF", Literal::Integer(-1)),
+            ("9223372036854775808", Literal::Real(9223372036854775808.0)),
+            ("-9223372036854775808", Literal::Integer(i64::MIN)),
+            ("'O''Brien'", Literal::new_text("O'Brien")),
+            ("''''", Literal::new_text("'")),
+            ("X'0aFF'", Literal::Blob(vec![0x0a, 0xff])),
+            ("x''", Literal::Blob(vec![])),
+            ("NULL", Literal::Null),
+            ("true", Literal::Integer(1)),
+            ("FALSE", Literal::Integer(0)),
+            (
+                "CURRENT_TIMESTAMP",
+                Literal::CurrentTime(CurrentTime::Timestamp),
+            ),
+            ("current_date", Literal::CurrentTime(CurrentTime::Date)),
+        ];
+        for (sql, want) in cases {
+            assert_eq!(parse_expr(sql), Expr::new_literal(want), "{sql}");
+        }
+
+        let got = parse_expr("-a");
+        let want = Expr::Unary(
+            UnaryOp::Neg,
+            Box::new(Expr::new_literal(Literal::new_id("a"))),
+        );
+        assert_eq!(got, want);
+
+        for sql in ["X'ABC'", "0x10000000000000000", "x 'AB'"] {
+            assert!(parser::expr(sql).is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn quoted_names() {
+        let got = parse_expr(r#""first name" = [my table].`a``b`"#);
+        let want = Expr::new_binary(
+            BinOp::Eq,
+            Expr::new_literal(Literal::new_id("first name")),
+            Expr::new_literal(Literal::new_id("my table.a`b")),
+        );
+        assert_eq!(got, want);
+
+        let stmt =
+            parse_select_stmt(r#"SELECT "x""y" FROM "Order Items" AS "o i" JOIN t USING ([a b])"#);
+        assert_eq!(
+            stmt.select_clause,
+            [Expr::new_literal(Literal::new_id("x\"y"))]
+        );
+        assert_eq!(from_clause(&stmt).table.table_name, "Order Items");
+        assert_eq!(from_clause(&stmt).table.alias.as_deref(), Some("o i"));
+        assert_eq!(
+            from_clause(&stmt).joins[0].constraint,
+            Some(JoinConstraint::Using(vec![String::from("a b")]))
+        );
+
+        let stmt =
+            parse_create_index_stmt(r#"CREATE INDEX "idx 1" ON "Order Items" ([item name] DESC)"#)
+                .unwrap();
+        assert_eq!(stmt.table_name, "Order Items");
+        assert_eq!(stmt.indexed_columns[0].column_name, "item name");
+        assert!(stmt.indexed_columns[0].desc);
+    }
+
     #[test]
     fn select_stmt() {
         let sql = "SELECT COUNT(*)
@@ -298,8 +1085,8 @@ mod tests {
 
         let stmt = parse_select_stmt(sql);
 
-        assert!(stmt.select_clause.len() == 0);
-        assert_eq!(stmt.from_clause, "apples");
+        assert_eq!(stmt.select_clause, [Expr::new_function("COUNT", vec![])]);
+        assert_eq!(from_clause(&stmt).table.table_name, "apples");
     }
 
     #[test]
@@ -309,8 +1096,8 @@ mod tests {
 
         let stmt = parse_select_stmt(sql);
 
-        assert!(stmt.select_clause.len() == 0);
-        assert_eq!(stmt.from_clause, "apples");
+        assert_eq!(stmt.select_clause, [Expr::new_function("COUNT", vec![])]);
+        assert_eq!(from_clause(&stmt).table.table_name, "apples");
     }
 
     #[test]
@@ -328,7 +1115,7 @@ mod tests {
             ]
         );
 
-        assert_eq!(stmt.from_clause, "apples");
+        assert_eq!(from_clause(&stmt).table.table_name, "apples");
     }
 
     #[test]
@@ -344,7 +1131,7 @@ mod tests {
                 Expr::new_literal(Literal::new_id("color"))
             ]
         );
-        assert_eq!(stmt.from_clause, "apples");
+        assert_eq!(from_clause(&stmt).table.table_name, "apples");
 
         let where_want = Expr::Binary(
             BinOp::Eq,
@@ -359,9 +1146,499 @@ mod tests {
     fn create_index_stmt() {
         let sql = "CREATE INDEX idx_companies_country on companies (country)";
 
-        let create_index_stmt = parse_create_index_stmt(sql);
+        let create_index_stmt = parse_create_index_stmt(sql).unwrap();
 
+        assert!(!create_index_stmt.unique);
         assert_eq!(create_index_stmt.table_name, "companies");
-        assert_eq!(create_index_stmt.indexed_columns, ["country"]);
+        assert_eq!(create_index_stmt.indexed_columns[0].column_name, "country");
+        assert!(!create_index_stmt.indexed_columns[0].desc);
+
+        let sql = "CREATE UNIQUE INDEX i ON t (a COLLATE NOCASE ASC, b COLLATE binary DESC)";
+        let create_index_stmt = parse_create_index_stmt(sql).unwrap();
+        let [a, b] = &create_index_stmt.indexed_columns[..] else {
+            panic!("expected two indexed columns");
+        };
+        assert!(create_index_stmt.unique);
+        assert!(a.is_collated() && !a.desc);
+        assert!(!b.is_collated() && b.desc);
+
+        assert!(parse_create_index_stmt("CREATE INDEX i ON t (lower(a))").is_err());
+        assert!(parse_create_index_stmt("CREATE INDEX i ON t (b) WHERE b > 0").is_err());
+    }
+
+    #[test]
+    fn create_table_stmt() {
+        let sql = "CREATE TABLE oranges
+        (
+                id integer primary key autoincrement,
+                name text,
+                description text
+        )";
+
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        assert_eq!(stmt.table_name, "oranges");
+
+        let column_names: Vec<_> = (stmt.column_defs.iter())
+            .map(|column_def| &column_def.column_name[..])
+            .collect();
+        assert_eq!(column_names, ["id", "name", "description"]);
+
+        let want = [ColumnConstraint::PrimaryKey {
+            desc: false,
+            autoincrement: true,
+        }];
+        assert_eq!(stmt.column_defs[0].constraints, want);
+    }
+
+    #[test]
+    fn create_table_stmt_quoted_names() {
+        let sql = "CREATE TABLE t (\"first name\" TEXT, [order] INTEGER, `a``b` REAL, \"x\"\"y\", untyped)";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        let columns: Vec<_> = (stmt.column_defs.iter())
+            .map(|column_def| (&column_def.column_name[..], &column_def.type_name[..]))
+            .collect();
+
+        assert_eq!(
+            columns,
+            [
+                ("first name", "TEXT"),
+                ("order", "INTEGER"),
+                ("a`b", "REAL"),
+                ("x\"y", ""),
+                ("untyped", ""),
+            ]
+        );
+
+        // fts5 names its shadow tables with 'strings'
+        let sql = "CREATE TABLE 'notes_content'(id INTEGER PRIMARY KEY, 'c''0')";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        assert_eq!(stmt.table_name, "notes_content");
+        assert_eq!(stmt.column_defs[1].column_name, "c'0");
+    }
+
+    #[test]
+    fn create_table_stmt_type_names() {
+        let sql = "CREATE TABLE t (a integer primary key, b VARCHAR NOT NULL, c unsigned big int,
+            d default 0, e DECIMAL(10,2), f double precision check (f > 0), g \"INTEGER\",
+            h 'long' \"text\" (5))";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        let type_names: Vec<_> = (stmt.column_defs.iter())
+            .map(|column_def| &column_def.type_name[..])
+            .collect();
+
+        assert_eq!(
+            type_names,
+            [
+                "integer",
+                "VARCHAR",
+                "unsigned big int",
+                "",
+                "DECIMAL(10,2)",
+                "double precision",
+                "INTEGER",
+                "long text(5)"
+            ]
+        );
+    }
+
+    #[test]
+    fn create_table_stmt_comments() {
+        let sql = "-- the ids
+            CREATE TABLE t ( /* the rowid */ id integer /* alias */ primary key, -- end
+            name text/**/not null) /* open";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        assert_eq!(stmt.column_defs[0].type_name, "integer");
+        assert_eq!(stmt.rowid_column(), Some(0));
+        assert!(stmt.column_defs[1].is_not_null());
+
+        let result = parse_create_table_stmt("CREATE VIRTUAL TABLE notes USING fts5(body)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn create_table_stmt_constraints() {
+        let id = |id| Expr::new_literal(Literal::new_id(id));
+        let integer = |i| Expr::new_literal(Literal::new_integer(i));
+
+        let sql = "CREATE TABLE IF NOT EXISTS main.line_items (
+            order_id INTEGER NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+            line INT CONSTRAINT positive CHECK (line > 0),
+            qty INTEGER DEFAULT -1 NULL,
+            price REAL DEFAULT (1.5 * 2) COLLATE nocase UNIQUE,
+            total REAL GENERATED ALWAYS AS (qty * price) STORED,
+            PRIMARY KEY (order_id, line DESC),
+            CONSTRAINT fk FOREIGN KEY (line) REFERENCES lines,
+            UNIQUE (price) ON CONFLICT REPLACE,
+            CHECK (qty <> 0)
+        );";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        assert_eq!(stmt.table_name, "line_items");
+
+        let constraints: Vec<_> = (stmt.column_defs.iter())
+            .map(|column_def| &column_def.constraints[..])
+            .collect();
+        let orders = ForeignKeyClause {
+            table_name: "orders".into(),
+            column_names: vec!["id".into()],
+        };
+        assert_eq!(
+            constraints[0],
+            [
+                ColumnConstraint::NotNull,
+                ColumnConstraint::References(orders)
+            ]
+        );
+        assert_eq!(
+            constraints[1],
+            [ColumnConstraint::Check(Expr::new_binary(
+                BinOp::Gt,
+                id("line"),
+                integer(0)
+            ))]
+        );
+        assert_eq!(constraints[2], [ColumnConstraint::Default(integer(-1))]);
+        assert_eq!(
+            constraints[3],
+            [
+                ColumnConstraint::Default(Expr::new_binary(
+                    BinOp::Mul,
+                    Expr::new_literal(Literal::Real(1.5)),
+                    integer(2)
+                )),
+                ColumnConstraint::Collate("nocase".into()),
+                ColumnConstraint::Unique,
+            ]
+        );
+        assert_eq!(
+            constraints[4],
+            [ColumnConstraint::Generated {
+                expr: Expr::new_binary(BinOp::Mul, id("qty"), id("price")),
+                stored: true,
+            }]
+        );
+
+        let lines = ForeignKeyClause {
+            table_name: "lines".into(),
+            column_names: vec![],
+        };
+        assert_eq!(
+            stmt.table_constraints,
+            [
+                TableConstraint::PrimaryKey(vec!["order_id".into(), "line".into()]),
+                TableConstraint::ForeignKey {
+                    column_names: vec!["line".into()],
+                    clause: lines,
+                },
+                TableConstraint::Unique(vec!["price".into()]),
+                TableConstraint::Check(Expr::new_binary(BinOp::Neq, id("qty"), integer(0))),
+            ]
+        );
+    }
+
+    #[test]
+    fn create_table_stmt_untyped() {
+        let stmt = parse_create_table_stmt("CREATE TABLE sqlite_sequence(name,seq)").unwrap();
+        let column_names: Vec<_> = (stmt.column_defs.iter())
+            .map(|column_def| &column_def.column_name[..])
+            .collect();
+        assert_eq!(column_names, ["name", "seq"]);
+        assert!(stmt.table_constraints.is_empty());
+    }
+
+    #[test]
+    fn create_view_stmt() {
+        let sql = "CREATE VIEW IF NOT EXISTS \"big spenders\" (who, total) AS
+            SELECT c.name, sum(o.amount) FROM customers c JOIN orders o ON o.customer_id = c.id
+            GROUP BY c.name";
+        let stmt = parse_create_view_stmt(sql).unwrap();
+        assert_eq!(stmt.view_name, "big spenders");
+        assert_eq!(stmt.column_names.unwrap(), ["who", "total"]);
+        assert_eq!(stmt.select_stmt.column_names, ["name", "sum(o.amount)"]);
+    }
+
+    #[test]
+    fn result_column_names() {
+        let stmt = parse_select_stmt("SELECT a, t.b, a + 1, count( * ), b AS \"my b\" FROM t");
+        assert_eq!(stmt.column_names, ["a", "b", "a + 1", "count( * )", "my b"]);
+        assert_eq!(stmt.select_clause.len(), 5);
+    }
+
+    #[test]
+    fn subqueries() {
+        let inner = || Box::new(parse_select_stmt("SELECT max(b) FROM t"));
+        assert_eq!(
+            parse_expr("a = (SELECT max(b) FROM t)"),
+            Expr::new_binary(
+                BinOp::Eq,
+                Expr::new_literal(Literal::new_id("a")),
+                Expr::Subquery(inner())
+            )
+        );
+        assert_eq!(
+            parse_expr("a NOT IN (SELECT max(b) FROM t)"),
+            Expr::InSelect {
+                expr: Box::new(Expr::new_literal(Literal::new_id("a"))),
+                not: true,
+                select: inner(),
+            }
+        );
+        assert_eq!(
+            parse_expr("NOT EXISTS (SELECT max(b) FROM t)"),
+            Expr::Unary(UnaryOp::Not, Box::new(Expr::Exists(inner())))
+        );
+        // A parenthesized expression is not a subquery
+        assert_eq!(
+            parse_expr("(selected)"),
+            Expr::new_literal(Literal::new_id("selected"))
+        );
+
+        let stmt = parse_select_stmt("SELECT n FROM (SELECT max(b) AS n FROM t) AS m WHERE n > 1");
+        assert_eq!(from_clause(&stmt).table.qualifier(), "m");
+        let subquery = from_clause(&stmt).table.subquery.as_ref().unwrap();
+        assert_eq!(subquery.column_names, ["n"]);
+
+        let stmt = parse_select_stmt("SELECT n FROM (SELECT max(b) AS n FROM t) JOIN u");
+        assert_eq!(from_clause(&stmt).table.alias, None);
+        assert_eq!(from_clause(&stmt).joins[0].table.table_name, "u");
+    }
+
+    #[test]
+    fn with_clause() {
+        let sql = "WITH RECURSIVE up(id, parent) AS (
+                SELECT id, parent_id FROM nodes WHERE id = 5
+                UNION ALL SELECT n.id, n.parent_id FROM nodes n JOIN up ON n.id = up.parent LIMIT 10
+            ),
+            roots AS (SELECT id FROM up WHERE parent IS NULL)
+            SELECT id FROM roots";
+        let stmt = parse_select_stmt(sql);
+        assert_eq!(from_clause(&stmt).table.table_name, "roots");
+
+        let [up, roots] = &stmt.with_clause[..] else {
+            panic!("expected two common table expressions");
+        };
+        assert_eq!(up.table_name, "up");
+        assert_eq!(
+            up.column_names,
+            Some(vec![String::from("id"), String::from("parent")])
+        );
+        assert_eq!(up.select_stmt.column_names, ["id", "parent_id"]);
+        let union = up.select_stmt.union.as_ref().unwrap();
+        assert!(union.all);
+        assert_eq!(
+            from_clause(&union.select_stmt).table.alias.as_deref(),
+            Some("n")
+        );
+        assert!(union.select_stmt.limit_clause.is_some());
+
+        assert_eq!(roots.column_names, None);
+        assert_eq!(roots.select_stmt.union, None);
+
+        // UNION ends a table reference rather than being its alias
+        let stmt =
+            parse_select_stmt("WITH t AS (SELECT a FROM x UNION SELECT b FROM y) SELECT a FROM t");
+        let union = stmt.with_clause[0].select_stmt.union.as_ref().unwrap();
+        assert!(!union.all);
+        assert_eq!(
+            from_clause(&stmt.with_clause[0].select_stmt).table.alias,
+            None
+        );
+    }
+
+    #[test]
+    fn insert_stmt() {
+        let sql = "INSERT INTO apples (name, color) VALUES ('Gala', 'Red'), ('Jazz', 'Red');";
+
+        let Stmt::Insert(stmt) = parse_stmt(sql).unwrap() else {
+            panic!();
+        };
+
+        assert_eq!(stmt.table_name, "apples");
+        assert_eq!(stmt.column_names.unwrap(), ["name", "color"]);
+        assert_eq!(stmt.values.len(), 2);
+        assert_eq!(
+            stmt.values[1],
+            [
+                Expr::new_literal(Literal::new_text("Jazz")),
+                Expr::new_literal(Literal::new_text("Red"))
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_stmt_without_column_names() {
+        let sql = "insert into apples values (5, 'Gala', 'Red')";
+
+        let Stmt::Insert(stmt) = parse_stmt(sql).unwrap() else {
+            panic!();
+        };
+
+        assert_eq!(stmt.table_name, "apples");
+        assert!(stmt.column_names.is_none());
+        assert_eq!(stmt.values[0].len(), 3);
+    }
+
+    #[test]
+    fn update_stmt() {
+        let sql = "UPDATE apples SET name = 'Gala', color = 'Red' WHERE id = 1;";
+
+        let Stmt::Update(stmt) = parse_stmt(sql).unwrap() else {
+            panic!();
+        };
+
+        assert_eq!(stmt.table_name, "apples");
+        assert_eq!(stmt.assignments.len(), 2);
+        assert_eq!(stmt.assignments[1].0, "color");
+        assert_eq!(
+            stmt.assignments[1].1,
+            Expr::new_literal(Literal::new_text("Red"))
+        );
+        assert!(stmt.where_clause.is_some());
+    }
+
+    #[test]
+    fn delete_stmt() {
+        let sql = "delete from apples";
+
+        let Stmt::Delete(stmt) = parse_stmt(sql).unwrap() else {
+            panic!();
+        };
+
+        assert_eq!(stmt.table_name, "apples");
+        assert!(stmt.where_clause.is_none());
+    }
+
+    #[test]
+    fn explain_query_plan_stmt() {
+        let Stmt::ExplainQueryPlan(stmt) =
+            parse_stmt("explain query plan SELECT name FROM apples WHERE color = 'Red'").unwrap()
+        else {
+            panic!("expected EXPLAIN QUERY PLAN");
+        };
+        assert_eq!(from_clause(&stmt).table.table_name, "apples");
+    }
+
+    #[test]
+    fn transaction_stmts() {
+        assert!(matches!(parse_stmt("BEGIN").unwrap(), Stmt::Begin));
+        assert!(matches!(
+            parse_stmt("begin transaction;").unwrap(),
+            Stmt::Begin
+        ));
+        assert!(matches!(parse_stmt("COMMIT").unwrap(), Stmt::Commit));
+        assert!(matches!(
+            parse_stmt("END TRANSACTION").unwrap(),
+            Stmt::Commit
+        ));
+        assert!(matches!(parse_stmt("ROLLBACK;").unwrap(), Stmt::Rollback));
+    }
+
+    #[test]
+    fn pragma_stmt() {
+        let Stmt::Pragma(stmt) = parse_stmt("PRAGMA cache_size = -100;").unwrap() else {
+            panic!();
+        };
+
+        assert_eq!(stmt.name, "cache_size");
+        assert_eq!(stmt.value.unwrap(), Literal::new_integer(-100));
+    }
+
+    #[test]
+    fn select_stmt_order_by() {
+        let sql = "SELECT name FROM apples WHERE id > 1 ORDER BY color COLLATE nocase DESC NULLS FIRST, 1";
+        let stmt = parse_select_stmt(sql);
+
+        assert_eq!(stmt.order_by_clause.len(), 2);
+        let term = &stmt.order_by_clause[0];
+        assert_eq!(term.expr, Expr::new_literal(Literal::new_id("color")));
+        assert_eq!(term.collation.as_deref(), Some("nocase"));
+        assert!(term.desc);
+        assert_eq!(term.nulls_first, Some(true));
+
+        let term = &stmt.order_by_clause[1];
+        assert_eq!(term.expr, Expr::new_literal(Literal::new_integer(1)));
+        assert!(!term.desc);
+        assert_eq!(term.nulls_first, None);
+    }
+
+    #[test]
+    fn select_stmt_limit() {
+        let stmt = parse_select_stmt("SELECT name FROM apples LIMIT 10");
+        let limit_clause = stmt.limit_clause.unwrap();
+        assert_eq!(
+            limit_clause.limit,
+            Expr::new_literal(Literal::new_integer(10))
+        );
+        assert_eq!(limit_clause.offset, None);
+
+        let stmt = parse_select_stmt("SELECT name FROM apples ORDER BY name LIMIT 10 OFFSET 5;");
+        let limit_clause = stmt.limit_clause.unwrap();
+        assert_eq!(
+            limit_clause.limit,
+            Expr::new_literal(Literal::new_integer(10))
+        );
+        assert_eq!(
+            limit_clause.offset,
+            Some(Expr::new_literal(Literal::new_integer(5)))
+        );
+
+        // The offset comes first in the comma form
+        let stmt = parse_select_stmt("SELECT name FROM apples LIMIT 5, 10");
+        let limit_clause = stmt.limit_clause.unwrap();
+        assert_eq!(
+            limit_clause.limit,
+            Expr::new_literal(Literal::new_integer(10))
+        );
+        assert_eq!(
+            limit_clause.offset,
+            Some(Expr::new_literal(Literal::new_integer(5)))
+        );
+    }
+
+    #[test]
+    fn select_stmt_join() {
+        let sql = "SELECT a.name, b.name FROM apples AS a
+            JOIN oranges o ON a.id = o.apple_id
+            LEFT OUTER JOIN pears USING (id, name)
+            CROSS JOIN plums, grapes g
+            WHERE a.color = 'Red'";
+        let stmt = parse_select_stmt(sql);
+
+        assert_eq!(
+            stmt.select_clause[0],
+            Expr::new_literal(Literal::new_id("a.name"))
+        );
+        assert_eq!(from_clause(&stmt).table.qualifier(), "a");
+
+        let table_ref = |table_name: &str, alias: Option<&str>| TableRef {
+            table_name: String::from(table_name),
+            alias: alias.map(String::from),
+            subquery: None,
+        };
+        let want = vec![
+            Join {
+                kind: JoinKind::Inner,
+                table: table_ref("oranges", Some("o")),
+                constraint: Some(JoinConstraint::On(parse_expr("a.id = o.apple_id"))),
+            },
+            Join {
+                kind: JoinKind::Left,
+                table: table_ref("pears", None),
+                constraint: Some(JoinConstraint::Using(vec![
+                    String::from("id"),
+                    String::from("name"),
+                ])),
+            },
+            Join {
+                kind: JoinKind::Cross,
+                table: table_ref("plums", None),
+                constraint: None,
+            },
+            Join {
+                kind: JoinKind::Inner,
+                table: table_ref("grapes", Some("g")),
+                constraint: None,
+            },
+        ];
+        assert_eq!(from_clause(&stmt).joins, want);
     }
 }
@@ -1,70 +1,224 @@
-use regex::RegexBuilder;
+use super::{Expr, SelectStmt};
 
-#[derive(Debug)]
-pub struct ColumnDef<'a> {
-    pub column_name: &'a str,
-    pub type_name_and_column_constraint: &'a str,
+#[derive(Debug, PartialEq)]
+pub struct ColumnDef {
+    pub column_name: String,
+    /// The declared type, empty if there is none
+    pub type_name: String,
+    pub constraints: Vec<ColumnConstraint>,
 }
 
-#[derive(Debug)]
-pub struct CreateTableStmt<'a> {
-    pub column_defs: Vec<ColumnDef<'a>>,
+impl ColumnDef {
+    pub fn is_primary_key(&self) -> bool {
+        self.constraints
+            .iter()
+            .any(|constraint| matches!(constraint, ColumnConstraint::PrimaryKey { .. }))
+    }
+
+    pub fn is_not_null(&self) -> bool {
+        self.constraints
+            .iter()
+            .any(|constraint| matches!(constraint, ColumnConstraint::NotNull))
+    }
+
+    /// The expression of a VIRTUAL generated column, which is not stored in the records
+    pub fn virtual_expr(&self) -> Option<&Expr> {
+        self.constraints
+            .iter()
+            .find_map(|constraint| match constraint {
+                ColumnConstraint::Generated {
+                    expr,
+                    stored: false,
+                } => Some(expr),
+                _ => None,
+            })
+    }
+
+    pub fn is_generated(&self) -> bool {
+        self.constraints
+            .iter()
+            .any(|constraint| matches!(constraint, ColumnConstraint::Generated { .. }))
+    }
+
+    pub fn collation_name(&self) -> Option<&str> {
+        self.constraints
+            .iter()
+            .find_map(|constraint| match constraint {
+                ColumnConstraint::Collate(name) => Some(&name[..]),
+                _ => None,
+            })
+    }
+
+    /// Whether the column compares TEXT other than byte by byte
+    pub fn is_collated(&self) -> bool {
+        self.collation_name()
+            .is_some_and(|name| !name.eq_ignore_ascii_case("binary"))
+    }
+
+    pub fn default_value(&self) -> Option<&Expr> {
+        self.constraints
+            .iter()
+            .find_map(|constraint| match constraint {
+                ColumnConstraint::Default(expr) => Some(expr),
+                _ => None,
+            })
+    }
 }
 
-impl<'a> CreateTableStmt<'a> {
-    pub fn parse(sql: &'a str) -> Self {
-        let pattern = r"\((.*?)\)";
-        let re = RegexBuilder::new(pattern)
-            .dot_matches_new_line(true)
-            .build()
-            .unwrap();
-        let caps = re.captures(sql).unwrap();
-        let columns = caps.get(1).unwrap().as_str();
-
-        let column_defs = columns
-            .split(",")
-            .map(|part| {
-                let (prefix, suffix) = part.trim().split_once(" ").unwrap();
-                ColumnDef {
-                    column_name: prefix,
-                    type_name_and_column_constraint: suffix,
-                }
+#[derive(Debug, PartialEq)]
+pub enum ColumnConstraint {
+    PrimaryKey { desc: bool, autoincrement: bool },
+    NotNull,
+    Unique,
+    Check(Expr),
+    Default(Expr),
+    Collate(String),
+    References(ForeignKeyClause),
+    Generated { expr: Expr, stored: bool },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<String>),
+    Unique(Vec<String>),
+    Check(Expr),
+    ForeignKey {
+        column_names: Vec<String>,
+        clause: ForeignKeyClause,
+    },
+}
+
+/// The parent table and columns of a foreign key, its actions are not kept
+#[derive(Debug, PartialEq)]
+pub struct ForeignKeyClause {
+    pub table_name: String,
+    pub column_names: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CreateTableStmt {
+    pub table_name: String,
+    pub column_defs: Vec<ColumnDef>,
+    pub table_constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+}
+
+impl CreateTableStmt {
+    /// Positions of the primary key columns in key order
+    pub fn primary_key(&self) -> Vec<usize> {
+        if let Some(position) = self.column_defs.iter().position(ColumnDef::is_primary_key) {
+            return vec![position];
+        }
+
+        let column_names = self
+            .table_constraints
+            .iter()
+            .find_map(|constraint| match constraint {
+                TableConstraint::PrimaryKey(column_names) => Some(column_names),
+                _ => None,
+            });
+        column_names
+            .into_iter()
+            .flatten()
+            .filter_map(|column_name| {
+                (self.column_defs.iter())
+                    .position(|column_def| column_def.column_name.eq_ignore_ascii_case(column_name))
             })
-            .collect();
+            .collect()
+    }
+
+    /// The column that is an alias for the rowid: the only primary key column if its type is INTEGER.
+    /// `INTEGER PRIMARY KEY DESC` as a column constraint is not an alias, tables WITHOUT ROWID have none.
+    pub fn rowid_column(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+
+        let [position] = self.primary_key()[..] else {
+            return None;
+        };
+        let column_def = &self.column_defs[position];
+        let is_desc = column_def.constraints.iter().any(|constraint| {
+            matches!(constraint, ColumnConstraint::PrimaryKey { desc: true, .. })
+        });
+        (column_def.type_name.eq_ignore_ascii_case("integer") && !is_desc).then_some(position)
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateViewStmt {
+    pub view_name: String,
+    pub column_names: Option<Vec<String>>,
+    pub select_stmt: SelectStmt,
+}
 
-        Self { column_defs }
+#[derive(Debug)]
+pub struct IndexedColumn {
+    pub column_name: String,
+    pub collation: Option<String>,
+    pub desc: bool,
+}
+
+impl IndexedColumn {
+    /// Whether the index orders the column other than byte by byte
+    pub fn is_collated(&self) -> bool {
+        (self.collation.as_ref()).is_some_and(|name| !name.eq_ignore_ascii_case("binary"))
     }
 }
 
 #[derive(Debug)]
-pub struct CreateIndexStmt<'a> {
-    pub table_name: &'a str,
-    pub indexed_columns: Vec<&'a str>,
+pub struct CreateIndexStmt {
+    pub unique: bool,
+    pub table_name: String,
+    pub indexed_columns: Vec<IndexedColumn>,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CreateTableStmt;
+    use crate::sql::parse_create_table_stmt;
 
     #[test]
-    fn create_table_stmt() {
-        let sql = "CREATE TABLE oranges
-        (
-                id integer primary key autoincrement,
-                name text,
-                description text
-        )";
-
-        let stmt = CreateTableStmt::parse(sql);
-        let column_names = stmt
-            .column_defs
-            .into_iter()
-            .map(|column_def| column_def.column_name);
+    fn rowid_column() {
+        let rowid_column = |sql| parse_create_table_stmt(sql).unwrap().rowid_column();
 
-        let want_arr = ["id", "name", "description"];
+        assert_eq!(
+            rowid_column("CREATE TABLE t (a text, id INTEGER PRIMARY KEY)"),
+            Some(1)
+        );
+        assert_eq!(
+            rowid_column("CREATE TABLE t (id integer primary key asc)"),
+            Some(0)
+        );
+        assert_eq!(
+            rowid_column("CREATE TABLE t (id INTEGER PRIMARY KEY DESC)"),
+            None
+        );
+        assert_eq!(
+            rowid_column("CREATE TABLE t (\"id\" \"INTEGER\" PRIMARY KEY)"),
+            Some(0)
+        );
+        assert_eq!(rowid_column("CREATE TABLE t (id INT PRIMARY KEY)"), None);
+        assert_eq!(rowid_column("CREATE TABLE t (id INTEGER)"), None);
+        assert_eq!(
+            rowid_column("CREATE TABLE t (a, id integer, PRIMARY KEY (ID DESC))"),
+            Some(1)
+        );
+        assert_eq!(
+            rowid_column("CREATE TABLE t (a integer, b integer, PRIMARY KEY (a, b))"),
+            None
+        );
+        assert_eq!(
+            rowid_column("CREATE TABLE t (id INTEGER PRIMARY KEY) WITHOUT ROWID"),
+            None
+        );
+    }
 
-        for (got, want) in column_names.zip(want_arr) {
-            assert_eq!(got, want);
-        }
+    #[test]
+    fn primary_key() {
+        let sql =
+            "CREATE TABLE t (a text, b integer, c real, PRIMARY KEY (c, a)) WITHOUT ROWID, STRICT";
+        let stmt = parse_create_table_stmt(sql).unwrap();
+        assert!(stmt.without_rowid);
+        assert_eq!(stmt.primary_key(), [2, 0]);
     }
 }
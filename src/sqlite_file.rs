@@ -1,33 +1,277 @@
+#[cfg(unix)]
+use std::rc::Rc;
 use std::{
-    fs::File,
-    io::{Read, Seek, SeekFrom},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
 };
 
+#[cfg(unix)]
+use crate::mmap::Mmap;
+use crate::{
+    journal::{journal_path, replay, Journal, StatementJournal},
+    wal::{wal_path, Wal},
+};
+
+/// How pages are read from the database file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileBackend {
+    /// Every page is read into a freshly allocated buffer
+    Pread,
+    /// The file is memory-mapped and pages borrow their bytes from the mapping.
+    /// Pages are read as with `Pread` during write transactions and on platforms other than unix.
+    Mmap,
+}
+
+/// The bytes of a page, either owned or borrowed from a memory mapping.
+/// The mapping is reference counted, so a borrowed page can outlive a remap of the file.
+#[derive(Debug)]
+pub enum PageBytes {
+    Owned(Vec<u8>),
+    #[cfg(unix)]
+    Mapped {
+        mmap: Rc<Mmap>,
+        start: usize,
+        len: usize,
+    },
+}
+
+impl Deref for PageBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            PageBytes::Owned(bytes) => bytes,
+            #[cfg(unix)]
+            PageBytes::Mapped { mmap, start, len } => &mmap[*start..*start + *len],
+        }
+    }
+}
+
+/// The mapping is read-only, a mapped page is copied before it is modified.
+impl DerefMut for PageBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        #[cfg(unix)]
+        if let PageBytes::Mapped { .. } = self {
+            *self = PageBytes::Owned(self.to_vec());
+        }
+
+        match self {
+            PageBytes::Owned(bytes) => bytes,
+            #[cfg(unix)]
+            PageBytes::Mapped { .. } => unreachable!(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for PageBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        PageBytes::Owned(bytes)
+    }
+}
+
 #[derive(Debug)]
 pub struct SQLiteFile {
     file: File,
+    // Set for the mmap backend outside write transactions, None for an empty file
+    #[cfg(unix)]
+    mmap: Option<Rc<Mmap>>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    backend: FileBackend,
+    // Set between begin_transaction and commit or rollback, the file is not mapped then
+    writing: bool,
+    // None if the file was not opened by path, transactions are not journaled then
+    journal_path: Option<PathBuf>,
+    journal: Option<Journal>,
+    // Set while a statement runs within a transaction
+    statement: Option<StatementJournal>,
+    // Committed pages not yet checkpointed into the database file
+    wal: Option<Wal>,
 }
 
 impl SQLiteFile {
     pub fn new(file: File) -> Self {
-        Self { file }
+        Self {
+            file,
+            #[cfg(unix)]
+            mmap: None,
+            backend: FileBackend::Pread,
+            writing: false,
+            journal_path: None,
+            journal: None,
+            statement: None,
+            wal: None,
+        }
+    }
+
+    /// Opens the database file and rolls back a hot journal left behind by a crash.
+    /// Pages are read from the write-ahead log if the database is in WAL mode.
+    /// Falls back to read-only access, write statements fail in that case.
+    pub fn open(path: &Path, backend: FileBackend) -> Self {
+        let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => file,
+            Err(_) => File::open(path).unwrap(),
+        };
+
+        let journal_path = journal_path(path);
+        replay(&mut file, &journal_path);
+
+        // The file is mapped when the first page is loaded
+        Self {
+            file,
+            #[cfg(unix)]
+            mmap: None,
+            backend,
+            writing: false,
+            journal_path: Some(journal_path),
+            journal: None,
+            statement: None,
+            wal: Wal::open(&wal_path(path)),
+        }
+    }
+
+    /// Unmaps the file until the transaction ends, the caller must drop the pages
+    /// borrowing from the mapping before writing, as the writes would change their bytes.
+    pub fn begin_transaction(&mut self, page_size: usize) -> Result<(), String> {
+        if self.wal.is_some() {
+            return Err(String::from("cannot write to a database in WAL mode"));
+        }
+
+        self.writing = true;
+        #[cfg(unix)]
+        {
+            self.mmap = None;
+        }
+
+        if let Some(path) = &self.journal_path {
+            let db_page_cnt = (self.file_size() / page_size as u64) as u32;
+            self.journal = Some(Journal::create(path.clone(), page_size, db_page_cnt));
+        }
+        Ok(())
+    }
+
+    pub fn commit(&mut self) {
+        self.writing = false;
+        if let Some(journal) = self.journal.take() {
+            self.file.sync_all().unwrap();
+            journal.commit();
+        }
+    }
+
+    pub fn rollback(&mut self) {
+        self.writing = false;
+        self.statement = None;
+        if let Some(journal) = self.journal.take() {
+            journal.rollback(&mut self.file);
+        }
+    }
+
+    /// Starts saving the pages the next statement modifies, so that it can be undone alone.
+    pub fn begin_statement(&mut self, page_size: usize) {
+        let db_page_cnt = (self.file_size() / page_size as u64) as u32;
+        self.statement = Some(StatementJournal::new(page_size, db_page_cnt));
+    }
+
+    pub fn commit_statement(&mut self) {
+        self.statement = None;
+    }
+
+    pub fn rollback_statement(&mut self) {
+        if let Some(statement) = self.statement.take() {
+            statement.rollback(&mut self.file);
+        }
     }
 
     pub fn load_db_header(&mut self) -> [u8; 100] {
+        if let Some(page) = self.wal.as_mut().and_then(|wal| wal.load_page(1)) {
+            return page[..100].try_into().unwrap();
+        }
+
         self.file.seek(SeekFrom::Start(0)).unwrap();
         let mut header = [0; 100];
         self.file.read_exact(&mut header).unwrap();
         header
     }
 
-    pub fn load_page(&mut self, page_no: u32, page_size: usize) -> Vec<u8> {
+    pub fn load_page(&mut self, page_no: u32, page_size: usize) -> PageBytes {
+        if let Some(page) = self.wal.as_mut().and_then(|wal| wal.load_page(page_no)) {
+            return PageBytes::Owned(page);
+        }
+
         let page_no = page_no as usize;
         let start = (page_no - 1) * page_size;
 
+        #[cfg(unix)]
+        if self.backend == FileBackend::Mmap && !self.writing {
+            let mapped_len = self.mmap.as_ref().map_or(0, |mmap| mmap.len());
+            if start + page_size > mapped_len {
+                // The file may have grown since it was mapped
+                self.remap();
+            }
+
+            if let Some(mmap) = &self.mmap {
+                if start + page_size <= mmap.len() {
+                    return PageBytes::Mapped {
+                        mmap: Rc::clone(mmap),
+                        start,
+                        len: page_size,
+                    };
+                }
+            }
+        }
+
         self.file.seek(SeekFrom::Start(start as u64)).unwrap();
         let mut page = vec![0; page_size];
         self.file.read_exact(&mut page).unwrap();
 
-        page
+        PageBytes::Owned(page)
+    }
+
+    pub fn write_db_header(&mut self, header: &[u8; 100]) {
+        self.journal_page(1);
+        self.file.seek(SeekFrom::Start(0)).unwrap();
+        self.file.write_all(header).unwrap();
+    }
+
+    pub fn write_page(&mut self, page_no: u32, page: &[u8]) {
+        self.journal_page(page_no);
+        let start = (page_no as usize - 1) * page.len();
+
+        self.file.seek(SeekFrom::Start(start as u64)).unwrap();
+        self.file.write_all(page).unwrap();
+    }
+
+    pub fn file_size(&self) -> u64 {
+        match &self.wal {
+            Some(wal) => wal.db_page_cnt() as u64 * wal.page_size() as u64,
+            None => self.file.metadata().unwrap().len(),
+        }
+    }
+
+    #[cfg(unix)]
+    fn remap(&mut self) {
+        self.mmap = Mmap::map(&self.file).unwrap().map(Rc::new);
+    }
+
+    /// Saves the original content of a page before it is modified for the first time
+    /// in the transaction and in the statement.
+    fn journal_page(&mut self, page_no: u32) {
+        let journal = (self.journal.as_ref()).filter(|journal| journal.needs(page_no));
+        let statement = (self.statement.as_ref()).filter(|statement| statement.needs(page_no));
+        let Some(page_size) = (journal.map(Journal::page_size))
+            .or_else(|| statement.map(StatementJournal::page_size))
+        else {
+            return;
+        };
+        let (is_journaled, is_statement_journaled) = (journal.is_some(), statement.is_some());
+
+        let page = self.load_page(page_no, page_size);
+        if is_journaled {
+            self.journal.as_mut().unwrap().append(page_no, &page);
+        }
+        if is_statement_journaled {
+            self.statement.as_mut().unwrap().append(page_no, &page);
+        }
     }
 }
@@ -0,0 +1,363 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use crate::engine::{Record, Value};
+
+use super::{
+    btree_writer::compare_keys,
+    cell::{IndexInteriorCell, IndexLeafCell, TableInteriorCell, TableLeafCell},
+    page::{PageType, RawPage},
+    sqlite_storage::SQLiteStorage,
+};
+
+/// Walks the entries of a table or index b-tree in key order, one page at a time.
+///
+/// Table entries are only stored on leaf pages,
+/// index entries also on interior pages, between the subtrees of their neighbouring children.
+pub struct BTreeCursor<'a> {
+    storage: &'a mut SQLiteStorage,
+    rootpage: u32,
+    // The pages from the root down to the current entry, each with the index of a cell.
+    // On an interior page it is the child being visited (the cell count stands for the right-most pointer),
+    // if the interior page is on top of the stack the cursor is at that cell's index entry.
+    stack: Vec<(Rc<RawPage>, usize)>,
+}
+
+impl<'a> BTreeCursor<'a> {
+    pub fn new(storage: &'a mut SQLiteStorage, rootpage: u32) -> Self {
+        Self {
+            storage,
+            rootpage,
+            stack: vec![],
+        }
+    }
+
+    /// Whether the cursor is at an entry
+    pub fn is_valid(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    /// Moves to the first entry, returns false if the b-tree is empty.
+    pub fn first(&mut self) -> bool {
+        self.stack.clear();
+        self.descend_leftmost(self.rootpage)
+    }
+
+    /// Moves to the next entry, returns false if there is none.
+    pub fn next(&mut self) -> bool {
+        let Some((page, idx)) = self.stack.last_mut() else {
+            return false;
+        };
+
+        *idx += 1;
+        if !page.page_header.page_type.is_interior() {
+            if *idx < page.cell_ptr_arr.len() {
+                return true;
+            }
+            return self.ascend();
+        }
+
+        // At an index entry on an interior page, the next entries are in the subtree to its right
+        let child = get_child(page, *idx);
+        self.descend_leftmost(child)
+    }
+
+    /// Moves to the entry with the smallest rowid not less than `rowid` in a table b-tree,
+    /// returns false if there is none.
+    pub fn seek_rowid(&mut self, rowid: i64) -> bool {
+        self.stack.clear();
+        let mut page_no = self.rootpage;
+
+        loop {
+            let page = self.storage.get_raw_page(page_no);
+
+            match page.page_header.page_type {
+                PageType::TableInterior => {
+                    let cnt = page.cell_ptr_arr.len();
+                    let idx = (0..cnt)
+                        .find(|idx| rowid <= page.get_cell::<TableInteriorCell>(*idx).key)
+                        .unwrap_or(cnt);
+
+                    page_no = get_child(&page, idx);
+                    self.stack.push((page, idx));
+                }
+                PageType::TableLeaf => {
+                    let idx = page
+                        .get_cells::<TableLeafCell>()
+                        .position(|cell| rowid <= cell.rowid)
+                        .unwrap_or(page.cell_ptr_arr.len());
+
+                    let exhausted = idx == page.cell_ptr_arr.len();
+                    self.stack.push((page, idx));
+                    return !exhausted || self.ascend();
+                }
+                _ => panic!("internal error: not a table b-tree"),
+            }
+        }
+    }
+
+    /// Moves to the first entry of an index b-tree whose leading values are not less than `key`,
+    /// returns false if there is none.
+    pub fn seek_key(&mut self, key: &[Value]) -> bool {
+        self.stack.clear();
+        let mut page_no = self.rootpage;
+
+        loop {
+            let page = self.storage.get_raw_page(page_no);
+            let is_interior = page.page_header.page_type.is_interior();
+
+            let cnt = page.cell_ptr_arr.len();
+            let idx = (0..cnt)
+                .find(|idx| {
+                    let values = self.parse_key(&page, *idx);
+                    let prefix = &values[..key.len().min(values.len())];
+                    compare_keys(prefix, key) != Ordering::Less
+                })
+                .unwrap_or(cnt);
+
+            if is_interior {
+                // Equal keys may also be in the subtree left of the entry
+                page_no = get_child(&page, idx);
+                self.stack.push((page, idx));
+            } else {
+                self.stack.push((page, idx));
+                return idx < cnt || self.ascend();
+            }
+        }
+    }
+
+    /// The rowid of the current entry, for index entries the last value of the key
+    pub fn rowid(&mut self) -> i64 {
+        let (page, idx) = self.stack.last().unwrap();
+
+        match page.page_header.page_type {
+            PageType::TableLeaf => page.get_cell::<TableLeafCell>(*idx).rowid,
+            _ => {
+                let (page, idx) = (Rc::clone(page), *idx);
+                i64::from(self.parse_key(&page, idx).last().unwrap())
+            }
+        }
+    }
+
+    /// The current entry: a table row or an index key with its rowid
+    pub fn record(&mut self) -> Record {
+        let (page, idx) = self.stack.last().unwrap();
+        let (page, idx) = (Rc::clone(page), *idx);
+
+        match page.page_header.page_type {
+            PageType::TableLeaf => {
+                let cell = page.get_cell::<TableLeafCell>(idx);
+                cell.parse_record(&mut self.storage.overflow_reader())
+            }
+            _ => {
+                let values = self.parse_key(&page, idx);
+                let rowid = i64::from(values.last().unwrap());
+                Record::new(rowid, values)
+            }
+        }
+    }
+
+    /// The values of the current entry of an index b-tree
+    pub fn key(&mut self) -> Vec<Value> {
+        let (page, idx) = self.stack.last().unwrap();
+        let (page, idx) = (Rc::clone(page), *idx);
+        self.parse_key(&page, idx)
+    }
+
+    fn parse_key(&mut self, page: &RawPage, idx: usize) -> Vec<Value> {
+        let overflow_reader = &mut self.storage.overflow_reader();
+
+        match page.page_header.page_type {
+            PageType::IndexInterior => page
+                .get_cell::<IndexInteriorCell>(idx)
+                .parse_record(overflow_reader),
+            PageType::IndexLeaf => page
+                .get_cell::<IndexLeafCell>(idx)
+                .parse_record(overflow_reader),
+            _ => panic!("internal error: not an index b-tree"),
+        }
+    }
+
+    /// Pushes the pages down to the left-most leaf of the subtree
+    fn descend_leftmost(&mut self, mut page_no: u32) -> bool {
+        loop {
+            let page = self.storage.get_raw_page(page_no);
+            let is_interior = page.page_header.page_type.is_interior();
+            let is_empty = page.cell_ptr_arr.is_empty();
+
+            if is_interior {
+                page_no = get_child(&page, 0);
+            }
+            self.stack.push((page, 0));
+
+            if !is_interior {
+                // Only a root leaf page can be empty
+                return !is_empty || self.ascend();
+            }
+        }
+    }
+
+    /// Pops an exhausted page and moves on to the next entry above it
+    fn ascend(&mut self) -> bool {
+        loop {
+            self.stack.pop();
+            let Some((page, idx)) = self.stack.last_mut() else {
+                return false;
+            };
+
+            let cnt = page.cell_ptr_arr.len();
+            match page.page_header.page_type {
+                // Returning from the subtree left of an index entry
+                PageType::IndexInterior if *idx < cnt => return true,
+                PageType::TableInterior if *idx < cnt => {
+                    *idx += 1;
+                    let child = get_child(page, *idx);
+                    return self.descend_leftmost(child);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The page number of the child left of cell `idx`, the right-most pointer for `idx == cell count`
+pub(super) fn get_child(page: &RawPage, idx: usize) -> u32 {
+    if idx == page.cell_ptr_arr.len() {
+        return page.page_header.right_most_ptr.unwrap();
+    }
+
+    match page.page_header.page_type {
+        PageType::TableInterior => page.get_cell::<TableInteriorCell>(idx).left_child_ptr,
+        PageType::IndexInterior => page.get_cell::<IndexInteriorCell>(idx).left_child_ptr,
+        _ => panic!("internal error: leaf pages have no children"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, fs, path::PathBuf, slice};
+
+    use crate::{
+        engine::{new_engine, Storage, Value},
+        sqlite_file::{FileBackend, SQLiteFile},
+        sqlite_storage::{btree_writer::compare_keys, sqlite_storage::SQLiteStorage},
+        test_util::copy_db,
+    };
+
+    /// Grows the mountains table and its index to several levels of interior pages.
+    fn build_storage(test_name: &str) -> (SQLiteStorage, PathBuf) {
+        let path = copy_db("dbs/mountains.db", test_name);
+        let mut engine = new_engine(path.to_str().unwrap());
+        engine.exec_sql("BEGIN").unwrap();
+        for i in 0..500 {
+            let sql = format!(
+                "INSERT INTO mountains (name, height, country, range) VALUES ('Peak {i}', {i}, 'Country {}', 'Range {i}')",
+                i % 10
+            );
+            engine.exec_sql(&sql).unwrap();
+        }
+        engine.exec_sql("COMMIT").unwrap();
+
+        let storage = SQLiteStorage::new(SQLiteFile::open(&path, FileBackend::Pread));
+        (storage, path)
+    }
+
+    fn get_rootpage(storage: &mut SQLiteStorage, name: &str) -> u32 {
+        storage
+            .get_schema()
+            .get_sqlite_object(name)
+            .unwrap()
+            .rootpage
+    }
+
+    #[test]
+    fn scan_table_in_rowid_order() {
+        let (mut storage, path) = build_storage("cursor_scan_table");
+        let rootpage = get_rootpage(&mut storage, "mountains");
+        let want: Vec<_> = storage
+            .search_table(rootpage, None)
+            .into_iter()
+            .map(|record| (record.rowid, record.values))
+            .collect();
+
+        let mut cursor = storage.cursor(rootpage);
+        let mut got = vec![];
+        let mut is_valid = cursor.first();
+        while is_valid {
+            let record = cursor.record();
+            assert_eq!(cursor.rowid(), record.rowid);
+            got.push((record.rowid, record.values));
+            is_valid = cursor.next();
+        }
+
+        assert_eq!(got.len(), 515);
+        assert_eq!(got, want);
+        assert!(!cursor.is_valid());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn scan_index_in_key_order() {
+        let (mut storage, path) = build_storage("cursor_scan_index");
+        let rootpage = get_rootpage(&mut storage, "idx_mountains_country");
+
+        let mut cursor = storage.cursor(rootpage);
+        let mut keys: Vec<Vec<Value>> = vec![];
+        let mut is_valid = cursor.first();
+        while is_valid {
+            let record = cursor.record();
+            assert_eq!(cursor.rowid(), record.rowid);
+            keys.push(record.values);
+            is_valid = cursor.next();
+        }
+
+        assert_eq!(keys.len(), 515);
+        for pair in keys.windows(2) {
+            assert_eq!(compare_keys(&pair[0], &pair[1]), Ordering::Less);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn seek_rowid() {
+        let (mut storage, path) = build_storage("cursor_seek_rowid");
+        let rootpage = get_rootpage(&mut storage, "mountains");
+        let mut cursor = storage.cursor(rootpage);
+
+        assert!(cursor.seek_rowid(300));
+        assert_eq!(cursor.rowid(), 300);
+        assert!(cursor.next());
+        assert_eq!(cursor.rowid(), 301);
+
+        assert!(cursor.seek_rowid(-5));
+        assert_eq!(cursor.rowid(), 1);
+
+        assert!(!cursor.seek_rowid(516));
+        assert!(!cursor.is_valid());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn seek_key() {
+        let (mut storage, path) = build_storage("cursor_seek_key");
+        let rootpage = get_rootpage(&mut storage, "idx_mountains_country");
+        let mut cursor = storage.cursor(rootpage);
+
+        let country = Value::Text(String::from("Country 7"));
+        let mut rowids = vec![];
+        let mut is_valid = cursor.seek_key(slice::from_ref(&country));
+        while is_valid && cursor.record().values[0] == country {
+            rowids.push(cursor.rowid());
+            is_valid = cursor.next();
+        }
+
+        let want: Vec<_> = (7..500).step_by(10).map(|i| i + 16).collect();
+        assert_eq!(rowids, want);
+
+        assert!(!cursor.seek_key(&[Value::Text(String::from("Zimbabwe"))]));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,582 @@
+use std::cmp::Ordering;
+
+use crate::{
+    bytes::varint::encode_varint,
+    engine::{Record, Value},
+    sqlite_file::SQLiteFile,
+};
+
+use super::{
+    cell::{
+        index_max_local, local_payload_size, table_leaf_max_local, IndexInteriorCell,
+        IndexLeafCell, TableInteriorCell, TableLeafCell,
+    },
+    db_header::DBHeader,
+    freelist::Freelist,
+    overflow::OverflowReader,
+    page::{PageBuilder, PageType, RawPage},
+    record::serialize_record,
+};
+
+/// The cells of a page together with its right-most pointer
+type Group = (Vec<Vec<u8>>, Option<u32>);
+
+/// An interior page on the way from the root to the page being modified
+struct Ancestor {
+    page: PageBuilder,
+    // Index of the child pointer that was followed
+    idx: usize,
+    dirty: bool,
+}
+
+/// Inserts and deletes cells in table and index b-trees,
+/// splitting pages that overflow and merging pages that become empty.
+///
+/// Pages are written through to the file as soon as they are modified.
+/// Pages are allocated from and released to the freelist,
+/// the caller is responsible for persisting the updated `DBHeader`.
+pub struct BTreeWriter<'a> {
+    sqlite_file: &'a mut SQLiteFile,
+    db_header: &'a mut DBHeader,
+}
+
+impl<'a> BTreeWriter<'a> {
+    pub fn new(sqlite_file: &'a mut SQLiteFile, db_header: &'a mut DBHeader) -> Self {
+        Self {
+            sqlite_file,
+            db_header,
+        }
+    }
+
+    /// Inserts a record into the table b-tree rooted at `rootpage`.
+    /// A record with the same rowid is overwritten, in place if possible.
+    pub fn insert_record(&mut self, rootpage: u32, record: &Record) {
+        let payload = serialize_record(&record.values);
+        let max_local = table_leaf_max_local(self.db_header.usable_size());
+        let stored_payload = self.write_payload(&payload, max_local);
+        let cell = TableLeafCell::build(record.rowid, payload.len(), &stored_payload);
+
+        let (path, mut raw_page, position) = self.find_table_leaf(rootpage, record.rowid);
+        let page_no = raw_page.page_no;
+
+        match position {
+            Ok(idx) => {
+                let old_cell = raw_page.get_cells::<TableLeafCell>().nth(idx).unwrap();
+                if let Some(first_overflow_page) = old_cell.first_overflow_page() {
+                    self.free_overflow_pages(first_overflow_page);
+                }
+
+                if raw_page.replace_cell(idx, &cell) {
+                    self.sqlite_file.write_page(page_no, &raw_page.bytes);
+                } else {
+                    let mut page = PageBuilder::from_raw_page(page_no, &raw_page);
+                    page.cells[idx] = cell;
+                    self.balance(path, page);
+                }
+            }
+            Err(idx) => {
+                let mut page = PageBuilder::from_raw_page(page_no, &raw_page);
+                page.cells.insert(idx, cell);
+                self.balance(path, page);
+            }
+        }
+    }
+
+    /// Deletes the record with the given rowid from the table b-tree rooted at `rootpage`.
+    /// Returns false if there is no such record.
+    pub fn delete_record(&mut self, rootpage: u32, rowid: i64) -> bool {
+        let (path, raw_page, position) = self.find_table_leaf(rootpage, rowid);
+        let Ok(idx) = position else {
+            return false;
+        };
+
+        let cell = raw_page.get_cells::<TableLeafCell>().nth(idx).unwrap();
+        if let Some(first_overflow_page) = cell.first_overflow_page() {
+            self.free_overflow_pages(first_overflow_page);
+        }
+
+        self.remove_leaf_cell(path, raw_page, idx);
+        true
+    }
+
+    /// Inserts a key into the index b-tree rooted at `rootpage`.
+    /// The last value of the key is the rowid of the indexed record.
+    pub fn insert_index_key(&mut self, rootpage: u32, key: &[Value]) {
+        let payload = serialize_record(key);
+        let max_local = index_max_local(self.db_header.usable_size());
+        let stored_payload = self.write_payload(&payload, max_local);
+        let cell = IndexLeafCell::build(payload.len(), &stored_payload);
+
+        let (path, raw_page, position) = self.find_index_key(rootpage, key);
+        let idx = match position {
+            Ok(_) => panic!("internal error: duplicate index key"),
+            Err(idx) => idx,
+        };
+
+        let mut page = PageBuilder::from_raw_page(raw_page.page_no, &raw_page);
+        page.cells.insert(idx, cell);
+        self.balance(path, page);
+    }
+
+    /// Deletes a key from the index b-tree rooted at `rootpage`.
+    /// Returns false if there is no such key.
+    pub fn delete_index_key(&mut self, rootpage: u32, key: &[Value]) -> bool {
+        let (mut path, raw_page, position) = self.find_index_key(rootpage, key);
+        let Ok(idx) = position else {
+            return false;
+        };
+
+        if !raw_page.page_header.page_type.is_interior() {
+            let cell = raw_page.get_cells::<IndexLeafCell>().nth(idx).unwrap();
+            if let Some(first_overflow_page) = cell.first_overflow_page() {
+                self.free_overflow_pages(first_overflow_page);
+            }
+
+            self.remove_leaf_cell(path, raw_page, idx);
+            return true;
+        }
+
+        let cell = raw_page.get_cells::<IndexInteriorCell>().nth(idx).unwrap();
+        if let Some(first_overflow_page) = cell.first_overflow_page() {
+            self.free_overflow_pages(first_overflow_page);
+        }
+
+        // The key of an interior cell is replaced by its predecessor,
+        // the largest key of the left subtree, which is removed from its leaf page instead.
+        let mut page_no = cell.left_child_ptr;
+        let page = PageBuilder::from_raw_page(raw_page.page_no, &raw_page);
+        path.push(Ancestor {
+            page,
+            idx,
+            dirty: true,
+        });
+
+        let mut leaf = loop {
+            let page = PageBuilder::from_raw_page(page_no, &self.load_page(page_no));
+            if !page.page_type.is_interior() {
+                break page;
+            }
+
+            page_no = page.right_most_ptr.unwrap();
+            path.push(Ancestor {
+                idx: page.cells.len(),
+                page,
+                dirty: false,
+            });
+        };
+
+        let predecessor = leaf.cells.pop().unwrap();
+        let interior = path
+            .iter_mut()
+            .rev()
+            .find(|ancestor| ancestor.dirty)
+            .unwrap();
+        let left_child_ptr = &interior.page.cells[idx][..4];
+        interior.page.cells[idx] = [left_child_ptr, &predecessor].concat();
+
+        self.balance(path, leaf);
+        true
+    }
+
+    /// Descends from `rootpage` to the leaf page that holds or would hold `rowid`.
+    /// Returns the position of the rowid on the leaf page as `binary_search` does.
+    fn find_table_leaf(
+        &mut self,
+        rootpage: u32,
+        rowid: i64,
+    ) -> (Vec<Ancestor>, RawPage, Result<usize, usize>) {
+        let mut path = vec![];
+        let mut page_no = rootpage;
+
+        loop {
+            let raw_page = self.load_page(page_no);
+
+            match raw_page.page_header.page_type {
+                PageType::TableInterior => {
+                    let cells: Vec<_> = raw_page.get_cells::<TableInteriorCell>().collect();
+                    let idx = cells
+                        .iter()
+                        .position(|cell| rowid <= cell.key)
+                        .unwrap_or(cells.len());
+
+                    page_no = match cells.get(idx) {
+                        Some(cell) => cell.left_child_ptr,
+                        None => raw_page.page_header.right_most_ptr.unwrap(),
+                    };
+                    path.push(Ancestor {
+                        page: PageBuilder::from_raw_page(raw_page.page_no, &raw_page),
+                        idx,
+                        dirty: false,
+                    });
+                }
+                PageType::TableLeaf => {
+                    let rowids: Vec<_> = raw_page
+                        .get_cells::<TableLeafCell>()
+                        .map(|cell| cell.rowid)
+                        .collect();
+
+                    let position = rowids.binary_search(&rowid);
+                    return (path, raw_page, position);
+                }
+                _ => panic!("internal error"),
+            }
+        }
+    }
+
+    /// Descends from `rootpage` until `key` is found or the leaf page where it would be inserted is reached.
+    /// Returns the position of the key on the last page as `binary_search` does.
+    fn find_index_key(
+        &mut self,
+        rootpage: u32,
+        key: &[Value],
+    ) -> (Vec<Ancestor>, RawPage, Result<usize, usize>) {
+        let mut path = vec![];
+        let mut page_no = rootpage;
+
+        loop {
+            let raw_page = self.load_page(page_no);
+            let mut overflow_reader = OverflowReader::new(self.sqlite_file, self.db_header);
+
+            match raw_page.page_header.page_type {
+                PageType::IndexInterior => {
+                    let cells: Vec<_> = raw_page.get_cells::<IndexInteriorCell>().collect();
+                    let keys = cells
+                        .iter()
+                        .map(|cell| cell.parse_record(&mut overflow_reader));
+                    let position = search_keys(keys, key);
+
+                    let idx = match position {
+                        Ok(_) => return (path, raw_page, position),
+                        Err(idx) => idx,
+                    };
+
+                    page_no = match cells.get(idx) {
+                        Some(cell) => cell.left_child_ptr,
+                        None => raw_page.page_header.right_most_ptr.unwrap(),
+                    };
+                    path.push(Ancestor {
+                        page: PageBuilder::from_raw_page(raw_page.page_no, &raw_page),
+                        idx,
+                        dirty: false,
+                    });
+                }
+                PageType::IndexLeaf => {
+                    let keys = raw_page
+                        .get_cells::<IndexLeafCell>()
+                        .map(|cell| cell.parse_record(&mut overflow_reader));
+                    let position = search_keys(keys, key);
+                    return (path, raw_page, position);
+                }
+                _ => panic!("internal error"),
+            }
+        }
+    }
+
+    /// Removes a cell from a leaf page, in place unless the page becomes empty.
+    fn remove_leaf_cell(&mut self, path: Vec<Ancestor>, mut raw_page: RawPage, idx: usize) {
+        if raw_page.cell_ptr_arr.len() > 1 || path.is_empty() {
+            raw_page.remove_cell(idx);
+            self.sqlite_file
+                .write_page(raw_page.page_no, &raw_page.bytes);
+        } else {
+            let mut page = PageBuilder::from_raw_page(raw_page.page_no, &raw_page);
+            page.cells.remove(idx);
+            self.balance(path, page);
+        }
+    }
+
+    /// Writes `page` and walks up the `path`, splitting pages that overflow
+    /// and merging pages that became empty with a sibling.
+    /// Stops once no page further up has been modified.
+    fn balance(&mut self, mut path: Vec<Ancestor>, mut page: PageBuilder) {
+        let usable_size = self.db_header.usable_size();
+
+        while let Some(mut parent) = path.pop() {
+            if page.cells.is_empty() && !parent.page.cells.is_empty() {
+                page = self.merge_with_sibling(&mut parent, page);
+            }
+
+            if !page.fits(usable_size) {
+                let page_type = page.page_type;
+                let (mut groups, dividers) =
+                    split(page_type, page.cells, page.right_most_ptr, usable_size);
+
+                // The original page keeps the right-most group,
+                // so the parent's pointer to it stays valid.
+                let (cells, right_most_ptr) = groups.pop().unwrap();
+                page.cells = cells;
+                page.right_most_ptr = right_most_ptr;
+
+                let divider_cells = self.write_groups(page_type, groups, dividers);
+                let idx = parent.idx;
+                parent.page.cells.splice(idx..idx, divider_cells);
+                parent.dirty = true;
+            }
+
+            self.write_page(&page);
+
+            if !parent.dirty && path.iter().all(|ancestor| !ancestor.dirty) {
+                return;
+            }
+            page = parent.page;
+        }
+
+        self.balance_root(page);
+    }
+
+    /// The root page keeps its page number: when it overflows, all of its cells move to new child pages,
+    /// when it is an interior page without cells, the content of its only child moves up into it.
+    fn balance_root(&mut self, mut page: PageBuilder) {
+        let usable_size = self.db_header.usable_size();
+
+        while !page.fits(usable_size) {
+            let page_type = page.page_type;
+            let (mut groups, dividers) =
+                split(page_type, page.cells, page.right_most_ptr, usable_size);
+
+            let (cells, right_most_ptr) = groups.pop().unwrap();
+            let mut right_page = PageBuilder::new(self.allocate_page(), page_type);
+            right_page.cells = cells;
+            right_page.right_most_ptr = right_most_ptr;
+            self.write_page(&right_page);
+
+            page.cells = self.write_groups(page_type, groups, dividers);
+            page.page_type = page_type.to_interior();
+            page.right_most_ptr = Some(right_page.page_no);
+        }
+
+        if page.page_type.is_interior() && page.cells.is_empty() {
+            let child_page_no = page.right_most_ptr.unwrap();
+            let child = PageBuilder::from_raw_page(child_page_no, &self.load_page(child_page_no));
+
+            let interior = (
+                page.page_type,
+                std::mem::take(&mut page.cells),
+                page.right_most_ptr,
+            );
+            page.page_type = child.page_type;
+            page.cells = child.cells;
+            page.right_most_ptr = child.right_most_ptr;
+
+            // Page 1 has less room because of the database header
+            if page.fits(usable_size) {
+                self.free_page(child_page_no);
+            } else {
+                (page.page_type, page.cells, page.right_most_ptr) = interior;
+            }
+        }
+
+        self.write_page(&page);
+    }
+
+    /// Merges an empty page with its left sibling, or its right sibling if it is the left-most child,
+    /// including the divider cell between them from the parent.
+    /// The merged page keeps the page number of the right page, the left page is freed.
+    fn merge_with_sibling(&mut self, parent: &mut Ancestor, page: PageBuilder) -> PageBuilder {
+        let (left, right) = if parent.idx > 0 {
+            let left = self.load_child(&parent.page, parent.idx - 1);
+            parent.idx -= 1;
+            (left, page)
+        } else {
+            let right = self.load_child(&parent.page, 1);
+            (page, right)
+        };
+
+        let divider = parent.page.cells.remove(parent.idx);
+        parent.dirty = true;
+
+        let mut cells = left.cells;
+        match left.page_type {
+            PageType::TableLeaf => {}
+            PageType::IndexLeaf => cells.push(divider[4..].to_vec()),
+            PageType::TableInterior | PageType::IndexInterior => {
+                let left_child_ptr = left.right_most_ptr.unwrap().to_be_bytes();
+                cells.push([&left_child_ptr, &divider[4..]].concat());
+            }
+        }
+        cells.extend(right.cells);
+        self.free_page(left.page_no);
+
+        let mut page = PageBuilder::new(right.page_no, right.page_type);
+        page.cells = cells;
+        page.right_most_ptr = right.right_most_ptr;
+        page
+    }
+
+    fn load_child(&mut self, parent: &PageBuilder, idx: usize) -> PageBuilder {
+        let page_no = match parent.cells.get(idx) {
+            Some(cell) => u32::from_be_bytes(cell[..4].try_into().unwrap()),
+            None => parent.right_most_ptr.unwrap(),
+        };
+        PageBuilder::from_raw_page(page_no, &self.load_page(page_no))
+    }
+
+    /// Writes each group to a new page and returns the interior cells pointing to them.
+    fn write_groups(
+        &mut self,
+        page_type: PageType,
+        groups: Vec<Group>,
+        dividers: Vec<Vec<u8>>,
+    ) -> Vec<Vec<u8>> {
+        groups
+            .into_iter()
+            .zip(dividers)
+            .map(|((cells, right_most_ptr), divider)| {
+                let mut page = PageBuilder::new(self.allocate_page(), page_type);
+                page.cells = cells;
+                page.right_most_ptr = right_most_ptr;
+                self.write_page(&page);
+
+                let mut cell = page.page_no.to_be_bytes().to_vec();
+                cell.extend(divider);
+                cell
+            })
+            .collect()
+    }
+
+    /// Stores the part of the payload that does not fit on the b-tree page on overflow pages
+    /// and returns the local part followed by the page number of the first overflow page.
+    fn write_payload(&mut self, payload: &[u8], max_local: usize) -> Vec<u8> {
+        let usable_size = self.db_header.usable_size();
+        let local_size = local_payload_size(payload.len(), max_local, usable_size);
+
+        let mut stored_payload = payload[..local_size].to_vec();
+        if local_size == payload.len() {
+            return stored_payload;
+        }
+
+        let chunks: Vec<_> = payload[local_size..].chunks(usable_size - 4).collect();
+        let page_nos: Vec<_> = chunks.iter().map(|_| self.allocate_page()).collect();
+        stored_payload.extend(page_nos[0].to_be_bytes());
+
+        let page_size = self.db_header.page_size as usize;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next_page_no = page_nos.get(i + 1).copied().unwrap_or(0);
+
+            let mut page = vec![0; page_size];
+            page[..4].copy_from_slice(&next_page_no.to_be_bytes());
+            page[4..4 + chunk.len()].copy_from_slice(chunk);
+            self.sqlite_file.write_page(page_nos[i], &page);
+        }
+
+        stored_payload
+    }
+
+    fn free_overflow_pages(&mut self, first_overflow_page: u32) {
+        let page_size = self.db_header.page_size as usize;
+
+        let mut page_no = first_overflow_page;
+        while page_no != 0 {
+            let page = self.sqlite_file.load_page(page_no, page_size);
+            let next_page_no = u32::from_be_bytes(page[..4].try_into().unwrap());
+            // Freeing may overwrite the page
+            self.free_page(page_no);
+            page_no = next_page_no;
+        }
+    }
+
+    fn load_page(&mut self, page_no: u32) -> RawPage {
+        let page_size = self.db_header.page_size as usize;
+        let bytes = self.sqlite_file.load_page(page_no, page_size);
+        RawPage::parse(bytes, page_no, self.db_header.usable_size())
+    }
+
+    fn write_page(&mut self, page: &PageBuilder) {
+        let page_size = self.db_header.page_size as usize;
+        let bytes = page.build(page_size, self.db_header.usable_size());
+        self.sqlite_file.write_page(page.page_no, &bytes);
+    }
+
+    fn allocate_page(&mut self) -> u32 {
+        Freelist::new(self.sqlite_file, self.db_header).allocate()
+    }
+
+    fn free_page(&mut self, page_no: u32) {
+        Freelist::new(self.sqlite_file, self.db_header).free(page_no)
+    }
+}
+
+/// Splits the cells of an overflowing page into groups that each fit on a page.
+///
+/// Returns the groups with their right-most pointers
+/// and, for every group but the last, the divider separating it from the next group.
+/// A divider is the content of an interior cell after its left child pointer.
+fn split(
+    page_type: PageType,
+    mut cells: Vec<Vec<u8>>,
+    right_most_ptr: Option<u32>,
+    usable_size: usize,
+) -> (Vec<Group>, Vec<Vec<u8>>) {
+    let mut page = PageBuilder::new(0, page_type);
+    page.cells = cells;
+    if page.fits(usable_size) {
+        return (vec![(page.cells, right_most_ptr)], vec![]);
+    }
+    cells = page.cells;
+
+    // Split where the cells before and after are about the same size
+    let total: usize = cells.iter().map(|cell| cell.len()).sum();
+    let mut acc = 0;
+    let mut mid = cells.len() - 1;
+    for (i, cell) in cells.iter().enumerate() {
+        acc += cell.len();
+        if acc * 2 >= total {
+            mid = i;
+            break;
+        }
+    }
+
+    let (left_right_most_ptr, divider, right_cells) = match page_type {
+        // Table leaf cells are not moved into the parent,
+        // the divider key is the largest rowid on the left page.
+        PageType::TableLeaf => {
+            let mid = mid.clamp(1, cells.len() - 1);
+            let right_cells = cells.split_off(mid);
+            let last = cells.last().unwrap();
+            let rowid = TableLeafCell::parse(last, usable_size).rowid;
+            (None, encode_varint(rowid), right_cells)
+        }
+        // The middle cell moves into the parent
+        PageType::IndexLeaf => {
+            let mid = mid.clamp(1, cells.len() - 2);
+            let right_cells = cells.split_off(mid + 1);
+            let divider = cells.pop().unwrap();
+            (None, divider, right_cells)
+        }
+        // The middle cell moves into the parent,
+        // its left child becomes the right-most child of the left page
+        PageType::TableInterior | PageType::IndexInterior => {
+            let mid = mid.clamp(1, cells.len() - 2);
+            let right_cells = cells.split_off(mid + 1);
+            let divider = cells.pop().unwrap();
+            let left_child_ptr = u32::from_be_bytes(divider[..4].try_into().unwrap());
+            (Some(left_child_ptr), divider[4..].to_vec(), right_cells)
+        }
+    };
+
+    let (mut groups, mut dividers) = split(page_type, cells, left_right_most_ptr, usable_size);
+    let (right_groups, right_dividers) = split(page_type, right_cells, right_most_ptr, usable_size);
+
+    dividers.push(divider);
+    groups.extend(right_groups);
+    dividers.extend(right_dividers);
+    (groups, dividers)
+}
+
+/// Binary search over the sorted keys of a page.
+fn search_keys(keys: impl Iterator<Item = Vec<Value>>, key: &[Value]) -> Result<usize, usize> {
+    let keys: Vec<_> = keys.collect();
+    keys.binary_search_by(|probe| compare_keys(probe, key))
+}
+
+/// Compares index keys value by value.
+pub fn compare_keys(a: &[Value], b: &[Value]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        let ordering = a.compare(b);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
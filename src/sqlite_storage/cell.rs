@@ -1,31 +1,135 @@
+use std::borrow::Cow;
+
+use nom::Offset;
+
 use crate::{
-    bytes::{from_be_bytes::from_be_bytes, varint::parse_varint},
+    bytes::{
+        from_be_bytes::from_be_bytes,
+        varint::{encode_varint, parse_varint},
+    },
     engine::{Record, Value},
 };
 
-use super::record::parse_record;
+use super::{overflow::OverflowReader, record::parse_record};
+
+/// The part of a cell's payload stored on the b-tree page itself,
+/// plus the first page of the overflow chain holding the rest.
+/// https://www.sqlite.org/fileformat.html#b_tree_pages
+#[derive(Debug)]
+pub struct Payload<'a> {
+    size: usize,
+    local: &'a [u8],
+    first_overflow_page: Option<u32>,
+}
+
+impl<'a> Payload<'a> {
+    fn parse(window: &mut &'a [u8], size: i64, max_local: usize, usable_size: usize) -> Self {
+        let size = size as usize;
+        let local_size = local_payload_size(size, max_local, usable_size);
+
+        let local = &window[..local_size];
+        *window = &window[local_size..];
+
+        let first_overflow_page = if local_size < size {
+            Some(from_be_bytes(window))
+        } else {
+            None
+        };
+
+        Self {
+            size,
+            local,
+            first_overflow_page,
+        }
+    }
+
+    pub fn load(&self, overflow_reader: &mut OverflowReader) -> Cow<'a, [u8]> {
+        match self.first_overflow_page {
+            Some(page_no) => {
+                let mut bytes = Vec::with_capacity(self.size);
+                bytes.extend_from_slice(self.local);
+                bytes.extend(overflow_reader.read(page_no, self.size - self.local.len()));
+                Cow::Owned(bytes)
+            }
+            None => Cow::Borrowed(self.local),
+        }
+    }
+
+    /// Number of bytes the payload occupies on the b-tree page
+    fn stored_len(&self) -> usize {
+        match self.first_overflow_page {
+            Some(_) => self.local.len() + 4,
+            None => self.local.len(),
+        }
+    }
+}
+
+/// Maximum payload stored locally on a table leaf page.
+pub fn table_leaf_max_local(usable_size: usize) -> usize {
+    usable_size - 35
+}
+
+/// Maximum payload stored locally on an index page.
+pub fn index_max_local(usable_size: usize) -> usize {
+    ((usable_size - 12) * 64 / 255) - 23
+}
+
+pub fn local_payload_size(size: usize, max_local: usize, usable_size: usize) -> usize {
+    if size <= max_local {
+        return size;
+    }
+
+    let min_local = ((usable_size - 12) * 32 / 255) - 23;
+    let local = min_local + ((size - min_local) % (usable_size - 4));
+    if local <= max_local {
+        local
+    } else {
+        min_local
+    }
+}
 
 #[derive(Debug)]
 pub struct TableLeafCell<'a> {
     pub rowid: i64,
-    payload: &'a [u8],
+    payload: Payload<'a>,
+    header_len: usize,
 }
 
 impl<'a> TableLeafCell<'a> {
-    pub fn parse(mut bytes: &'a [u8]) -> Self {
+    pub fn parse(mut bytes: &'a [u8], usable_size: usize) -> Self {
+        let start = bytes;
         let window = &mut bytes;
 
-        let _payload_size = parse_varint(window);
-        let rowid = parse_varint(window) as i64;
+        let payload_size = parse_varint(window);
+        let rowid = parse_varint(window);
+        let header_len = start.offset(window);
+        let max_local = table_leaf_max_local(usable_size);
 
         Self {
             rowid,
-            payload: window,
+            payload: Payload::parse(window, payload_size, max_local, usable_size),
+            header_len,
         }
     }
 
-    pub fn parse_record(&self) -> Record {
-        let values = parse_record(self.payload);
+    /// `stored_payload` is the local part of the payload followed by the overflow page number, if any
+    pub fn build(rowid: i64, payload_size: usize, stored_payload: &[u8]) -> Vec<u8> {
+        let mut bytes = encode_varint(payload_size as i64);
+        bytes.extend(encode_varint(rowid));
+        bytes.extend_from_slice(stored_payload);
+        bytes
+    }
+
+    pub fn size(&self) -> usize {
+        self.header_len + self.payload.stored_len()
+    }
+
+    pub fn first_overflow_page(&self) -> Option<u32> {
+        self.payload.first_overflow_page
+    }
+
+    pub fn parse_record(&self, overflow_reader: &mut OverflowReader) -> Record {
+        let values = parse_record(&self.payload.load(overflow_reader));
         Record::new(self.rowid, values)
     }
 }
@@ -34,59 +138,126 @@ impl<'a> TableLeafCell<'a> {
 pub struct TableInteriorCell {
     pub left_child_ptr: u32,
     pub key: i64,
+    size: usize,
 }
 
 impl TableInteriorCell {
     pub fn parse(bytes: &[u8]) -> Self {
         let left_child_ptr = from_be_bytes(&mut &bytes[..4]);
-        let key = parse_varint(&mut &bytes[4..]);
+        let window = &mut &bytes[4..];
+        let key = parse_varint(window);
 
         Self {
             left_child_ptr,
             key,
+            size: bytes.offset(window),
         }
     }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
 }
 
 #[derive(Debug)]
 pub struct IndexLeafCell<'a> {
-    payload: &'a [u8],
+    payload: Payload<'a>,
+    header_len: usize,
 }
 
 impl<'a> IndexLeafCell<'a> {
-    pub fn parse(mut bytes: &'a [u8]) -> Self {
+    pub fn parse(mut bytes: &'a [u8], usable_size: usize) -> Self {
+        let start = bytes;
         let window = &mut bytes;
 
-        let _payload_size = parse_varint(window);
+        let payload_size = parse_varint(window);
+        let header_len = start.offset(window);
+        let max_local = index_max_local(usable_size);
+
+        Self {
+            payload: Payload::parse(window, payload_size, max_local, usable_size),
+            header_len,
+        }
+    }
+
+    /// `stored_payload` is the local part of the payload followed by the overflow page number, if any
+    pub fn build(payload_size: usize, stored_payload: &[u8]) -> Vec<u8> {
+        let mut bytes = encode_varint(payload_size as i64);
+        bytes.extend_from_slice(stored_payload);
+        bytes
+    }
+
+    pub fn size(&self) -> usize {
+        self.header_len + self.payload.stored_len()
+    }
 
-        Self { payload: window }
+    pub fn first_overflow_page(&self) -> Option<u32> {
+        self.payload.first_overflow_page
     }
 
-    pub fn parse_record(&self) -> Vec<Value> {
-        parse_record(self.payload)
+    pub fn parse_record(&self, overflow_reader: &mut OverflowReader) -> Vec<Value> {
+        parse_record(&self.payload.load(overflow_reader))
     }
 }
 
 #[derive(Debug)]
 pub struct IndexInteriorCell<'a> {
     pub left_child_ptr: u32,
-    payload: &'a [u8],
+    payload: Payload<'a>,
+    header_len: usize,
 }
 
 impl<'a> IndexInteriorCell<'a> {
-    pub fn parse(mut bytes: &'a [u8]) -> Self {
+    pub fn parse(mut bytes: &'a [u8], usable_size: usize) -> Self {
+        let start = bytes;
         let window = &mut bytes;
 
         let left_child_ptr = from_be_bytes(window);
-        let _payload_size = parse_varint(window);
+        let payload_size = parse_varint(window);
+        let header_len = start.offset(window);
+        let max_local = index_max_local(usable_size);
 
         Self {
             left_child_ptr,
-            payload: window,
+            payload: Payload::parse(window, payload_size, max_local, usable_size),
+            header_len,
         }
     }
 
-    pub fn parse_record(&self) -> Vec<Value> {
-        parse_record(self.payload)
+    pub fn size(&self) -> usize {
+        self.header_len + self.payload.stored_len()
+    }
+
+    pub fn first_overflow_page(&self) -> Option<u32> {
+        self.payload.first_overflow_page
+    }
+
+    pub fn parse_record(&self, overflow_reader: &mut OverflowReader) -> Vec<Value> {
+        parse_record(&self.payload.load(overflow_reader))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_max_local, local_payload_size, table_leaf_max_local};
+
+    #[test]
+    fn local_payload_size_fits() {
+        let usable_size = 4096;
+        let max_local = table_leaf_max_local(usable_size);
+
+        assert_eq!(local_payload_size(100, max_local, usable_size), 100);
+        assert_eq!(local_payload_size(4061, max_local, usable_size), 4061);
+    }
+
+    #[test]
+    fn local_payload_size_spills() {
+        let usable_size = 1024;
+
+        let max_local = table_leaf_max_local(usable_size);
+        assert_eq!(local_payload_size(5000, max_local, usable_size), 920);
+
+        let max_local = index_max_local(usable_size);
+        assert_eq!(local_payload_size(5000, max_local, usable_size), 103);
     }
 }
@@ -1,6 +1,7 @@
 use crate::bytes::from_be_bytes::from_be_bytes;
 
-#[derive(Debug)]
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
 pub struct DBHeader {
     // The header string: "SQLite format 3\000"
     pub page_size: u16,
@@ -10,11 +11,11 @@ pub struct DBHeader {
     // Maximum embedded payload fraction
     // Minimum embedded payload fraction
     // Leaf payload fraction
-    // File change counter
+    pub file_change_counter: u32,
     pub page_cnt: u32,
-    // Page number of the first freelist trunk page
-    // Total number of freelist pages
-    // The schema cookie
+    pub first_freelist_trunk_page: u32,
+    pub freelist_page_cnt: u32,
+    pub schema_cookie: u32,
     // The schema format number. Supported schema formats are 1, 2, 3, and 4
     // Default page cache size
     // The page number of the largest root b-tree page when in auto-vacuum or incremental-vacuum modes, or zero otherwise
@@ -23,7 +24,7 @@ pub struct DBHeader {
     // True (non-zero) for incremental-vacuum mode. False (zero) otherwise
     // The "Application ID" set by PRAGMA application_id
     // Reserved for expansion. Must be zero
-    // The version-valid-for number
+    pub version_valid_for: u32,
     // SQLITE_VERSION_NUMBER
 }
 
@@ -32,8 +33,33 @@ impl DBHeader {
         Self {
             page_size: from_be_bytes(&mut &bytes[16..]),
             reserved_size: from_be_bytes(&mut &bytes[20..]),
+            file_change_counter: from_be_bytes(&mut &bytes[24..]),
             page_cnt: from_be_bytes(&mut &bytes[28..]),
+            first_freelist_trunk_page: from_be_bytes(&mut &bytes[32..]),
+            freelist_page_cnt: from_be_bytes(&mut &bytes[36..]),
+            schema_cookie: from_be_bytes(&mut &bytes[40..]),
             text_encoding: from_be_bytes(&mut &bytes[56..]),
+            version_valid_for: from_be_bytes(&mut &bytes[92..]),
         }
     }
+
+    /// Writes the fields maintained by this implementation into a copy of the on-disk header.
+    pub fn write(&self, bytes: &mut [u8; 100]) {
+        bytes[24..28].copy_from_slice(&self.file_change_counter.to_be_bytes());
+        bytes[28..32].copy_from_slice(&self.page_cnt.to_be_bytes());
+        bytes[32..36].copy_from_slice(&self.first_freelist_trunk_page.to_be_bytes());
+        bytes[36..40].copy_from_slice(&self.freelist_page_cnt.to_be_bytes());
+        bytes[92..96].copy_from_slice(&self.version_valid_for.to_be_bytes());
+    }
+
+    /// "The in-header database size is only considered to be valid if it is non-zero
+    /// and if the 4-byte change counter at offset 24 exactly matches the 4-byte version-valid-for number at offset 92."
+    pub fn is_page_cnt_valid(&self) -> bool {
+        self.page_cnt != 0 && self.file_change_counter == self.version_valid_for
+    }
+
+    /// The usable size of a page is the page size minus the reserved space at the end of each page.
+    pub fn usable_size(&self) -> usize {
+        self.page_size as usize - self.reserved_size as usize
+    }
 }
@@ -0,0 +1,86 @@
+use crate::{
+    bytes::from_be_bytes::from_be_bytes,
+    sqlite_file::{PageBytes, SQLiteFile},
+};
+
+use super::db_header::DBHeader;
+
+/// Hands out and takes back unused pages.
+///
+/// The freelist is a linked list of trunk pages. Each trunk page holds the page number of the next trunk page,
+/// the number of leaf page numbers that follow and the leaf page numbers themselves.
+/// https://www.sqlite.org/fileformat.html#the_freelist
+pub struct Freelist<'a> {
+    sqlite_file: &'a mut SQLiteFile,
+    db_header: &'a mut DBHeader,
+}
+
+impl<'a> Freelist<'a> {
+    pub fn new(sqlite_file: &'a mut SQLiteFile, db_header: &'a mut DBHeader) -> Self {
+        Self {
+            sqlite_file,
+            db_header,
+        }
+    }
+
+    /// Takes a page from the freelist or, if the freelist is empty, appends a page to the file.
+    pub fn allocate(&mut self) -> u32 {
+        let trunk_page_no = self.db_header.first_freelist_trunk_page;
+        if trunk_page_no == 0 {
+            self.db_header.page_cnt += 1;
+            return self.db_header.page_cnt;
+        }
+
+        self.db_header.freelist_page_cnt -= 1;
+
+        let mut trunk_page = self.load_page(trunk_page_no);
+        let window = &mut &trunk_page[..];
+        let next_trunk_page_no: u32 = from_be_bytes(window);
+        let leaf_cnt: u32 = from_be_bytes(window);
+
+        if leaf_cnt == 0 {
+            // The trunk page itself is reused
+            self.db_header.first_freelist_trunk_page = next_trunk_page_no;
+            return trunk_page_no;
+        }
+
+        let offset = 4 + 4 * leaf_cnt as usize;
+        let leaf_page_no = from_be_bytes(&mut &trunk_page[offset..]);
+        trunk_page[4..8].copy_from_slice(&(leaf_cnt - 1).to_be_bytes());
+        self.sqlite_file.write_page(trunk_page_no, &trunk_page);
+
+        leaf_page_no
+    }
+
+    /// Adds a page to the freelist.
+    pub fn free(&mut self, page_no: u32) {
+        self.db_header.freelist_page_cnt += 1;
+
+        let trunk_page_no = self.db_header.first_freelist_trunk_page;
+        if trunk_page_no != 0 {
+            let mut trunk_page = self.load_page(trunk_page_no);
+            let leaf_cnt: u32 = from_be_bytes(&mut &trunk_page[4..]);
+
+            // SQLite never uses the last six entries of a trunk page
+            let max_leaf_cnt = self.db_header.usable_size() / 4 - 8;
+            if (leaf_cnt as usize) < max_leaf_cnt {
+                let offset = 8 + 4 * leaf_cnt as usize;
+                trunk_page[offset..offset + 4].copy_from_slice(&page_no.to_be_bytes());
+                trunk_page[4..8].copy_from_slice(&(leaf_cnt + 1).to_be_bytes());
+                self.sqlite_file.write_page(trunk_page_no, &trunk_page);
+                return;
+            }
+        }
+
+        // The page becomes the new first trunk page
+        let mut page = vec![0; self.db_header.page_size as usize];
+        page[..4].copy_from_slice(&trunk_page_no.to_be_bytes());
+        self.sqlite_file.write_page(page_no, &page);
+        self.db_header.first_freelist_trunk_page = page_no;
+    }
+
+    fn load_page(&mut self, page_no: u32) -> PageBytes {
+        let page_size = self.db_header.page_size as usize;
+        self.sqlite_file.load_page(page_no, page_size)
+    }
+}
@@ -1,9 +1,16 @@
+mod btree_cursor;
+mod btree_writer;
 mod cell;
 mod db_header;
+mod freelist;
+mod overflow;
 mod page;
+mod page_cache;
 mod record;
 mod sqlite_object;
+#[allow(clippy::module_inception)]
 mod sqlite_storage;
 mod value;
 
+pub use record::{parse_record, serialize_record};
 pub use sqlite_storage::SQLiteStorage;
@@ -0,0 +1,45 @@
+use crate::{bytes::from_be_bytes::from_be_bytes, sqlite_file::SQLiteFile};
+
+use super::db_header::DBHeader;
+
+/// Reads the part of a payload that did not fit on the b-tree page.
+///
+/// Each overflow page starts with the page number of the next overflow page
+/// (zero for the last page) followed by `usable_size - 4` bytes of content.
+/// https://www.sqlite.org/fileformat.html#cell_payload_overflow_pages
+pub struct OverflowReader<'a> {
+    sqlite_file: &'a mut SQLiteFile,
+    page_size: usize,
+    usable_size: usize,
+}
+
+impl<'a> OverflowReader<'a> {
+    pub fn new(sqlite_file: &'a mut SQLiteFile, db_header: &DBHeader) -> Self {
+        Self {
+            sqlite_file,
+            page_size: db_header.page_size as usize,
+            usable_size: db_header.usable_size(),
+        }
+    }
+
+    pub fn read(&mut self, first_page_no: u32, len: usize) -> Vec<u8> {
+        let mut content = Vec::with_capacity(len);
+        let mut page_no = first_page_no;
+
+        while content.len() < len {
+            if page_no == 0 {
+                panic!("internal error: overflow chain ends early");
+            }
+
+            let page = self.sqlite_file.load_page(page_no, self.page_size);
+            let window = &mut &page[..];
+            page_no = from_be_bytes(window);
+
+            let remaining = len - content.len();
+            let chunk_len = remaining.min(self.usable_size - 4);
+            content.extend_from_slice(&window[..chunk_len]);
+        }
+
+        content
+    }
+}
@@ -1,28 +1,33 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, rc::Rc};
 
 use crate::{
     engine::Value,
-    sqlite_storage::cell::{IndexInteriorCell, IndexLeafCell},
+    sqlite_storage::{
+        cell::{IndexInteriorCell, IndexLeafCell},
+        overflow::OverflowReader,
+    },
 };
 
 use super::raw_page::RawPage;
 
 #[derive(Debug)]
 pub struct IndexLeafPage {
-    raw_page: RawPage,
+    raw_page: Rc<RawPage>,
 }
 
 impl IndexLeafPage {
-    pub fn new(raw_page: RawPage) -> Self {
+    pub fn new(raw_page: Rc<RawPage>) -> Self {
         Self { raw_page }
     }
 
-    pub fn get_rowids(&self, value: &Value) -> Vec<i64> {
+    pub fn get_rowids(&self, value: &Value, overflow_reader: &mut OverflowReader) -> Vec<i64> {
         let cells = self.raw_page.get_cells::<IndexLeafCell>();
-        let values: Vec<_> = cells.map(|cell| cell.parse_record()).collect();
+        let values: Vec<_> = cells
+            .map(|cell| cell.parse_record(overflow_reader))
+            .collect();
 
         let keys: Vec<_> = values.iter().map(|value| &value[0]).collect();
-        let indices = binary_search_range(&keys, &value);
+        let indices = binary_search_range(&keys, &value, |a, b| a.compare(b));
 
         let mut records = vec![];
         if let Ok((start, end)) = indices {
@@ -30,7 +35,8 @@ impl IndexLeafPage {
                 .into_iter()
                 .skip(start)
                 .take(end - start)
-                .map(|values| i64::from(&values[1]))
+                // The rowid follows the indexed values
+                .map(|values| i64::from(values.last().unwrap()))
                 .collect()
         };
         records
@@ -39,20 +45,27 @@ impl IndexLeafPage {
 
 #[derive(Debug)]
 pub struct IndexInteriorPage {
-    raw_page: RawPage,
+    raw_page: Rc<RawPage>,
 }
 
 impl IndexInteriorPage {
-    pub fn new(raw_page: RawPage) -> Self {
+    pub fn new(raw_page: Rc<RawPage>) -> Self {
         Self { raw_page }
     }
 
-    pub fn get_children(&self, value: &Value) -> (Vec<u32>, Vec<i64>) {
+    pub fn get_children(
+        &self,
+        value: &Value,
+        overflow_reader: &mut OverflowReader,
+    ) -> (Vec<u32>, Vec<i64>) {
         let cells: Vec<_> = self.raw_page.get_cells::<IndexInteriorCell>().collect();
-        let values: Vec<_> = cells.iter().map(|cell| cell.parse_record()).collect();
+        let values: Vec<_> = cells
+            .iter()
+            .map(|cell| cell.parse_record(overflow_reader))
+            .collect();
 
         let keys: Vec<_> = values.iter().map(|value| &value[0]).collect();
-        let indices = binary_search_range(&keys, &value);
+        let indices = binary_search_range(&keys, &value, |a, b| a.compare(b));
 
         let mut ptrs = cells.iter().map(|cell| cell.left_child_ptr).chain([self
             .raw_page
@@ -68,7 +81,7 @@ impl IndexInteriorPage {
                     .into_iter()
                     .skip(start)
                     .take(end - start)
-                    .map(|values| i64::from(&values[1]))
+                    .map(|values| i64::from(values.last().unwrap()))
                     .collect();
 
                 (ptrs, rowids)
@@ -78,41 +91,32 @@ impl IndexInteriorPage {
     }
 }
 
-/// Makes a `PartialOrd` type act as if it implemented `Ord`
-/// by treating values that are not comparable as equal.
-fn cmp<T: PartialOrd>(elem: &T, needle: &T) -> Ordering {
-    if let Some(ordering) = elem.partial_cmp(needle) {
-        ordering
-    } else {
-        Ordering::Equal
+/// The range of elements equal to the needle in a sorted haystack,
+/// otherwise the position the needle would be inserted at.
+fn binary_search_range<T>(
+    haystack: &[T],
+    needle: &T,
+    compare: impl Fn(&T, &T) -> Ordering,
+) -> Result<(usize, usize), usize> {
+    let idx = haystack.binary_search_by(|elem| compare(elem, needle))?;
+
+    let mut left = idx;
+    while left > 0 && compare(&haystack[left - 1], needle).is_eq() {
+        left -= 1;
     }
-}
-
-fn binary_search_range<T: PartialOrd>(haystack: &[T], needle: &T) -> Result<(usize, usize), usize> {
-    let result = haystack.binary_search_by(|elem| cmp(elem, needle));
-    match result {
-        Ok(idx) => {
-            let mut left = idx;
-            loop {
-                if left == 0 || haystack[left - 1] != *needle {
-                    break;
-                }
-                left -= 1;
-            }
 
-            let mut right = idx;
-            while right < haystack.len() && haystack[right] == *needle {
-                right += 1;
-            }
-
-            Ok((left, right))
-        }
-        Err(idx) => Err(idx),
+    let mut right = idx;
+    while right < haystack.len() && compare(&haystack[right], needle).is_eq() {
+        right += 1;
     }
+
+    Ok((left, right))
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::engine::Value;
+
     use super::binary_search_range;
 
     #[test]
@@ -120,7 +124,7 @@ mod tests {
         let haystack = ["b", "d", "d", "e"];
         let needle = "a";
 
-        let got = binary_search_range(&haystack, &needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Err(0));
     }
 
@@ -129,7 +133,7 @@ mod tests {
         let haystack = ["b", "d", "d", "e"];
         let needle = "b";
 
-        let got = binary_search_range(&haystack, &needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Ok((0, 1)));
     }
 
@@ -138,7 +142,7 @@ mod tests {
         let haystack = ["b", "d", "d", "e"];
         let needle = "c";
 
-        let got = binary_search_range(&haystack, &&needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Err(1));
     }
 
@@ -147,7 +151,7 @@ mod tests {
         let haystack = ["b", "d", "d", "e"];
         let needle = "d";
 
-        let got = binary_search_range(&haystack, &needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Ok((1, 3)));
     }
 
@@ -156,16 +160,38 @@ mod tests {
         let haystack = ["b", "d", "d", "e"];
         let needle = "e";
 
-        let got = binary_search_range(&haystack, &needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Ok((3, 4)))
     }
 
+    #[test]
+    fn binary_search_range_numeric() {
+        // Integers and reals of equal value are equal keys
+        let haystack = [
+            Value::Integer(3),
+            Value::Real(3.0),
+            Value::Real(3.5),
+            Value::Integer(4),
+            Value::Text(String::from("3")),
+        ];
+        let compare = |a: &Value, b: &Value| a.compare(b);
+
+        let got = binary_search_range(&haystack, &Value::Real(3.0), compare);
+        assert_eq!(got, Ok((0, 2)));
+
+        let got = binary_search_range(&haystack, &Value::Real(3.5), compare);
+        assert_eq!(got, Ok((2, 3)));
+
+        let got = binary_search_range(&haystack, &Value::Integer(5), compare);
+        assert_eq!(got, Err(4));
+    }
+
     #[test]
     fn binary_search_range_6() {
         let haystack = ["b", "d", "d", "e"];
         let needle = "f";
 
-        let got = binary_search_range(&haystack, &needle);
+        let got = binary_search_range(&haystack, &needle, Ord::cmp);
         assert_eq!(got, Err(4));
     }
 }
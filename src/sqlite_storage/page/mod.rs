@@ -1,15 +1,20 @@
+use std::rc::Rc;
+
 use self::{
     index_page::{IndexInteriorPage, IndexLeafPage},
-    page_header::PageType,
-    raw_page::RawPage,
     table_page::{TableInteriorPage, TableLeafPage},
 };
 
 mod index_page;
+mod page_builder;
 mod page_header;
 mod raw_page;
 mod table_page;
 
+pub use page_builder::PageBuilder;
+pub use page_header::PageType;
+pub use raw_page::RawPage;
+
 #[derive(Debug)]
 pub enum Page {
     Table(TablePage),
@@ -17,9 +22,7 @@ pub enum Page {
 }
 
 impl Page {
-    pub fn parse(bytes: Vec<u8>, page_no: u32) -> Self {
-        let page = RawPage::parse(bytes, page_no);
-
+    pub fn new(page: Rc<RawPage>) -> Self {
         match page.page_header.page_type {
             PageType::TableLeaf => Page::Table(TablePage::Leaf(TableLeafPage::new(page))),
             PageType::TableInterior => {
@@ -0,0 +1,106 @@
+use super::{page_header::PageType, raw_page::RawPage};
+
+/// An editable b-tree page: the cells are kept as raw bytes
+/// and the page is laid out from scratch (without any free space fragments) on `build`.
+#[derive(Debug)]
+pub struct PageBuilder {
+    pub page_no: u32,
+    pub page_type: PageType,
+    pub cells: Vec<Vec<u8>>,
+    pub right_most_ptr: Option<u32>,
+    // The database header on page 1
+    prefix: Vec<u8>,
+}
+
+impl PageBuilder {
+    pub fn new(page_no: u32, page_type: PageType) -> Self {
+        Self {
+            page_no,
+            page_type,
+            cells: vec![],
+            right_most_ptr: None,
+            prefix: vec![],
+        }
+    }
+
+    pub fn from_raw_page(page_no: u32, raw_page: &RawPage) -> Self {
+        let start_offset = if page_no == 1 { 100 } else { 0 };
+
+        Self {
+            page_no,
+            page_type: raw_page.page_header.page_type,
+            cells: raw_page
+                .get_raw_cells()
+                .into_iter()
+                .map(|cell| cell.to_vec())
+                .collect(),
+            right_most_ptr: raw_page.page_header.right_most_ptr,
+            prefix: raw_page.bytes[..start_offset].to_vec(),
+        }
+    }
+
+    pub fn used_space(&self) -> usize {
+        self.prefix.len()
+            + self.page_type.header_len()
+            + self
+                .cells
+                .iter()
+                .map(|cell| cell.len() + 2) // + cell pointer
+                .sum::<usize>()
+    }
+
+    pub fn fits(&self, usable_size: usize) -> bool {
+        self.used_space() <= usable_size
+    }
+
+    pub fn build(&self, page_size: usize, usable_size: usize) -> Vec<u8> {
+        assert!(self.fits(usable_size), "internal error: page overflow");
+
+        let mut bytes = vec![0; page_size];
+        bytes[..self.prefix.len()].copy_from_slice(&self.prefix);
+
+        let mut content_start = usable_size;
+        let mut cell_ptrs = Vec::with_capacity(self.cells.len());
+        for cell in &self.cells {
+            content_start -= cell.len();
+            bytes[content_start..content_start + cell.len()].copy_from_slice(cell);
+            cell_ptrs.push(content_start as u16);
+        }
+
+        let mut header = vec![self.page_type.flag()];
+        header.extend(0u16.to_be_bytes()); // first freeblock
+        header.extend((self.cells.len() as u16).to_be_bytes());
+        // A zero value is interpreted as 65536
+        header.extend((content_start as u16).to_be_bytes());
+        header.push(0); // fragmented free bytes
+        if let Some(right_most_ptr) = self.right_most_ptr {
+            header.extend(right_most_ptr.to_be_bytes());
+        }
+        header.extend(cell_ptrs.into_iter().flat_map(u16::to_be_bytes));
+
+        let start = self.prefix.len();
+        bytes[start..start + header.len()].copy_from_slice(&header);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sqlite_storage::page::{page_header::PageType, raw_page::RawPage};
+
+    use super::PageBuilder;
+
+    #[test]
+    fn build_roundtrip() {
+        let mut page = PageBuilder::new(2, PageType::TableInterior);
+        page.cells = vec![vec![0, 0, 0, 3, 10], vec![0, 0, 0, 4, 20]];
+        page.right_most_ptr = Some(5);
+
+        let bytes = page.build(512, 512);
+        let raw_page = RawPage::parse(bytes, 2, 512);
+
+        assert_eq!(raw_page.page_header.cell_cnt, 2);
+        assert_eq!(raw_page.page_header.right_most_ptr, Some(5));
+        assert_eq!(raw_page.get_raw_cells(), page.cells);
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use crate::bytes::from_be_bytes::from_be_bytes;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PageType {
     TableInterior,
     TableLeaf,
@@ -20,6 +20,39 @@ impl PageType {
             _ => panic!("internal error: page type: {}", flag),
         }
     }
+
+    pub fn flag(&self) -> u8 {
+        match self {
+            PageType::IndexInterior => 2,
+            PageType::TableInterior => 5,
+            PageType::IndexLeaf => 10,
+            PageType::TableLeaf => 13,
+        }
+    }
+
+    pub fn is_table(&self) -> bool {
+        matches!(self, PageType::TableInterior | PageType::TableLeaf)
+    }
+
+    pub fn is_interior(&self) -> bool {
+        matches!(self, PageType::TableInterior | PageType::IndexInterior)
+    }
+
+    pub fn to_interior(self) -> PageType {
+        match self {
+            PageType::TableInterior | PageType::TableLeaf => PageType::TableInterior,
+            PageType::IndexInterior | PageType::IndexLeaf => PageType::IndexInterior,
+        }
+    }
+
+    /// Size of the b-tree page header: 12 bytes for interior pages, 8 bytes for leaf pages
+    pub fn header_len(&self) -> usize {
+        if self.is_interior() {
+            12
+        } else {
+            8
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -43,15 +76,30 @@ impl PageHeader {
             right_most_ptr: None,
         };
 
-        if page_header.is_interior() {
+        if page_header.page_type.is_interior() {
             page_header.right_most_ptr = Some(from_be_bytes(window));
         }
 
         page_header
     }
 
-    fn is_interior(&self) -> bool {
-        matches!(self.page_type, PageType::TableInterior)
-            || matches!(self.page_type, PageType::IndexInterior)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.page_type.flag()];
+        bytes.extend(self.freeblock_start.to_be_bytes());
+        bytes.extend(self.cell_cnt.to_be_bytes());
+        bytes.extend(self.cell_content_area_start.to_be_bytes());
+        bytes.push(self.fragmented_free_bytes_cnt);
+        if let Some(right_most_ptr) = self.right_most_ptr {
+            bytes.extend(right_most_ptr.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// "A zero value for this integer is interpreted as 65536."
+    pub fn get_cell_content_area_start(&self) -> usize {
+        match self.cell_content_area_start {
+            0 => 65536,
+            start => start as usize,
+        }
     }
 }
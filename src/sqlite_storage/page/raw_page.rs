@@ -1,19 +1,25 @@
 use std::fmt::Debug;
 
+use crate::bytes::from_be_bytes::from_be_bytes;
+use crate::sqlite_file::PageBytes;
 use crate::sqlite_storage::cell::{
     IndexInteriorCell, IndexLeafCell, TableInteriorCell, TableLeafCell,
 };
 
-use super::page_header::PageHeader;
+use super::page_header::{PageHeader, PageType};
 
 pub struct RawPage {
+    pub page_no: u32,
     pub page_header: PageHeader,
     pub cell_ptr_arr: Vec<u16>,
-    pub bytes: Vec<u8>,
+    pub bytes: PageBytes,
+    usable_size: usize,
+    start_offset: usize,
 }
 
 impl RawPage {
-    pub fn parse(bytes: Vec<u8>, page_no: u32) -> Self {
+    pub fn parse(bytes: impl Into<PageBytes>, page_no: u32, usable_size: usize) -> Self {
+        let bytes = bytes.into();
         let start_offset = if page_no == 1 { 100 } else { 0 };
         let window = &mut &bytes[start_offset..];
         let page_header = PageHeader::parse(window);
@@ -24,18 +30,199 @@ impl RawPage {
             .collect();
 
         Self {
+            page_no,
             page_header,
             cell_ptr_arr,
             bytes,
+            usable_size,
+            start_offset,
         }
     }
 
     pub fn get_cells<'a, T: Cell<'a>>(&'a self) -> impl Iterator<Item = T> + 'a {
         self.cell_ptr_arr.iter().map(|cell_ptr| {
             let bytes = &self.bytes[*cell_ptr as usize..];
-            T::parse(bytes)
+            T::parse(bytes, self.usable_size)
         })
     }
+
+    pub fn get_cell<'a, T: Cell<'a>>(&'a self, idx: usize) -> T {
+        let bytes = &self.bytes[self.cell_ptr_arr[idx] as usize..];
+        T::parse(bytes, self.usable_size)
+    }
+
+    /// Returns the bytes of every cell, in cell pointer array order.
+    pub fn get_raw_cells(&self) -> Vec<&[u8]> {
+        let sizes: Vec<_> = match self.page_header.page_type {
+            PageType::TableLeaf => self
+                .get_cells::<TableLeafCell>()
+                .map(|c| c.size())
+                .collect(),
+            PageType::TableInterior => self
+                .get_cells::<TableInteriorCell>()
+                .map(|c| c.size())
+                .collect(),
+            PageType::IndexLeaf => self
+                .get_cells::<IndexLeafCell>()
+                .map(|c| c.size())
+                .collect(),
+            PageType::IndexInterior => self
+                .get_cells::<IndexInteriorCell>()
+                .map(|c| c.size())
+                .collect(),
+        };
+
+        self.cell_ptr_arr
+            .iter()
+            .zip(sizes)
+            .map(|(cell_ptr, size)| &self.bytes[*cell_ptr as usize..*cell_ptr as usize + size])
+            .collect()
+    }
+
+    /// Removes a cell and adds the space it occupied to the freeblock list.
+    pub fn remove_cell(&mut self, idx: usize) {
+        let size = self.get_raw_cells()[idx].len();
+        let cell_ptr = self.cell_ptr_arr.remove(idx);
+        self.page_header.cell_cnt -= 1;
+
+        self.free_space(cell_ptr as usize, size);
+        self.write_header();
+    }
+
+    /// Overwrites a cell, returns false if there is not enough contiguous free space for the new cell.
+    pub fn replace_cell(&mut self, idx: usize, cell: &[u8]) -> bool {
+        let old_size = self.get_raw_cells()[idx].len();
+        let old_cell_ptr = self.cell_ptr_arr[idx] as usize;
+
+        let cell_ptr = if cell.len() == old_size {
+            old_cell_ptr
+        } else {
+            let Some(cell_ptr) = self.allocate_space(cell.len()) else {
+                return false;
+            };
+            self.free_space(old_cell_ptr, old_size);
+            cell_ptr
+        };
+
+        self.bytes[cell_ptr..cell_ptr + cell.len()].copy_from_slice(cell);
+        self.cell_ptr_arr[idx] = cell_ptr as u16;
+        self.write_header();
+        true
+    }
+
+    /// Takes space from the first freeblock that is large enough,
+    /// or else from the gap between the cell pointer array and the cell content area.
+    fn allocate_space(&mut self, size: usize) -> Option<usize> {
+        let mut prev = None;
+        let mut freeblock = self.page_header.freeblock_start as usize;
+
+        while freeblock != 0 {
+            let (next, freeblock_size) = self.read_freeblock(freeblock);
+
+            let leftover = freeblock_size.wrapping_sub(size);
+            let fragmented_free_bytes_cnt = self.page_header.fragmented_free_bytes_cnt as usize;
+
+            // A freeblock needs at least 4 bytes, smaller leftovers become fragments.
+            // SQLite defragments a page once it has more than 60 fragmented bytes.
+            if freeblock_size >= size
+                && (leftover >= 4 || fragmented_free_bytes_cnt + leftover <= 60)
+            {
+                if leftover < 4 {
+                    self.set_next_freeblock(prev, next);
+                    self.page_header.fragmented_free_bytes_cnt += leftover as u8;
+                    return Some(freeblock);
+                }
+
+                self.write_freeblock(freeblock, next, leftover);
+                return Some(freeblock + leftover);
+            }
+
+            prev = Some(freeblock);
+            freeblock = next;
+        }
+
+        let header_len = self.page_header.page_type.header_len();
+        let cell_ptr_arr_end = self.start_offset + header_len + 2 * self.cell_ptr_arr.len();
+        let content_start = self.page_header.get_cell_content_area_start();
+
+        if content_start - cell_ptr_arr_end >= size {
+            let start = content_start - size;
+            self.page_header.cell_content_area_start = start as u16;
+            return Some(start);
+        }
+
+        None
+    }
+
+    /// Inserts the space into the sorted freeblock list, merging it with adjacent freeblocks.
+    fn free_space(&mut self, start: usize, mut size: usize) {
+        let mut prev = None;
+        let mut next = self.page_header.freeblock_start as usize;
+        while next != 0 && next < start {
+            prev = Some(next);
+            next = self.read_freeblock(next).0;
+        }
+
+        if next != 0 && start + size == next {
+            let (next_next, next_size) = self.read_freeblock(next);
+            size += next_size;
+            next = next_next;
+        }
+
+        match prev {
+            Some(prev) if prev + self.read_freeblock(prev).1 == start => {
+                let prev_size = self.read_freeblock(prev).1;
+                self.write_freeblock(prev, next, prev_size + size);
+            }
+            _ => {
+                self.write_freeblock(start, next, size);
+                self.set_next_freeblock(prev, start);
+            }
+        }
+
+        // A freeblock at the start of the cell content area is given back to the unallocated space
+        let first = self.page_header.freeblock_start as usize;
+        if first != 0 && first == self.page_header.get_cell_content_area_start() {
+            let (next, size) = self.read_freeblock(first);
+            self.page_header.freeblock_start = next as u16;
+            self.page_header.cell_content_area_start = (first + size) as u16;
+        }
+    }
+
+    /// Returns the offset of the next freeblock and the size of the freeblock.
+    fn read_freeblock(&self, offset: usize) -> (usize, usize) {
+        let window = &mut &self.bytes[offset..];
+        let next: u16 = from_be_bytes(window);
+        let size: u16 = from_be_bytes(window);
+        (next as usize, size as usize)
+    }
+
+    fn write_freeblock(&mut self, offset: usize, next: usize, size: usize) {
+        self.bytes[offset..offset + 2].copy_from_slice(&(next as u16).to_be_bytes());
+        self.bytes[offset + 2..offset + 4].copy_from_slice(&(size as u16).to_be_bytes());
+    }
+
+    fn set_next_freeblock(&mut self, prev: Option<usize>, next: usize) {
+        match prev {
+            Some(prev) => {
+                self.bytes[prev..prev + 2].copy_from_slice(&(next as u16).to_be_bytes());
+            }
+            None => self.page_header.freeblock_start = next as u16,
+        }
+    }
+
+    /// Writes the page header and the cell pointer array back into `bytes`.
+    fn write_header(&mut self) {
+        let mut header = self.page_header.to_bytes();
+        header.extend(
+            self.cell_ptr_arr
+                .iter()
+                .flat_map(|cell_ptr| cell_ptr.to_be_bytes()),
+        );
+
+        let start = self.start_offset;
+        self.bytes[start..start + header.len()].copy_from_slice(&header);
+    }
 }
 
 impl Debug for RawPage {
@@ -49,29 +236,85 @@ impl Debug for RawPage {
 }
 
 pub trait Cell<'a> {
-    fn parse(bytes: &'a [u8]) -> Self;
+    fn parse(bytes: &'a [u8], usable_size: usize) -> Self;
 }
 
 impl<'a> Cell<'a> for TableLeafCell<'a> {
-    fn parse(bytes: &'a [u8]) -> Self {
-        Self::parse(bytes)
+    fn parse(bytes: &'a [u8], usable_size: usize) -> Self {
+        Self::parse(bytes, usable_size)
     }
 }
 
 impl Cell<'_> for TableInteriorCell {
-    fn parse(bytes: &[u8]) -> Self {
+    fn parse(bytes: &[u8], _usable_size: usize) -> Self {
         Self::parse(bytes)
     }
 }
 
 impl<'a> Cell<'a> for IndexLeafCell<'a> {
-    fn parse(bytes: &'a [u8]) -> Self {
-        Self::parse(bytes)
+    fn parse(bytes: &'a [u8], usable_size: usize) -> Self {
+        Self::parse(bytes, usable_size)
     }
 }
 
 impl<'a> Cell<'a> for IndexInteriorCell<'a> {
-    fn parse(bytes: &'a [u8]) -> Self {
-        Self::parse(bytes)
+    fn parse(bytes: &'a [u8], usable_size: usize) -> Self {
+        Self::parse(bytes, usable_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sqlite_storage::page::{page_builder::PageBuilder, page_header::PageType};
+
+    use super::RawPage;
+
+    fn build_raw_page() -> RawPage {
+        let mut page = PageBuilder::new(2, PageType::TableInterior);
+        page.cells = vec![
+            vec![0, 0, 0, 3, 10],
+            vec![0, 0, 0, 4, 20],
+            vec![0, 0, 0, 5, 30],
+        ];
+        page.right_most_ptr = Some(6);
+        RawPage::parse(page.build(512, 512), 2, 512)
+    }
+
+    #[test]
+    fn remove_cell_creates_freeblock() {
+        let mut raw_page = build_raw_page();
+        raw_page.remove_cell(1);
+
+        let raw_page = RawPage::parse(raw_page.bytes, 2, 512);
+        assert_eq!(raw_page.page_header.cell_cnt, 2);
+        assert_eq!(raw_page.page_header.freeblock_start, 502);
+        assert_eq!(raw_page.read_freeblock(502), (0, 5));
+        assert_eq!(
+            raw_page.get_raw_cells(),
+            [[0, 0, 0, 3, 10], [0, 0, 0, 5, 30]]
+        );
+    }
+
+    #[test]
+    fn remove_last_cell_returns_space_to_content_area() {
+        let mut raw_page = build_raw_page();
+        raw_page.remove_cell(1);
+        raw_page.remove_cell(1);
+
+        assert_eq!(raw_page.page_header.freeblock_start, 0);
+        assert_eq!(raw_page.page_header.get_cell_content_area_start(), 507);
+    }
+
+    #[test]
+    fn replace_cell_merges_freeblocks() {
+        let mut raw_page = build_raw_page();
+        raw_page.remove_cell(1);
+
+        // The freeblock is too small for the new cell, the old cell's space is merged into it
+        assert!(raw_page.replace_cell(0, &[0, 0, 0, 7, 0x81, 0]));
+        assert_eq!(raw_page.page_header.freeblock_start, 502);
+        assert_eq!(raw_page.read_freeblock(502), (0, 10));
+        assert_eq!(raw_page.page_header.get_cell_content_area_start(), 491);
+        assert_eq!(raw_page.get_raw_cells()[0], [0, 0, 0, 7, 0x81, 0]);
     }
 }
@@ -1,21 +1,30 @@
+use std::rc::Rc;
+
 use crate::{
     engine::Record,
-    sqlite_storage::cell::{TableInteriorCell, TableLeafCell},
+    sqlite_storage::{
+        cell::{TableInteriorCell, TableLeafCell},
+        overflow::OverflowReader,
+    },
 };
 
 use super::raw_page::RawPage;
 
 #[derive(Debug)]
 pub struct TableLeafPage {
-    raw_page: RawPage,
+    raw_page: Rc<RawPage>,
 }
 
 impl TableLeafPage {
-    pub fn new(raw_page: RawPage) -> Self {
+    pub fn new(raw_page: Rc<RawPage>) -> Self {
         Self { raw_page }
     }
 
-    pub fn get_records(&self, rowids: Option<&[i64]>) -> Vec<Record> {
+    pub fn get_records(
+        &self,
+        rowids: Option<&[i64]>,
+        overflow_reader: &mut OverflowReader,
+    ) -> Vec<Record> {
         let cells = self.raw_page.get_cells::<TableLeafCell>();
 
         match rowids {
@@ -25,32 +34,41 @@ impl TableLeafPage {
 
                 rowids
                     .iter()
-                    .map(|rowid| {
-                        let idx = cells
-                            .binary_search_by_key(rowid, |cell| cell.rowid)
-                            .unwrap();
+                    .filter_map(|rowid| {
+                        let idx = cells.binary_search_by_key(rowid, |cell| cell.rowid).ok()?;
 
-                        let record = cells[idx].parse_record();
+                        let record = cells[idx].parse_record(overflow_reader);
                         cells = &cells[idx..];
-                        record
+                        Some(record)
                     })
                     .collect()
             }
-            None => cells.map(|cell| cell.parse_record()).collect(),
+            None => cells
+                .map(|cell| cell.parse_record(overflow_reader))
+                .collect(),
         }
     }
+
+    pub fn max_rowid(&self) -> Option<i64> {
+        let cells = self.raw_page.get_cells::<TableLeafCell>();
+        cells.last().map(|cell| cell.rowid)
+    }
 }
 
 #[derive(Debug)]
 pub struct TableInteriorPage {
-    raw_page: RawPage,
+    raw_page: Rc<RawPage>,
 }
 
 impl TableInteriorPage {
-    pub fn new(raw_page: RawPage) -> Self {
+    pub fn new(raw_page: Rc<RawPage>) -> Self {
         Self { raw_page }
     }
 
+    pub fn get_right_most_ptr(&self) -> u32 {
+        self.raw_page.page_header.right_most_ptr.unwrap()
+    }
+
     pub fn get_buckets<'a>(&self, rowids: Option<&'a [i64]>) -> Vec<(u32, Option<&'a [i64]>)> {
         let cells = self.raw_page.get_cells::<TableInteriorCell>();
         let right_most_ptr = self.raw_page.page_header.right_most_ptr.unwrap();
@@ -58,7 +76,7 @@ impl TableInteriorPage {
         match rowids {
             Some(rowids) => {
                 let cells: Vec<_> = cells.collect();
-                let buckets = create_buckets(&cells, right_most_ptr, &rowids);
+                let buckets = create_buckets(&cells, right_most_ptr, rowids);
                 buckets
                     .into_iter()
                     .map(|(page_no, rowids)| (page_no, Some(rowids)))
@@ -82,7 +100,7 @@ fn create_buckets<'a>(
     rowids: &'a [i64],
 ) -> Vec<(u32, &'a [i64])> {
     let keys: Vec<_> = cells.iter().map(|cell| cell.key).collect();
-    let groups = create_bins(&keys, &rowids);
+    let groups = create_bins(&keys, rowids);
 
     cells
         .iter()
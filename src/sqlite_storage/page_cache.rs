@@ -0,0 +1,113 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use super::page::RawPage;
+
+/// Number of pages cached when no capacity is configured (SQLite's default is 2000 KiB)
+pub const DEFAULT_CACHE_SIZE: usize = 2000;
+
+/// Least recently used cache of parsed b-tree pages.
+#[derive(Debug)]
+pub struct PageCache {
+    capacity: usize,
+    // Page number to the page and the tick of its last use
+    pages: HashMap<u32, (Rc<RawPage>, u64)>,
+    // Tick of the last use to page number, the first entry is the least recently used page
+    recency: BTreeMap<u64, u32>,
+    tick: u64,
+}
+
+impl PageCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            pages: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    pub fn get(&mut self, page_no: u32) -> Option<Rc<RawPage>> {
+        let tick = self.next_tick();
+        let (page, last_used) = self.pages.get_mut(&page_no)?;
+
+        self.recency.remove(last_used);
+        self.recency.insert(tick, page_no);
+        *last_used = tick;
+        Some(Rc::clone(page))
+    }
+
+    pub fn insert(&mut self, page_no: u32, page: Rc<RawPage>) {
+        let tick = self.next_tick();
+        if let Some((_, last_used)) = self.pages.insert(page_no, (page, tick)) {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(tick, page_no);
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+
+    fn evict(&mut self) {
+        while self.pages.len() > self.capacity {
+            let (_, page_no) = self.recency.pop_first().unwrap();
+            self.pages.remove(&page_no);
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::sqlite_storage::page::{PageBuilder, PageType, RawPage};
+
+    use super::PageCache;
+
+    fn raw_page(page_no: u32) -> Rc<RawPage> {
+        let page = PageBuilder::new(page_no, PageType::TableLeaf);
+        Rc::new(RawPage::parse(page.build(512, 512), page_no, 512))
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut page_cache = PageCache::new(2);
+        page_cache.insert(2, raw_page(2));
+        page_cache.insert(3, raw_page(3));
+
+        assert!(page_cache.get(2).is_some());
+        page_cache.insert(4, raw_page(4));
+
+        assert!(page_cache.get(3).is_none());
+        assert!(page_cache.get(2).is_some());
+        assert!(page_cache.get(4).is_some());
+    }
+
+    #[test]
+    fn set_capacity_shrinks() {
+        let mut page_cache = PageCache::new(3);
+        for page_no in 2..5 {
+            page_cache.insert(page_no, raw_page(page_no));
+        }
+
+        page_cache.set_capacity(1);
+        assert!(page_cache.get(2).is_none());
+        assert!(page_cache.get(3).is_none());
+        assert!(page_cache.get(4).is_some());
+    }
+}
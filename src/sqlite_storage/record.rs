@@ -1,7 +1,7 @@
 use nom::Offset;
 
-use super::value::{parse_integer, parse_real, parse_text_or_blob};
-use crate::bytes::varint::{parse_varint, parse_varints};
+use super::value::{parse_integer, parse_real, parse_text_or_blob, serialize_value};
+use crate::bytes::varint::{encode_varint, parse_varint, parse_varints};
 use crate::engine::Value;
 
 #[derive(Debug)]
@@ -46,3 +46,42 @@ pub fn parse_record(bytes: &[u8]) -> Vec<Value> {
         })
         .collect()
 }
+
+pub fn serialize_record(values: &[Value]) -> Vec<u8> {
+    let (serial_types, bodies): (Vec<_>, Vec<_>) = values.iter().map(serialize_value).unzip();
+
+    let serial_types: Vec<_> = serial_types.into_iter().flat_map(encode_varint).collect();
+
+    // The header size includes the varint holding the header size itself
+    let mut header_size = serial_types.len() + 1;
+    while encode_varint(header_size as i64).len() + serial_types.len() > header_size {
+        header_size += 1;
+    }
+
+    let mut bytes = encode_varint(header_size as i64);
+    bytes.extend(serial_types);
+    bytes.extend(bodies.into_iter().flatten());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Value;
+
+    use super::{parse_record, serialize_record};
+
+    #[test]
+    fn serialize_record_roundtrip() {
+        let values = vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(-42),
+            Value::Real(1.5),
+            Value::Text("x".repeat(200)),
+            Value::Blob(vec![1, 2, 3]),
+        ];
+
+        let bytes = serialize_record(&values);
+        assert_eq!(parse_record(&bytes), values);
+    }
+}
@@ -10,6 +10,8 @@ impl From<Record> for SQLiteObject {
             Value::Text(object_type) => match object_type.as_ref() {
                 "table" => SQLiteObjectType::Table,
                 "index" => SQLiteObjectType::Index,
+                "view" => SQLiteObjectType::View,
+                "trigger" => SQLiteObjectType::Trigger,
                 _ => unimplemented!(),
             },
             _ => panic!(),
@@ -24,8 +26,11 @@ impl From<Record> for SQLiteObject {
         let Value::Integer(rootpage) = records_it.next().unwrap() else {
             panic!()
         };
-        let Value::Text(sql) = records_it.next().unwrap() else {
-            panic!()
+        // Automatic indexes created for UNIQUE and PRIMARY KEY constraints have no SQL text
+        let sql = match records_it.next().unwrap() {
+            Value::Text(sql) => sql,
+            Value::Null => String::new(),
+            _ => panic!(),
         };
 
         SQLiteObject {
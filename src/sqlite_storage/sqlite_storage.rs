@@ -1,26 +1,114 @@
+use std::{iter, rc::Rc, slice};
+
+use super::btree_cursor::{get_child, BTreeCursor};
+use super::btree_writer::BTreeWriter;
 use super::db_header::DBHeader;
-use super::page::{IndexPage, Page, TablePage};
+use super::overflow::OverflowReader;
+use super::page::{IndexPage, Page, RawPage, TablePage};
+use super::page_cache::{PageCache, DEFAULT_CACHE_SIZE};
 use crate::engine::{DBInfo, Record, SQLiteSchema, Storage, Value};
 use crate::sqlite_file::SQLiteFile;
 
 #[derive(Debug)]
 pub struct SQLiteStorage {
     sqlite_file: SQLiteFile,
+    page_cache: PageCache,
+    db_header: Option<DBHeader>,
+    sqlite_schema: Option<SQLiteSchema>,
 }
 
 impl SQLiteStorage {
     pub fn new(sqlite_file: SQLiteFile) -> Self {
-        Self { sqlite_file }
+        Self {
+            sqlite_file,
+            page_cache: PageCache::new(DEFAULT_CACHE_SIZE),
+            db_header: None,
+            sqlite_schema: None,
+        }
     }
 
     fn get_db_header(&mut self) -> DBHeader {
-        DBHeader::parse(self.sqlite_file.load_db_header())
+        match &self.db_header {
+            Some(db_header) => db_header.clone(),
+            None => {
+                let db_header = DBHeader::parse(self.sqlite_file.load_db_header());
+                self.db_header = Some(db_header.clone());
+                db_header
+            }
+        }
     }
 
-    fn get_page(&mut self, page_no: u32) -> Page {
-        let page_size = self.get_db_header().page_size as usize;
+    /// Re-reads the header to detect modifications by other processes:
+    /// the cached pages are dropped when the file change counter moved,
+    /// the cached schema when the schema cookie did.
+    fn revalidate(&mut self) {
+        let db_header = DBHeader::parse(self.sqlite_file.load_db_header());
+
+        if let Some(cached) = &self.db_header {
+            if cached.file_change_counter != db_header.file_change_counter {
+                self.page_cache.clear();
+            }
+            if cached.schema_cookie != db_header.schema_cookie {
+                self.sqlite_schema = None;
+            }
+        }
+
+        self.db_header = Some(db_header);
+    }
+
+    /// Reads the header before modifying the file.
+    fn begin_write(&mut self) -> DBHeader {
+        let mut db_header = self.get_db_header();
+        if !db_header.is_page_cnt_valid() {
+            let page_size = db_header.page_size as u64;
+            db_header.page_cnt = (self.sqlite_file.file_size() / page_size) as u32;
+        }
+        db_header
+    }
+
+    /// Persists the header after modifying the file.
+    /// Pages were written to the file directly, so the cached ones are stale.
+    fn end_write(&mut self, mut db_header: DBHeader) {
+        db_header.file_change_counter = db_header.file_change_counter.wrapping_add(1);
+        db_header.version_valid_for = db_header.file_change_counter;
+
+        let mut bytes = self.sqlite_file.load_db_header();
+        db_header.write(&mut bytes);
+        self.sqlite_file.write_db_header(&bytes);
+
+        self.page_cache.clear();
+        self.db_header = Some(db_header);
+    }
+
+    fn get_page(&mut self, page_no: u32, db_header: &DBHeader) -> Page {
+        Page::new(self.load_raw_page(page_no, db_header))
+    }
+
+    fn load_raw_page(&mut self, page_no: u32, db_header: &DBHeader) -> Rc<RawPage> {
+        if let Some(raw_page) = self.page_cache.get(page_no) {
+            return raw_page;
+        }
+
+        let page_size = db_header.page_size as usize;
         let bytes = self.sqlite_file.load_page(page_no, page_size);
-        Page::parse(bytes, page_no)
+        let raw_page = Rc::new(RawPage::parse(bytes, page_no, db_header.usable_size()));
+        self.page_cache.insert(page_no, Rc::clone(&raw_page));
+        raw_page
+    }
+
+    pub(super) fn get_raw_page(&mut self, page_no: u32) -> Rc<RawPage> {
+        let db_header = self.get_db_header();
+        self.load_raw_page(page_no, &db_header)
+    }
+
+    pub(super) fn overflow_reader(&mut self) -> OverflowReader<'_> {
+        let db_header = self.get_db_header();
+        OverflowReader::new(&mut self.sqlite_file, &db_header)
+    }
+
+    /// Opens a cursor on the table or index b-tree rooted at `rootpage`.
+    pub fn cursor(&mut self, rootpage: u32) -> BTreeCursor<'_> {
+        BTreeCursor::new(self, rootpage)
     }
 }
 
@@ -33,36 +121,82 @@ impl Storage for SQLiteStorage {
     }
 
     fn get_schema(&mut self) -> SQLiteSchema {
+        self.revalidate();
+        if let Some(sqlite_schema) = &self.sqlite_schema {
+            return sqlite_schema.clone();
+        }
+
         let records = self.search_table(1, None);
         let sqlite_objects = records.into_iter().map(|r| r.into()).collect();
-        SQLiteSchema::new(sqlite_objects)
+        let sqlite_schema = SQLiteSchema::new(sqlite_objects);
+        self.sqlite_schema = Some(sqlite_schema.clone());
+        sqlite_schema
     }
 
     fn search_table(&mut self, page_no: u32, rowids: Option<&[i64]>) -> Vec<Record> {
-        let Page::Table(page) = self.get_page(page_no) else {
-            panic!("internal error");
+        let db_header = self.get_db_header();
+        let page = match self.get_page(page_no, &db_header) {
+            Page::Table(page) => page,
+            Page::Index(_) if rowids.is_none() => return self.scan_table(page_no, None).collect(),
+            Page::Index(_) => panic!("internal error: rowids of a WITHOUT ROWID table"),
         };
 
         match page {
-            TablePage::Leaf(page) => page.get_records(rowids),
+            TablePage::Leaf(page) => {
+                let mut overflow_reader = OverflowReader::new(&mut self.sqlite_file, &db_header);
+                page.get_records(rowids, &mut overflow_reader)
+            }
             TablePage::Interior(page) => page
                 .get_buckets(rowids)
                 .into_iter()
-                .map(|(ptr, rowids)| self.search_table(ptr, rowids))
-                .flatten()
+                .flat_map(|(ptr, rowids)| self.search_table(ptr, rowids))
                 .collect(),
         }
     }
 
+    fn scan_table<'a>(
+        &'a mut self,
+        page_no: u32,
+        rowids: Option<Vec<i64>>,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        let is_index = !self.get_raw_page(page_no).page_header.page_type.is_table();
+        let mut cursor = self.cursor(page_no);
+
+        match rowids {
+            None => {
+                let mut is_valid = cursor.first();
+                Box::new(iter::from_fn(move || {
+                    if !is_valid {
+                        return None;
+                    }
+                    // A WITHOUT ROWID table is stored as an index b-tree keyed by its primary key
+                    let record = if is_index {
+                        Record::new(0, cursor.key())
+                    } else {
+                        cursor.record()
+                    };
+                    is_valid = cursor.next();
+                    Some(record)
+                }))
+            }
+            Some(rowids) => Box::new(rowids.into_iter().filter_map(move |rowid| {
+                let found = cursor.seek_rowid(rowid) && cursor.rowid() == rowid;
+                found.then(|| cursor.record())
+            })),
+        }
+    }
+
     fn search_index(&mut self, page_no: u32, value: &Value) -> Vec<i64> {
-        let Page::Index(page) = self.get_page(page_no) else {
+        let db_header = self.get_db_header();
+        let Page::Index(page) = self.get_page(page_no, &db_header) else {
             panic!("internal error");
         };
 
+        let mut overflow_reader = OverflowReader::new(&mut self.sqlite_file, &db_header);
         match page {
-            IndexPage::Leaf(page) => page.get_rowids(value),
+            IndexPage::Leaf(page) => page.get_rowids(value, &mut overflow_reader),
             IndexPage::Interior(page) => {
-                let (ptrs, rowids) = page.get_children(value);
+                let (ptrs, rowids) = page.get_children(value, &mut overflow_reader);
 
                 let mut results: Vec<i64> = vec![];
                 for i in 0..rowids.len() {
@@ -74,17 +208,139 @@ impl Storage for SQLiteStorage {
             }
         }
     }
+
+    fn search_index_range(&mut self, page_no: u32, start: &Value, end: &Value) -> Vec<i64> {
+        let mut cursor = BTreeCursor::new(self, page_no);
+        let mut rowids = vec![];
+
+        let mut is_valid = cursor.seek_key(slice::from_ref(start));
+        while is_valid {
+            let record = cursor.record();
+            if record.values[0].compare(end).is_ge() {
+                break;
+            }
+            rowids.push(record.rowid);
+            is_valid = cursor.next();
+        }
+        rowids
+    }
+
+    fn max_rowid(&mut self, page_no: u32) -> Option<i64> {
+        let db_header = self.get_db_header();
+        let Page::Table(page) = self.get_page(page_no, &db_header) else {
+            panic!("internal error");
+        };
+
+        match page {
+            TablePage::Leaf(page) => page.max_rowid(),
+            TablePage::Interior(page) => self.max_rowid(page.get_right_most_ptr()),
+        }
+    }
+
+    fn count_leaf_pages(&mut self, page_no: u32) -> usize {
+        let page = self.get_raw_page(page_no);
+        if !page.page_header.page_type.is_interior() {
+            return 1;
+        }
+
+        // All leaves are at the same depth, only the pages above them are read
+        let child_cnt = page.cell_ptr_arr.len() + 1;
+        let first_child = self.get_raw_page(get_child(&page, 0));
+        if !first_child.page_header.page_type.is_interior() {
+            return child_cnt;
+        }
+
+        (0..child_cnt)
+            .map(|idx| self.count_leaf_pages(get_child(&page, idx)))
+            .sum()
+    }
+
+    fn insert_record(&mut self, page_no: u32, record: &Record) {
+        let mut db_header = self.begin_write();
+        BTreeWriter::new(&mut self.sqlite_file, &mut db_header).insert_record(page_no, record);
+        self.end_write(db_header);
+    }
+
+    fn insert_index_key(&mut self, page_no: u32, key: &[Value]) {
+        let mut db_header = self.begin_write();
+        BTreeWriter::new(&mut self.sqlite_file, &mut db_header).insert_index_key(page_no, key);
+        self.end_write(db_header);
+    }
+
+    fn delete_record(&mut self, page_no: u32, rowid: i64) {
+        let mut db_header = self.begin_write();
+        BTreeWriter::new(&mut self.sqlite_file, &mut db_header).delete_record(page_no, rowid);
+        self.end_write(db_header);
+    }
+
+    fn delete_index_key(&mut self, page_no: u32, key: &[Value]) {
+        let mut db_header = self.begin_write();
+        BTreeWriter::new(&mut self.sqlite_file, &mut db_header).delete_index_key(page_no, key);
+        self.end_write(db_header);
+    }
+
+    /// The cached pages may borrow from a mapping of the file, which must not outlive the writes.
+    fn begin_transaction(&mut self) -> Result<(), String> {
+        let page_size = self.get_db_header().page_size as usize;
+        self.sqlite_file.begin_transaction(page_size)?;
+        self.page_cache.clear();
+        Ok(())
+    }
+
+    fn commit(&mut self) {
+        self.sqlite_file.commit();
+    }
+
+    fn rollback(&mut self) {
+        self.sqlite_file.rollback();
+
+        self.page_cache.clear();
+        self.db_header = None;
+        self.sqlite_schema = None;
+    }
+
+    fn begin_statement(&mut self) {
+        let page_size = self.get_db_header().page_size as usize;
+        self.sqlite_file.begin_statement(page_size);
+    }
+
+    fn commit_statement(&mut self) {
+        self.sqlite_file.commit_statement();
+    }
+
+    fn rollback_statement(&mut self) {
+        self.sqlite_file.rollback_statement();
+
+        self.page_cache.clear();
+        self.db_header = None;
+        self.sqlite_schema = None;
+    }
+
+    /// Positive values are a number of pages, negative values an amount of KiB.
+    fn set_cache_size(&mut self, cache_size: i64) {
+        let capacity = if cache_size >= 0 {
+            cache_size as usize
+        } else {
+            let page_size = self.get_db_header().page_size as usize;
+            cache_size.unsigned_abs() as usize * 1024 / page_size
+        };
+        self.page_cache.set_capacity(capacity);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, path::PathBuf};
+    use std::{
+        fs::{self, File},
+        path::PathBuf,
+    };
 
     use itertools::Itertools;
 
     use crate::{
-        engine::{Storage, Value},
-        sqlite_file::SQLiteFile,
+        engine::{new_engine, Storage, Value},
+        sqlite_file::{FileBackend, SQLiteFile},
+        test_util::copy_db,
     };
 
     use super::SQLiteStorage;
@@ -154,6 +410,18 @@ mod tests {
         assert_eq!(superheroes.len(), 6895);
     }
 
+    #[test]
+    fn count_leaf_pages() {
+        let mut sqlite_storage = construct_sqlite_storage("dbs/shop.db");
+
+        let rootpage = get_rootpage(&mut sqlite_storage, "customers");
+        assert_eq!(sqlite_storage.count_leaf_pages(rootpage), 1);
+        let rootpage = get_rootpage(&mut sqlite_storage, "orders");
+        assert_eq!(sqlite_storage.count_leaf_pages(rootpage), 9);
+        let rootpage = get_rootpage(&mut sqlite_storage, "idx_orders_code");
+        assert_eq!(sqlite_storage.count_leaf_pages(rootpage), 7);
+    }
+
     #[test]
     fn search_index_mountains() {
         let mut sqlite_storage = construct_sqlite_storage("dbs/mountains.db");
@@ -164,6 +432,22 @@ mod tests {
         assert_eq!(rowids.len(), 2);
     }
 
+    #[test]
+    fn search_index_range() {
+        let mut sqlite_storage = construct_sqlite_storage("dbs/mountains.db");
+        let rootpage = get_rootpage(&mut sqlite_storage, "idx_mountains_country");
+        let (start, end) = (Value::Text("I".into()), Value::Text("S".into()));
+        let rowids = sqlite_storage.search_index_range(rootpage, &start, &end);
+        assert_eq!(rowids, [7, 15, 11]);
+
+        // The keys span several pages
+        let mut sqlite_storage = construct_sqlite_storage("dbs/shop.db");
+        let rootpage = get_rootpage(&mut sqlite_storage, "idx_orders_code");
+        let (start, end) = (Value::Text("O01".into()), Value::Text("O02".into()));
+        let rowids = sqlite_storage.search_index_range(rootpage, &start, &end);
+        assert_eq!(rowids, (100..200).collect::<Vec<_>>());
+    }
+
     #[test]
     fn search_index_companies() {
         let mut sqlite_storage = construct_sqlite_storage("companies.db");
@@ -232,4 +516,47 @@ mod tests {
             assert_eq!(record.values[7].to_string(), "eritrea");
         }
     }
+
+    #[test]
+    fn search_table_overflow() {
+        let mut sqlite_storage = construct_sqlite_storage("dbs/articles.db");
+
+        let rootpage = get_rootpage(&mut sqlite_storage, "articles");
+        let records = sqlite_storage.search_table(rootpage, None);
+
+        let body_lens: Vec<_> = records
+            .iter()
+            .map(|record| record.values[2].to_string().len())
+            .collect();
+        assert_eq!(body_lens, [16, 1007, 5005, 20010]);
+        assert!(records[3].values[2].to_string().ends_with("efef"));
+    }
+
+    #[test]
+    fn search_index_overflow() {
+        let mut sqlite_storage = construct_sqlite_storage("dbs/articles.db");
+
+        let body = String::from("long ") + &"cd".repeat(2500);
+        let rootpage = get_rootpage(&mut sqlite_storage, "idx_articles_body");
+        let rowids = sqlite_storage.search_index(rootpage, &Value::Text(body));
+        assert_eq!(rowids, [3]);
+    }
+
+    #[test]
+    fn page_cache_invalidated_by_other_writer() {
+        let path = copy_db("dbs/mountains.db", "page_cache_invalidated_by_other_writer");
+        let mut sqlite_storage = SQLiteStorage::new(SQLiteFile::open(&path, FileBackend::Pread));
+        let rootpage = get_rootpage(&mut sqlite_storage, "mountains");
+        assert_eq!(sqlite_storage.search_table(rootpage, None).len(), 15);
+
+        let mut engine = new_engine(path.to_str().unwrap());
+        let sql = "INSERT INTO mountains (name) VALUES ('Olympus')";
+        engine.exec_sql(sql).unwrap();
+
+        // Every statement starts by loading the schema, which revalidates the cache
+        let rootpage = get_rootpage(&mut sqlite_storage, "mountains");
+        assert_eq!(sqlite_storage.search_table(rootpage, None).len(), 16);
+
+        fs::remove_file(path).unwrap();
+    }
 }
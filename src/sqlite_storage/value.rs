@@ -7,7 +7,13 @@ pub fn parse_integer(serial_type: i64, window: &mut &[u8]) -> Value {
         _ => serial_type as usize,
     };
 
-    let mut arr = [0; 8];
+    // Sign-extend the big-endian two's complement integer
+    let fill = if size > 0 && (window[0] as i8) < 0 {
+        0xff
+    } else {
+        0
+    };
+    let mut arr = [fill; 8];
     for (i, byte) in window[..size].iter().enumerate() {
         arr[arr.len() - size + i] = *byte;
     }
@@ -36,11 +42,39 @@ pub fn parse_text_or_blob(serial_type: i64, window: &mut &[u8]) -> Value {
     }
 }
 
+/// Returns the serial type and the body bytes of a value.
+/// https://www.sqlite.org/fileformat.html#record_format
+pub fn serialize_value(value: &Value) -> (i64, Vec<u8>) {
+    match value {
+        Value::Null => (0, vec![]),
+        Value::Integer(0) => (8, vec![]),
+        Value::Integer(1) => (9, vec![]),
+        Value::Integer(integer) => serialize_integer(*integer),
+        Value::Real(real) => (7, real.to_be_bytes().to_vec()),
+        Value::Text(text) => (text.len() as i64 * 2 + 13, text.as_bytes().to_vec()),
+        Value::Blob(blob) => (blob.len() as i64 * 2 + 12, blob.clone()),
+    }
+}
+
+fn serialize_integer(integer: i64) -> (i64, Vec<u8>) {
+    let (serial_type, size) = match integer {
+        -0x80..=0x7f => (1, 1),
+        -0x8000..=0x7fff => (2, 2),
+        -0x80_0000..=0x7f_ffff => (3, 3),
+        -0x8000_0000..=0x7fff_ffff => (4, 4),
+        -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+        _ => (6, 8),
+    };
+
+    let bytes = integer.to_be_bytes();
+    (serial_type, bytes[bytes.len() - size..].to_vec())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::engine::Value;
 
-    use super::parse_integer;
+    use super::{parse_integer, serialize_value};
 
     #[test]
     fn parse_integer_ok() {
@@ -48,4 +82,13 @@ mod tests {
         let res = parse_integer(3, &mut &bytes[..]);
         assert_eq!(res, Value::Integer(1332971));
     }
+
+    #[test]
+    fn serialize_integer_roundtrip() {
+        for integer in [2, -1, 300, -70000, 1 << 40, i64::MIN] {
+            let (serial_type, bytes) = serialize_value(&Value::Integer(integer));
+            let res = parse_integer(serial_type, &mut &bytes[..]);
+            assert_eq!(res, Value::Integer(integer));
+        }
+    }
 }
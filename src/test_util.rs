@@ -0,0 +1,10 @@
+use std::{fs, path::PathBuf};
+
+/// Copies a database to a temporary file so that tests can modify it.
+pub fn copy_db(db_file_rel_path: &str, test_name: &str) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let file_name = format!("sqlite-rust-{}-{}.db", test_name, std::process::id());
+    let path = std::env::temp_dir().join(file_name);
+    fs::copy(root.join(db_file_rel_path), &path).unwrap();
+    path
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::bytes::from_be_bytes::from_be_bytes;
+
+const HEADER_LEN: usize = 32;
+const FRAME_HEADER_LEN: usize = 24;
+
+/// Reader for the write-ahead log of a database in WAL mode.
+///
+/// Committed transactions are appended to the WAL as frames (a frame header followed by a page)
+/// and only copied back into the database file on checkpoint.
+/// A page therefore has to be read from the latest committed frame holding it, if there is one.
+/// https://www.sqlite.org/fileformat.html#the_write_ahead_log
+#[derive(Debug)]
+pub struct Wal {
+    file: File,
+    page_size: usize,
+    // Page number to the offset of the page content in the latest committed frame
+    frames: HashMap<u32, u64>,
+    // Database size in pages after the last commit
+    db_page_cnt: u32,
+}
+
+impl Wal {
+    /// Returns None if there is no WAL or it contains no committed frames.
+    pub fn open(path: &Path) -> Option<Self> {
+        let mut file = File::open(path).ok()?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+
+        let (frames, page_size, db_page_cnt) = parse_frames(&bytes)?;
+        if frames.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            file,
+            page_size,
+            frames,
+            db_page_cnt,
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn db_page_cnt(&self) -> u32 {
+        self.db_page_cnt
+    }
+
+    pub fn load_page(&mut self, page_no: u32) -> Option<Vec<u8>> {
+        let offset = *self.frames.get(&page_no)?;
+
+        self.file.seek(SeekFrom::Start(offset)).unwrap();
+        let mut page = vec![0; self.page_size];
+        self.file.read_exact(&mut page).unwrap();
+        Some(page)
+    }
+}
+
+/// Path of the write-ahead log belonging to a database file
+pub fn wal_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push("-wal");
+    PathBuf::from(path)
+}
+
+/// Validates the frames and maps every page to its latest committed frame.
+/// Frames are valid as long as their salts match the header and the cumulative checksum matches,
+/// frames after the last commit frame belong to an unfinished transaction and are ignored.
+fn parse_frames(bytes: &[u8]) -> Option<(HashMap<u32, u64>, usize, u32)> {
+    let header = bytes.get(..HEADER_LEN)?;
+    let window = &mut &header[..];
+    let magic: u32 = from_be_bytes(window);
+    let _version: u32 = from_be_bytes(window);
+    let page_size: u32 = from_be_bytes(window);
+    let _checkpoint_seq: u32 = from_be_bytes(window);
+    let salt: [u8; 8] = from_be_bytes(window);
+    let header_checksum: [u32; 2] = [from_be_bytes(window), from_be_bytes(window)];
+
+    // The lowest bit of the magic number selects the byte order of the checksums
+    let big_endian = match magic {
+        0x377f0682 => false,
+        0x377f0683 => true,
+        _ => return None,
+    };
+
+    let mut checksum = wal_checksum([0, 0], &header[..24], big_endian);
+    if checksum != header_checksum {
+        return None;
+    }
+
+    let page_size = match page_size {
+        1 => 65536,
+        page_size => page_size as usize,
+    };
+    let frame_len = FRAME_HEADER_LEN + page_size;
+
+    let mut frames = HashMap::new();
+    let mut pending = vec![];
+    let mut db_page_cnt = 0;
+
+    let mut offset = HEADER_LEN;
+    while let Some(frame) = bytes.get(offset..offset + frame_len) {
+        let window = &mut &frame[..];
+        let page_no: u32 = from_be_bytes(window);
+        let commit_page_cnt: u32 = from_be_bytes(window);
+        let frame_salt: [u8; 8] = from_be_bytes(window);
+        let frame_checksum: [u32; 2] = [from_be_bytes(window), from_be_bytes(window)];
+
+        if frame_salt != salt {
+            break;
+        }
+
+        checksum = wal_checksum(checksum, &frame[..8], big_endian);
+        checksum = wal_checksum(checksum, &frame[FRAME_HEADER_LEN..], big_endian);
+        if checksum != frame_checksum {
+            break;
+        }
+
+        pending.push((page_no, (offset + FRAME_HEADER_LEN) as u64));
+        if commit_page_cnt != 0 {
+            frames.extend(pending.drain(..));
+            db_page_cnt = commit_page_cnt;
+        }
+
+        offset += frame_len;
+    }
+
+    Some((frames, page_size, db_page_cnt))
+}
+
+/// Fletcher-like checksum over 32-bit words, continuing from `checksum`.
+fn wal_checksum(checksum: [u32; 2], bytes: &[u8], big_endian: bool) -> [u32; 2] {
+    let [mut s0, mut s1] = checksum;
+
+    for words in bytes.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (
+                u32::from_be_bytes(words[..4].try_into().unwrap()),
+                u32::from_be_bytes(words[4..].try_into().unwrap()),
+            )
+        } else {
+            (
+                u32::from_le_bytes(words[..4].try_into().unwrap()),
+                u32::from_le_bytes(words[4..].try_into().unwrap()),
+            )
+        };
+
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+
+    [s0, s1]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::parse_frames;
+
+    fn read_wal() -> Vec<u8> {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fs::read(root.join("dbs/birds.db-wal")).unwrap()
+    }
+
+    #[test]
+    fn parse_frames_committed() {
+        let bytes = read_wal();
+        let (frames, page_size, db_page_cnt) = parse_frames(&bytes).unwrap();
+
+        assert_eq!(page_size, 1024);
+        assert!(db_page_cnt > 4);
+        assert!(frames.contains_key(&1));
+    }
+
+    #[test]
+    fn parse_frames_stops_at_corrupt_frame() {
+        let mut bytes = read_wal();
+        let (frames, _, _) = parse_frames(&bytes).unwrap();
+
+        // Corrupting the first frame invalidates it and every later frame
+        bytes[32 + 24] ^= 0xff;
+        let (corrupt_frames, _, db_page_cnt) = parse_frames(&bytes).unwrap();
+        assert!(!frames.is_empty());
+        assert!(corrupt_frames.is_empty());
+        assert_eq!(db_page_cnt, 0);
+    }
+}
@@ -1,8 +1,14 @@
+use std::{cell::Cell, rc::Rc};
+
 use sqlite_starter_rust::engine::{
     DBInfo, Engine, Record, SQLiteObject, SQLiteObjectType, SQLiteSchema, Storage, Value,
 };
 
-struct MockStorage;
+#[derive(Default)]
+struct MockStorage {
+    // Number of records pulled from scan_table
+    scanned: Rc<Cell<usize>>,
+}
 
 impl Storage for MockStorage {
     fn get_dbinfo(&mut self) -> DBInfo {
@@ -57,14 +63,84 @@ impl Storage for MockStorage {
             .collect()
     }
 
+    fn scan_table<'a>(
+        &'a mut self,
+        rootpage: u32,
+        rowids: Option<Vec<i64>>,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        let scanned = Rc::clone(&self.scanned);
+        let records = self.search_table(rootpage, rowids.as_deref());
+        Box::new(
+            records
+                .into_iter()
+                .inspect(move |_| scanned.set(scanned.get() + 1)),
+        )
+    }
+
     fn search_index(&mut self, _rootpage: u32, _value: &Value) -> Vec<i64> {
         unimplemented!()
     }
+
+    fn search_index_range(&mut self, _rootpage: u32, _start: &Value, _end: &Value) -> Vec<i64> {
+        unimplemented!()
+    }
+
+    fn max_rowid(&mut self, _rootpage: u32) -> Option<i64> {
+        unimplemented!()
+    }
+
+    fn count_leaf_pages(&mut self, _rootpage: u32) -> usize {
+        unimplemented!()
+    }
+
+    fn insert_record(&mut self, _rootpage: u32, _record: &Record) {
+        unimplemented!()
+    }
+
+    fn insert_index_key(&mut self, _rootpage: u32, _key: &[Value]) {
+        unimplemented!()
+    }
+
+    fn delete_record(&mut self, _rootpage: u32, _rowid: i64) {
+        unimplemented!()
+    }
+
+    fn delete_index_key(&mut self, _rootpage: u32, _key: &[Value]) {
+        unimplemented!()
+    }
+
+    fn begin_transaction(&mut self) -> Result<(), String> {
+        unimplemented!()
+    }
+
+    fn commit(&mut self) {
+        unimplemented!()
+    }
+
+    fn rollback(&mut self) {
+        unimplemented!()
+    }
+
+    fn begin_statement(&mut self) {
+        unimplemented!()
+    }
+
+    fn commit_statement(&mut self) {
+        unimplemented!()
+    }
+
+    fn rollback_statement(&mut self) {
+        unimplemented!()
+    }
+
+    fn set_cache_size(&mut self, _cache_size: i64) {
+        unimplemented!()
+    }
 }
 
 #[test]
 fn exec_select() {
-    let mut engine = Engine::new(MockStorage);
+    let mut engine = Engine::new(MockStorage::default());
     let sql = "SELECT title, author, year_published FROM books";
 
     let table = engine.exec_sql(sql).unwrap();
@@ -87,7 +163,7 @@ fn exec_select() {
 
 #[test]
 fn exec_select_count() {
-    let mut engine = Engine::new(MockStorage);
+    let mut engine = Engine::new(MockStorage::default());
     let sql = "SELECT COUNT(*) FROM books";
 
     let table = engine.exec_sql(sql).unwrap();
@@ -96,7 +172,7 @@ fn exec_select_count() {
 
 #[test]
 fn exec_select_with_where_1() {
-    let mut engine = Engine::new(MockStorage);
+    let mut engine = Engine::new(MockStorage::default());
     let sql = "SELECT title, author, year_published FROM books WHERE genre = 'Dystopian'";
 
     let table = engine.exec_sql(sql).unwrap();
@@ -113,7 +189,7 @@ fn exec_select_with_where_1() {
 
 #[test]
 fn exec_select_with_where_2() {
-    let mut engine = Engine::new(MockStorage);
+    let mut engine = Engine::new(MockStorage::default());
     let sql = "SELECT title, author, year_published FROM books WHERE author = 'Harper Lee' OR author = 'Jane Austen'";
 
     let table = engine.exec_sql(sql).unwrap();
@@ -130,3 +206,15 @@ fn exec_select_with_where_2() {
         assert_eq!(record.values[1].to_string(), want.1);
     }
 }
+
+#[test]
+fn exec_select_limit_stops_scan() {
+    let storage = MockStorage::default();
+    let scanned = Rc::clone(&storage.scanned);
+    let mut engine = Engine::new(storage);
+    let sql = "SELECT title FROM books WHERE year_published < 1950 LIMIT 1";
+
+    let table = engine.exec_sql(sql).unwrap();
+    assert_eq!(table.to_string(), "1984");
+    assert_eq!(scanned.get(), 2);
+}